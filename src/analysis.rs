@@ -0,0 +1,278 @@
+//! Warnings about uncommitted files
+//!
+//! This module looks at the untracked and modified entries reported by
+//! `git status` for files that should never be committed: files over a size
+//! threshold, such as database dumps, and files whose names look like they
+//! hold secrets, such as `.env` files and private keys. The same walk finds
+//! the largest untracked file when a query needs it.
+
+use crate::core::{self, RepoStatus};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// Patterns for files that usually hold secrets
+pub const DEFAULT_SECRET_PATTERNS: [&str; 10] = [
+    ".env",
+    ".env.*",
+    "*.pem",
+    "*.key",
+    "*.p12",
+    "id_rsa",
+    "id_ecdsa",
+    "id_ed25519",
+    "*credentials*.json",
+    "service-account*.json",
+];
+
+/// Templates matching a default secret pattern that are routinely committed
+const NOT_SECRET_NAMES: [&str; 4] = [".env.example", ".env.sample", ".env.template", ".env.dist"];
+
+/// Files larger than this are flagged unless a threshold is given
+pub const DEFAULT_LARGE_FILE_THRESHOLD: u64 = 50 * 1000 * 1000;
+
+/// Why an uncommitted file was flagged
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "lowercase")]
+pub enum FileWarningKind {
+    /// Larger than the size threshold
+    Large { size: u64 },
+    /// Name matches a secret-like pattern
+    Secret { pattern: String },
+}
+
+/// An untracked or modified file that probably shouldn't be committed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileWarning {
+    /// Path relative to the repository root
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub kind: FileWarningKind,
+}
+
+/// Size threshold and secret patterns to check files against
+#[derive(Debug)]
+pub struct Analyzer {
+    threshold: u64,
+    patterns: Vec<String>,
+    secrets: GlobSet,
+    /// Whether to record the size of the largest untracked file
+    largest_untracked: bool,
+}
+
+impl Analyzer {
+    /// Creates an analyzer flagging files over `threshold` bytes and files
+    /// matching the default secret patterns or any of `extra_patterns`,
+    /// and recording the largest untracked file if `largest_untracked` is set
+    ///
+    /// Patterns match either the file name or the path relative to the
+    /// repository root. Invalid patterns are ignored, since the command line
+    /// validates them.
+    pub fn new(threshold: u64, extra_patterns: &[String], largest_untracked: bool) -> Analyzer {
+        let mut builder = GlobSetBuilder::new();
+        let mut patterns = Vec::new();
+        let all = DEFAULT_SECRET_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .chain(extra_patterns.iter().cloned());
+        for pattern in all {
+            if let Ok(glob) = Glob::new(&pattern) {
+                builder.add(glob);
+                patterns.push(pattern);
+            }
+        }
+        Analyzer {
+            threshold,
+            patterns,
+            secrets: builder.build().unwrap_or_else(|_| GlobSet::empty()),
+            largest_untracked,
+        }
+    }
+
+    /// Returns the first secret pattern matching a path, if any
+    ///
+    /// Templates such as `.env.example` don't match the default patterns.
+    fn secret_pattern(&self, path: &Path) -> Option<&str> {
+        let name = path.file_name()?;
+        let template = NOT_SECRET_NAMES.iter().any(|t| name == *t);
+        let mut matches = self.secrets.matches(name);
+        if matches.is_empty() {
+            matches = self.secrets.matches(path);
+        }
+        matches
+            .into_iter()
+            .find(|&i| !(template && i < DEFAULT_SECRET_PATTERNS.len()))
+            .map(|i| self.patterns[i].as_str())
+    }
+
+    /// Checks the untracked and modified files of a repository
+    ///
+    /// Untracked directories are reported by git as a single entry, so
+    /// they are walked to check each file inside. Returns the warnings and,
+    /// if requested, the size of the largest untracked file.
+    pub fn analyze(&self, repo_path: &Path, changes: &str) -> (Vec<FileWarning>, Option<u64>) {
+        let mut warnings = Vec::new();
+        let mut largest = None;
+        for line in changes.lines() {
+            let Some(entry) = changed_path(line) else {
+                continue;
+            };
+            let untracked = self.largest_untracked && line.starts_with("??");
+            for file in walk_files(&repo_path.join(entry)) {
+                let path = file
+                    .path()
+                    .strip_prefix(repo_path)
+                    .unwrap_or(file.path())
+                    .to_path_buf();
+                if let Some(pattern) = self.secret_pattern(&path) {
+                    warnings.push(FileWarning {
+                        path: path.clone(),
+                        kind: FileWarningKind::Secret {
+                            pattern: pattern.to_string(),
+                        },
+                    });
+                }
+                let size = file.metadata().map_or(0, |m| m.len());
+                if untracked {
+                    largest = largest.max(Some(size));
+                }
+                if size > self.threshold {
+                    warnings.push(FileWarning {
+                        path,
+                        kind: FileWarningKind::Large { size },
+                    });
+                }
+            }
+        }
+        (warnings, largest)
+    }
+
+    /// Adds file warnings, and the largest untracked file if requested, to
+    /// every dirty repository
+    pub fn analyze_all(&self, statuses: &mut [RepoStatus]) {
+        for status in statuses {
+            if let RepoStatus::Dirty {
+                path,
+                changes,
+                info,
+                ..
+            } = status
+            {
+                (info.file_warnings, info.largest_untracked) = self.analyze(path, changes);
+            }
+        }
+    }
+}
+
+/// Lists the files under a changed entry of `git status`
+///
+/// Nested repositories, including submodules, and `.git` directories
+/// are skipped, since their files aren't part of the repository.
+pub(crate) fn walk_files(entry: &Path) -> impl Iterator<Item = DirEntry> {
+    WalkDir::new(entry)
+        .into_iter()
+        .filter_entry(|e| {
+            e.file_name() != ".git" && !(e.file_type().is_dir() && e.path().join(".git").exists())
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+}
+
+/// Extracts the path of an untracked or modified entry from a line of
+/// `git status --porcelain`
+///
+/// Deleted entries are skipped, and renames yield their new path.
+fn changed_path(line: &str) -> Option<String> {
+    let (code, field) = (line.get(..2)?, line.get(3..)?);
+    if code.contains('D') || code == "!!" {
+        return None;
+    }
+    Some(core::porcelain_path(field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_changed_path() {
+        assert_eq!(changed_path("?? dump.sql").as_deref(), Some("dump.sql"));
+        assert_eq!(
+            changed_path(" M src/main.rs").as_deref(),
+            Some("src/main.rs")
+        );
+        assert_eq!(
+            changed_path("R  old.txt -> new.txt").as_deref(),
+            Some("new.txt")
+        );
+        assert_eq!(
+            changed_path("?? \"with space\"").as_deref(),
+            Some("with space")
+        );
+        assert_eq!(changed_path(" D gone.txt"), None);
+        assert_eq!(changed_path(""), None);
+    }
+
+    #[test]
+    fn test_secret_pattern() {
+        let analyzer = Analyzer::new(
+            DEFAULT_LARGE_FILE_THRESHOLD,
+            &["config/*.yml".to_string()],
+            false,
+        );
+        let pattern = |path: &str| analyzer.secret_pattern(Path::new(path));
+        assert_eq!(pattern(".env"), Some(".env"));
+        assert_eq!(pattern("deploy/.env.production"), Some(".env.*"));
+        assert_eq!(pattern("certs/server.pem"), Some("*.pem"));
+        assert_eq!(pattern("keys/id_rsa"), Some("id_rsa"));
+        assert_eq!(pattern("gcp-credentials.json"), Some("*credentials*.json"));
+        assert_eq!(pattern("config/secrets.yml"), Some("config/*.yml"));
+        assert_eq!(pattern("id_rsa.pub"), None);
+        assert_eq!(pattern(".env.example"), None);
+        assert_eq!(pattern("deploy/.env.sample"), None);
+        assert_eq!(pattern("src/env.rs"), None);
+    }
+
+    #[test]
+    fn test_analyze() {
+        let temp = tempfile::tempdir().unwrap();
+        let repo = temp.path();
+        fs::create_dir_all(repo.join("backups")).unwrap();
+        fs::write(repo.join("backups/dump.sql"), vec![0u8; 2048]).unwrap();
+        fs::write(repo.join(".env"), "TOKEN=x").unwrap();
+        fs::write(repo.join("notes.txt"), "hello").unwrap();
+        // A nested clone and a submodule, whose files aren't part of the repo
+        fs::create_dir_all(repo.join("inner/.git/objects")).unwrap();
+        fs::write(repo.join("inner/.git/objects/pack"), vec![0u8; 2048]).unwrap();
+        fs::write(repo.join("inner/big.bin"), vec![0u8; 2048]).unwrap();
+        fs::create_dir_all(repo.join("lib")).unwrap();
+        fs::write(repo.join("lib/.git"), "gitdir: ../.git/modules/lib").unwrap();
+        fs::write(repo.join("lib/big.bin"), vec![0u8; 2048]).unwrap();
+
+        let analyzer = Analyzer::new(1024, &[], true);
+        let (warnings, largest) = analyzer.analyze(
+            repo,
+            "?? backups/\n?? .env\n M notes.txt\n D x\n?? inner/\n M lib\n",
+        );
+        assert_eq!(
+            warnings,
+            [
+                FileWarning {
+                    path: PathBuf::from("backups/dump.sql"),
+                    kind: FileWarningKind::Large { size: 2048 },
+                },
+                FileWarning {
+                    path: PathBuf::from(".env"),
+                    kind: FileWarningKind::Secret {
+                        pattern: ".env".to_string(),
+                    },
+                },
+            ]
+        );
+        assert_eq!(largest, Some(2048));
+        let (_, largest) = Analyzer::new(1024, &[], false).analyze(repo, "?? backups/\n");
+        assert_eq!(largest, None);
+    }
+}
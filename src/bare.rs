@@ -0,0 +1,233 @@
+//! Bare repositories
+//!
+//! Bare repositories, such as mirrors kept on a server, have no working
+//! tree, so their status is made of refs-related state instead: how far
+//! each branch is ahead of or behind its upstream, or the remote it mirrors,
+//! and which branches have no upstream at all.
+
+use crate::core::{self, Details, HeadState, RepoInfo, RepoKind, RepoStatus};
+use crate::remote;
+use crate::worktree;
+use std::path::Path;
+
+/// Returns true if a directory has the layout of a bare repository
+///
+/// A bare repository holds `HEAD`, `objects` and `refs` directly. `.git`
+/// directories share that layout but belong to the repository above them.
+pub fn is_bare_repo(dir: &Path) -> bool {
+    dir.file_name().is_some_and(|name| name != ".git")
+        && dir.join("HEAD").is_file()
+        && dir.join("objects").is_dir()
+        && dir.join("refs").is_dir()
+}
+
+/// A local branch and its upstream, from `git for-each-ref`
+#[derive(Debug, PartialEq, Eq)]
+struct BranchRef {
+    name: String,
+    /// `None` when no upstream is configured or it no longer exists
+    tracking: Option<(usize, usize)>,
+}
+
+/// Parses `git for-each-ref --format=%(refname:short)%00%(upstream)%00%(upstream:track)`
+///
+/// The tracking column is empty when up to date, `[gone]` when the upstream
+/// ref is missing, and otherwise like `[ahead 1, behind 2]`.
+fn parse_branches(output: &str) -> Vec<BranchRef> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\0');
+            let name = fields.next()?.to_string();
+            let upstream = fields.next()?;
+            let track = fields.next().unwrap_or("");
+            let tracking = if upstream.is_empty() || track == "[gone]" {
+                None
+            } else {
+                let mut counts = (0, 0);
+                for part in track.trim_matches(|c| c == '[' || c == ']').split(", ") {
+                    if let Some(n) = part.strip_prefix("ahead ") {
+                        counts.0 = n.parse().unwrap_or(0);
+                    } else if let Some(n) = part.strip_prefix("behind ") {
+                        counts.1 = n.parse().unwrap_or(0);
+                    }
+                }
+                Some(counts)
+            };
+            Some(BranchRef { name, tracking })
+        })
+        .collect()
+}
+
+/// Parses `git config --bool --get-regexp '^remote\..*\.mirror$'` into
+/// the names of the remotes that are mirrored
+fn parse_mirrors(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter(|(_, value)| *value == "true")
+        .filter_map(|(key, _)| key.strip_prefix("remote.")?.strip_suffix(".mirror"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Compares the branches without an upstream with a mirrored remote
+///
+/// A fetch mirror, as made by `git clone --mirror`, stores the remote's
+/// branches as its own, so they are in sync by definition. Branches the
+/// remote also has a remote-tracking ref for, as push mirrors may, are
+/// counted against that ref instead.
+fn track_mirror(repo_path: &Path, mirror: &str, branches: &mut [BranchRef]) {
+    let prefix = format!("refs/remotes/{mirror}/");
+    let tracked = core::git_output(repo_path, &["for-each-ref", "--format=%(refname)", &prefix])
+        .unwrap_or_default();
+    for branch in branches.iter_mut().filter(|b| b.tracking.is_none()) {
+        let remote_ref = format!("{prefix}{}", branch.name);
+        let counts = if tracked.lines().any(|line| line == remote_ref) {
+            let range = format!("refs/heads/{}...{remote_ref}", branch.name);
+            core::git_output(repo_path, &["rev-list", "--left-right", "--count", &range]).and_then(
+                |counts| {
+                    let (ahead, behind) = counts.split_once('\t')?;
+                    Some((ahead.parse().ok()?, behind.parse().ok()?))
+                },
+            )
+        } else {
+            None
+        };
+        branch.tracking = Some(counts.unwrap_or((0, 0)));
+    }
+}
+
+/// Gets what HEAD points at in a bare repository
+fn get_head(repo_path: &Path) -> Option<HeadState> {
+    match core::git_output(repo_path, &["symbolic-ref", "--short", "-q", "HEAD"]) {
+        Some(name)
+            if core::git_output(repo_path, &["rev-parse", "-q", "--verify", "HEAD"]).is_some() =>
+        {
+            Some(HeadState::Branch { name })
+        }
+        Some(name) => Some(HeadState::Unborn { name }),
+        None => Some(core::get_detached_head(repo_path)),
+    }
+}
+
+/// Checks the refs of a bare repository
+///
+/// Ahead and behind counts are summed over the branches that track an
+/// upstream, and are `None` if no branch does. In a mirror, branches without
+/// an upstream track the mirrored remote's branch of the same name.
+pub fn check_bare_repo(repo_path: &Path, details: Details) -> RepoStatus {
+    let mut branches = core::git_output(
+        repo_path,
+        &[
+            "for-each-ref",
+            "--format=%(refname:short)%00%(upstream)%00%(upstream:track)",
+            "refs/heads",
+        ],
+    )
+    .map(|output| parse_branches(&output))
+    .unwrap_or_default();
+    let mirrors = core::git_output(
+        repo_path,
+        &["config", "--bool", "--get-regexp", r"^remote\..*\.mirror$"],
+    )
+    .map(|output| parse_mirrors(&output))
+    .unwrap_or_default();
+    if let Some(mirror) = mirrors.first() {
+        track_mirror(repo_path, mirror, &mut branches);
+    }
+
+    let tracked: Vec<(usize, usize)> = branches.iter().filter_map(|b| b.tracking).collect();
+    let (ahead, behind) = if tracked.is_empty() {
+        (None, None)
+    } else {
+        let sum = |pick: fn(&(usize, usize)) -> usize| Some(tracked.iter().map(pick).sum());
+        (sum(|t| t.0), sum(|t| t.1))
+    };
+    let head = get_head(repo_path);
+    let branch = head
+        .as_ref()
+        .and_then(HeadState::branch)
+        .filter(|_| details.branch)
+        .map(str::to_string);
+
+    RepoStatus::Clean {
+        path: repo_path.to_path_buf(),
+        branch,
+        info: RepoInfo {
+            kind: RepoKind::Bare,
+            ahead,
+            behind,
+            last_commit: details
+                .last_commit
+                .then(|| core::get_last_commit(repo_path))
+                .flatten(),
+            remotes: if details.remotes {
+                remote::get_remotes(repo_path)
+            } else {
+                Vec::new()
+            },
+            head,
+            missing_upstream: branches
+                .into_iter()
+                .filter(|b| b.tracking.is_none())
+                .map(|b| b.name)
+                .collect(),
+            worktrees: worktree::get_worktrees(repo_path, repo_path),
+            ..RepoInfo::default()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_is_bare_repo() {
+        let temp = tempfile::tempdir().unwrap();
+        let bare = temp.path().join("tools.git");
+        fs::create_dir_all(bare.join("objects")).unwrap();
+        fs::create_dir_all(bare.join("refs")).unwrap();
+        assert!(!is_bare_repo(&bare));
+        fs::write(bare.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        assert!(is_bare_repo(&bare));
+
+        let git_dir = temp.path().join("work/.git");
+        fs::create_dir_all(git_dir.join("objects")).unwrap();
+        fs::create_dir_all(git_dir.join("refs")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        assert!(!is_bare_repo(&git_dir));
+    }
+
+    #[test]
+    fn test_parse_mirrors() {
+        let output = "remote.origin.mirror true\nremote.backup.mirror false\n\
+                      remote.team.v2.mirror true";
+        assert_eq!(parse_mirrors(output), ["origin", "team.v2"]);
+        assert!(parse_mirrors("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_branches() {
+        let output = "main\0refs/remotes/origin/main\0[ahead 2, behind 1]\n\
+                      dev\0refs/remotes/origin/dev\0\n\
+                      old\0refs/remotes/origin/old\0[gone]\n\
+                      wip\0\0\n";
+        let branches = parse_branches(output);
+        let tracking: Vec<_> = branches
+            .iter()
+            .map(|b| (b.name.as_str(), b.tracking))
+            .collect();
+        assert_eq!(
+            tracking,
+            [
+                ("main", Some((2, 1))),
+                ("dev", Some((0, 0))),
+                ("old", None),
+                ("wip", None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,580 @@
+//! Persistent status and discovery caches
+//!
+//! This module stores each repository's last `RepoStatus` on disk together
+//! with a fingerprint of the files that can change it: `.git/index`,
+//! `HEAD`, the refs, the config and the modification times of the paths in
+//! the working tree that git doesn't ignore.
+//! Repositories whose fingerprint is unchanged are not re-checked.
+//!
+//! It also stores the list of discovered repositories per root and depth,
+//! along with the modification time of every directory that was walked, so
+//! later runs only re-read directories whose contents changed.
+
+use crate::bare;
+use crate::core::{self, Details, RepoStatus};
+use crate::error::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tempfile::NamedTempFile;
+use walkdir::WalkDir;
+
+/// Cache file format version
+///
+/// Bump this whenever `RepoStatus` gains fields, so stale entries are
+/// discarded instead of being read back with the new fields missing.
+const FORMAT_VERSION: u32 = 16;
+
+/// Name of the status cache file inside the cache directory
+const CACHE_FILE: &str = "status-cache.json";
+
+/// Name of the discovery cache file inside the cache directory
+const DISCOVERY_FILE: &str = "discovery-cache.json";
+
+/// Files inside `.git` whose changes affect the status
+const GIT_FILES: [&str; 5] = ["index", "HEAD", "packed-refs", "config", "logs/refs/stash"];
+
+/// Modification state of the files that determine a repository's status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// Modification time in nanoseconds and size of each file in `GIT_FILES`
+    git_files: Vec<Option<(u128, u64)>>,
+    /// Latest modification time below `.git/refs`
+    refs_mtime: u128,
+    /// Latest modification time in the working tree, excluding `.git`,
+    /// ignored paths and nested repositories
+    tree_mtime: u128,
+    /// Number of entries in the working tree, excluding the same paths
+    tree_entries: u64,
+}
+
+fn mtime_nanos(metadata: &fs::Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos())
+}
+
+/// Computes the latest modification time and entry count below a directory
+fn scan_tree(dir: &Path) -> (u128, u64) {
+    let mut latest = 0;
+    let mut entries = 0;
+    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+        if let Ok(metadata) = entry.metadata() {
+            latest = latest.max(mtime_nanos(&metadata));
+            entries += 1;
+        }
+    }
+    (latest, entries)
+}
+
+/// Computes the latest modification time and entry count of a working tree
+///
+/// Ignored paths aren't walked, see `core::walk_work_tree`.
+fn scan_work_tree(repo: &Path) -> (u128, u64) {
+    let mut latest = 0;
+    let mut entries = 0;
+    for entry in core::walk_work_tree(repo).filter_map(|e| e.ok()) {
+        if let Ok(metadata) = entry.metadata() {
+            latest = latest.max(mtime_nanos(&metadata));
+            entries += 1;
+        }
+    }
+    (latest, entries)
+}
+
+/// Computes the fingerprint of a repository
+///
+/// Returns `None` if the repository is bare or has no `.git` directory, or has
+/// submodules or linked worktrees whose state lives in other directories, in
+/// which case it is never cached. Repositories using LFS aren't cached
+/// either, see `StatusCache::insert`.
+pub fn fingerprint(repo: &Path) -> Option<Fingerprint> {
+    let git_dir = repo.join(".git");
+    if !git_dir.is_dir() || repo.join(".gitmodules").exists() || git_dir.join("worktrees").exists()
+    {
+        return None;
+    }
+
+    let git_files = GIT_FILES
+        .iter()
+        .map(|name| {
+            fs::metadata(git_dir.join(name))
+                .ok()
+                .map(|m| (mtime_nanos(&m), m.len()))
+        })
+        .collect();
+    let (refs_mtime, _) = scan_tree(&git_dir.join("refs"));
+    let (tree_mtime, tree_entries) = scan_work_tree(repo);
+
+    Some(Fingerprint {
+        git_files,
+        refs_mtime,
+        tree_mtime,
+        tree_entries,
+    })
+}
+
+/// A cached status and the fingerprint it was computed for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    fingerprint: Fingerprint,
+    details: Details,
+    status: RepoStatus,
+}
+
+/// On-disk cache file contents
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile<T> {
+    version: u32,
+    entries: T,
+}
+
+/// Hit and miss counts for one run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub entries: usize,
+}
+
+/// Status cache backed by a JSON file
+#[derive(Debug)]
+pub struct StatusCache {
+    path: PathBuf,
+    entries: HashMap<PathBuf, CacheEntry>,
+    stats: CacheStats,
+}
+
+impl StatusCache {
+    /// Gets the default cache file location under the XDG cache directory
+    pub fn default_path() -> Option<PathBuf> {
+        cache_file(CACHE_FILE)
+    }
+
+    /// Loads the cache, starting empty if the file is missing, unreadable
+    /// or written by an incompatible version
+    pub fn load(path: PathBuf) -> StatusCache {
+        let entries = read_entries(&path).unwrap_or_default();
+
+        StatusCache {
+            path,
+            entries,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Looks up a status computed for the same fingerprint
+    ///
+    /// An entry computed with more details also serves requests for fewer.
+    pub fn lookup(
+        &self,
+        repo: &Path,
+        fingerprint: &Fingerprint,
+        details: Details,
+    ) -> Option<RepoStatus> {
+        let entry = self.entries.get(repo)?;
+        if entry.fingerprint != *fingerprint || !entry.details.covers(&details) {
+            return None;
+        }
+
+        let mut status = entry.status.clone();
+        if !details.branch {
+            status.clear_branch();
+        }
+        Some(status)
+    }
+
+    /// Records a cache hit
+    pub fn record_hit(&mut self) {
+        self.stats.hits += 1;
+    }
+
+    /// Stores a freshly computed status, recording a cache miss
+    ///
+    /// Statuses of repositories using LFS aren't stored, since their objects
+    /// live outside the fingerprinted files.
+    pub fn insert(&mut self, fingerprint: Fingerprint, details: Details, status: RepoStatus) {
+        self.stats.misses += 1;
+        if status.info().lfs.is_some() {
+            self.entries.remove(status.path());
+            return;
+        }
+        self.entries.insert(
+            status.path().to_path_buf(),
+            CacheEntry {
+                fingerprint,
+                details,
+                status,
+            },
+        );
+    }
+
+    /// Gets the statistics for this run
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            ..self.stats
+        }
+    }
+
+    /// Gets the cache file location
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the cache back to disk, dropping repositories that no longer exist
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the cache file can't be written
+    pub fn save(&mut self) -> Result<()> {
+        self.entries.retain(|path, _| path.join(".git").is_dir());
+
+        write_atomic(&self.path, &self.entries)
+    }
+}
+
+/// Gets the location of a cache file under the XDG cache directory
+fn cache_file(name: &str) -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("check-git-status").join(name))
+}
+
+/// Reads the entries of a cache file
+///
+/// Returns `None` if the file is missing, unreadable or written by an
+/// incompatible version.
+fn read_entries<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let data = fs::read(path).ok()?;
+    let file: CacheFile<T> = serde_json::from_slice(&data).ok()?;
+    (file.version == FORMAT_VERSION).then_some(file.entries)
+}
+
+/// Writes cache entries, replacing the file at `path`
+fn write_atomic<T: Serialize>(path: &Path, entries: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = CacheFile {
+        version: FORMAT_VERSION,
+        entries,
+    };
+    let data = serde_json::to_vec(&file).map_err(std::io::Error::other)?;
+
+    // Write to a uniquely named temp file first so an interrupted run never
+    // leaves a truncated cache behind, and concurrent runs don't clobber
+    // each other's half-written files
+    let mut tmp = NamedTempFile::new_in(path.parent().unwrap_or(Path::new(".")))?;
+    tmp.write_all(&data)?;
+    tmp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Directories walked below one root and the repositories found in them
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Discovery {
+    /// Modification time of every walked directory, excluding `.git`
+    dirs: BTreeMap<PathBuf, u128>,
+    /// Directories containing a `.git` directory
+    repos: BTreeSet<PathBuf>,
+}
+
+impl Discovery {
+    /// Reads a directory and walks any subdirectories not seen before
+    ///
+    /// Directories at `max_depth - 1` are the deepest ones read, matching
+    /// the `.git` entries `find_git_repos` sees at `max_depth`. Returns the
+    /// number of directories read.
+    fn scan_dir(&mut self, dir: &Path, depth: usize, max_depth: usize) -> usize {
+        let Ok(metadata) = fs::metadata(dir) else {
+            return 0;
+        };
+        self.dirs.insert(dir.to_path_buf(), mtime_nanos(&metadata));
+        self.repos.remove(dir);
+        if bare::is_bare_repo(dir) {
+            self.repos.insert(dir.to_path_buf());
+            return 1;
+        }
+
+        let mut scanned = 1;
+        let Ok(entries) = fs::read_dir(dir) else {
+            return scanned;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+                continue;
+            }
+            let path = entry.path();
+            if entry.file_name() == ".git" {
+                self.repos.insert(dir.to_path_buf());
+            } else if bare::is_bare_repo(&path) {
+                self.repos.insert(path);
+            } else if depth + 1 < max_depth && !self.dirs.contains_key(&path) {
+                scanned += self.scan_dir(&path, depth + 1, max_depth);
+            }
+        }
+        scanned
+    }
+
+    /// Forgets a directory and everything below it
+    fn remove_subtree(&mut self, dir: &Path) {
+        self.dirs.retain(|d, _| !d.starts_with(dir));
+        self.repos.retain(|r| !r.starts_with(dir));
+    }
+
+    /// Re-reads directories whose modification time changed
+    ///
+    /// Adding, removing or renaming an entry updates the modification time
+    /// of its parent directory, so unchanged directories can't contain new
+    /// repositories. Returns the number of directories read.
+    fn update(&mut self, root: &Path, max_depth: usize) -> usize {
+        if self.dirs.is_empty() {
+            return self.scan_dir(root, 0, max_depth);
+        }
+
+        let mut scanned = 0;
+        let known: Vec<(PathBuf, u128)> = self.dirs.iter().map(|(d, m)| (d.clone(), *m)).collect();
+        for (dir, mtime) in known {
+            // Already dropped along with a removed parent
+            if !self.dirs.contains_key(&dir) {
+                continue;
+            }
+            match fs::metadata(&dir) {
+                Ok(metadata) if metadata.is_dir() => {
+                    if mtime_nanos(&metadata) != mtime {
+                        let depth = dir.strip_prefix(root).map_or(0, |r| r.components().count());
+                        scanned += self.scan_dir(&dir, depth, max_depth);
+                    }
+                }
+                _ => self.remove_subtree(&dir),
+            }
+        }
+
+        self.repos
+            .retain(|repo| repo.join(".git").is_dir() || bare::is_bare_repo(repo));
+        scanned
+    }
+}
+
+/// Cache of discovered repositories per root and depth
+#[derive(Debug)]
+pub struct DiscoveryCache {
+    path: PathBuf,
+    entries: HashMap<String, Discovery>,
+    scanned: usize,
+}
+
+impl DiscoveryCache {
+    /// Gets the default cache file location under the XDG cache directory
+    pub fn default_path() -> Option<PathBuf> {
+        cache_file(DISCOVERY_FILE)
+    }
+
+    /// Loads the cache, starting empty if the file is missing, unreadable
+    /// or written by an incompatible version
+    pub fn load(path: PathBuf) -> DiscoveryCache {
+        let entries = read_entries(&path).unwrap_or_default();
+
+        DiscoveryCache {
+            path,
+            entries,
+            scanned: 0,
+        }
+    }
+
+    /// Finds the repositories below a root, like `core::find_git_repos`
+    ///
+    /// Reuses the list from the previous run, re-reading only directories
+    /// that changed since. With `rescan`, the whole tree is walked again.
+    pub fn find_repos(&mut self, root: &Path, max_depth: usize, rescan: bool) -> Vec<PathBuf> {
+        let key = format!("{}:{}", max_depth, root.display());
+        if rescan {
+            self.entries.remove(&key);
+        }
+        let discovery = self.entries.entry(key).or_default();
+        self.scanned += discovery.update(root, max_depth);
+        discovery.repos.iter().cloned().collect()
+    }
+
+    /// Gets the number of directories read during this run
+    pub fn scanned(&self) -> usize {
+        self.scanned
+    }
+
+    /// Writes the cache back to disk
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the cache file can't be written
+    pub fn save(&self) -> Result<()> {
+        write_atomic(&self.path, &self.entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::RepoInfo;
+
+    fn init_repo(dir: &Path) {
+        fs::create_dir_all(dir.join(".git/refs/heads")).unwrap();
+        fs::write(dir.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(dir.join("README.md"), "hello").unwrap();
+    }
+
+    fn clean(path: &Path, branch: Option<&str>) -> RepoStatus {
+        RepoStatus::Clean {
+            path: path.to_path_buf(),
+            branch: branch.map(str::to_string),
+            info: RepoInfo::default(),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_working_tree() {
+        let temp = tempfile::tempdir().unwrap();
+        init_repo(temp.path());
+
+        let before = fingerprint(temp.path()).unwrap();
+        assert_eq!(fingerprint(temp.path()).unwrap(), before);
+
+        fs::write(temp.path().join("new.txt"), "x").unwrap();
+        assert_ne!(fingerprint(temp.path()).unwrap(), before);
+    }
+
+    #[test]
+    fn test_fingerprint_skips_ignored_paths_and_nested_repos() {
+        let temp = tempfile::tempdir().unwrap();
+        init_repo(temp.path());
+        fs::write(temp.path().join(".gitignore"), "target/\n").unwrap();
+        fs::create_dir_all(temp.path().join("target/debug")).unwrap();
+        init_repo(&temp.path().join("vendor/lib"));
+
+        let before = fingerprint(temp.path()).unwrap();
+        fs::write(temp.path().join("target/debug/app"), "x").unwrap();
+        fs::write(temp.path().join("vendor/lib/new.txt"), "x").unwrap();
+        assert_eq!(fingerprint(temp.path()).unwrap(), before);
+
+        fs::write(temp.path().join("vendor/notes.txt"), "x").unwrap();
+        assert_ne!(fingerprint(temp.path()).unwrap(), before);
+    }
+
+    #[test]
+    fn test_fingerprint_requires_git_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        assert!(fingerprint(temp.path()).is_none());
+    }
+
+    #[test]
+    fn test_lookup_and_detail_coverage() {
+        let temp = tempfile::tempdir().unwrap();
+        init_repo(temp.path());
+        let fp = fingerprint(temp.path()).unwrap();
+        let none = Details::default();
+        let branch = Details {
+            branch: true,
+            ..Details::default()
+        };
+
+        let mut cache = StatusCache::load(temp.path().join("cache.json"));
+        assert!(cache.lookup(temp.path(), &fp, none).is_none());
+
+        cache.insert(fp.clone(), Details::ALL, clean(temp.path(), Some("main")));
+        let hit = cache.lookup(temp.path(), &fp, none).unwrap();
+        assert_eq!(hit.branch(), None);
+        let hit = cache.lookup(temp.path(), &fp, branch).unwrap();
+        assert_eq!(hit.branch(), Some("main"));
+
+        cache.insert(fp.clone(), branch, clean(temp.path(), Some("main")));
+        assert!(cache.lookup(temp.path(), &fp, branch).is_some());
+        assert!(cache.lookup(temp.path(), &fp, Details::ALL).is_none());
+
+        cache.insert(fp.clone(), none, clean(temp.path(), None));
+        assert!(cache.lookup(temp.path(), &fp, branch).is_none());
+        assert_eq!(cache.stats().misses, 3);
+    }
+
+    #[test]
+    fn test_save_and_reload() {
+        let temp = tempfile::tempdir().unwrap();
+        let repo = temp.path().join("repo");
+        init_repo(&repo);
+        let fp = fingerprint(&repo).unwrap();
+        let cache_path = temp.path().join("cache/status.json");
+
+        let mut cache = StatusCache::load(cache_path.clone());
+        cache.insert(fp.clone(), Details::default(), clean(&repo, None));
+        cache.insert(
+            fp.clone(),
+            Details::default(),
+            clean(&temp.path().join("gone"), None),
+        );
+        cache.save().unwrap();
+
+        let cache = StatusCache::load(cache_path);
+        assert_eq!(cache.stats().entries, 1);
+        assert_eq!(
+            cache.lookup(&repo, &fp, Details::default()),
+            Some(clean(&repo, None))
+        );
+    }
+
+    #[test]
+    fn test_load_ignores_other_versions() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("cache.json");
+        fs::write(&path, r#"{"version":0,"entries":{}}"#).unwrap();
+        assert_eq!(StatusCache::load(path).stats().entries, 0);
+    }
+
+    #[test]
+    fn test_discovery_matches_full_walk() {
+        let temp = tempfile::tempdir().unwrap();
+        init_repo(&temp.path().join("a"));
+        init_repo(&temp.path().join("nested/b"));
+        init_repo(&temp.path().join("deep/x/y/c"));
+
+        let mut cache = DiscoveryCache::load(temp.path().join("discovery.json"));
+        for depth in 1..=5 {
+            let mut expected = crate::core::find_git_repos(temp.path(), depth);
+            expected.sort();
+            assert_eq!(cache.find_repos(temp.path(), depth, false), expected);
+        }
+    }
+
+    #[test]
+    fn test_discovery_rereads_changed_directories() {
+        let temp = tempfile::tempdir().unwrap();
+        init_repo(&temp.path().join("work/a"));
+        init_repo(&temp.path().join("oss/b"));
+        let cache_dir = tempfile::tempdir().unwrap();
+        let path = cache_dir.path().join("discovery.json");
+
+        let mut cache = DiscoveryCache::load(path.clone());
+        assert_eq!(cache.find_repos(temp.path(), 3, false).len(), 2);
+        cache.save().unwrap();
+
+        // Nothing changed, so no directory is read
+        let mut cache = DiscoveryCache::load(path.clone());
+        assert_eq!(cache.find_repos(temp.path(), 3, false).len(), 2);
+        assert_eq!(cache.scanned(), 0);
+
+        init_repo(&temp.path().join("work/c"));
+        fs::remove_dir_all(temp.path().join("oss/b")).unwrap();
+        let repos = cache.find_repos(temp.path(), 3, false);
+        assert_eq!(
+            repos,
+            [temp.path().join("work/a"), temp.path().join("work/c")]
+        );
+        assert!(cache.scanned() < 5);
+
+        let mut cache = DiscoveryCache::load(path);
+        cache.find_repos(temp.path(), 3, true);
+        assert_eq!(cache.scanned(), 5);
+    }
+}
@@ -3,18 +3,38 @@
 //! This module defines the CLI structure using clap and provides
 //! shell completion generation functionality.
 
+#[cfg(unix)]
+use crate::daemon;
 use crate::error::{Error, Result};
-use crate::output::{OutputFormat, Verbosity};
-use clap::{CommandFactory, Parser};
+use crate::exit::ExitCodeMode;
+use crate::output::{ColorChoice, OutputFormat, PathStyle, Verbosity};
+use crate::query;
+use crate::sort::{GroupKey, SortKey};
+use crate::watch::DEFAULT_DEBOUNCE_MS;
+use clap::{CommandFactory, Parser, ValueEnum};
 use clap_complete::{Shell, generate};
 use std::io;
 use std::path::PathBuf;
+use std::time::Duration;
 
 const VERSION: &str = "1.3.1";
 const DEFAULT_MAX_DEPTH: usize = 3;
 
+/// Requests that can be sent to a running daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ClientRequest {
+    /// Print the status of every repository
+    List,
+    /// Print the status of the repository containing the given path
+    Get,
+    /// Stream status changes until interrupted
+    Subscribe,
+    /// Re-discover and re-check all repositories
+    Refresh,
+}
+
 /// Recursively check git repository status
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 #[command(name = "check-git-status")]
 #[command(author, version = VERSION, about = "Check git repository status recursively")]
 #[command(override_usage = "check-git-status [OPTIONS] [path] [maxdepth]")]
@@ -27,7 +47,7 @@ pub struct Args {
     #[arg(value_name = "maxdepth")]
     pub maxdepth: Option<usize>,
 
-    /// Only exit code (0=all clean, N=dirty count)
+    /// Print nothing and only set the exit code (see --exit-code)
     #[arg(short = 'q', long = "quiet", conflicts_with = "verbose")]
     pub quiet: bool,
 
@@ -39,10 +59,221 @@ pub struct Args {
     #[arg(short = 'j', long = "json")]
     pub json: bool,
 
+    /// Show repos as a tree under their parent directories
+    #[arg(short = 't', long = "tree", conflicts_with = "json")]
+    pub tree: bool,
+
+    /// Expand directories in tree view even if all their repos are clean
+    #[arg(long = "expand", requires = "tree")]
+    pub expand: bool,
+
     /// Show branch names in output
     #[arg(short = 'b', long = "branch")]
     pub show_branch: bool,
 
+    /// How to display repo paths in verbose output
+    #[arg(long = "path-style", value_name = "STYLE", value_enum, default_value_t = PathStyle::Relative)]
+    pub path_style: PathStyle,
+
+    /// Only include repos matching an expression, e.g. 'dirty and age > 30d'
+    ///
+    /// Compares fields such as name, branch, changes, ahead, age (e.g. 30d,
+    /// 12h) or dirty, combined with and/or/not and parentheses. Text fields
+    /// support ==, != and the regex operators =~ and !~. The README lists
+    /// every field.
+    #[arg(short = 'w', long = "where", value_name = "EXPR")]
+    pub filter: Option<String>,
+
+    /// Only include repos with no commits for longer than a duration, e.g. 90d
+    #[arg(long = "stale", value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub stale: Option<Duration>,
+
+    /// Only include repos with a commit within a duration, e.g. 2w
+    #[arg(long = "active", value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub active: Option<Duration>,
+
+    /// Only include repos whose origin remote is on a host, e.g. github.com
+    #[arg(long = "host", value_name = "HOST")]
+    pub host: Option<String>,
+
+    /// Only include repos whose origin remote belongs to a user or organization
+    #[arg(long = "owner", value_name = "OWNER")]
+    pub owner: Option<String>,
+
+    /// Only include repos whose checked-out branch is merged into the
+    /// default branch and can be deleted
+    #[arg(long = "merged")]
+    pub merged: bool,
+
+    /// Count a repo as dirty when any of its submodules is dirty, at a
+    /// different commit than recorded, or at a commit not on any remote
+    #[arg(long = "roll-up-submodules")]
+    pub roll_up_submodules: bool,
+
+    /// Report the Git LFS state of every repo, not only those with
+    /// LFS-tracked files
+    #[arg(long = "lfs")]
+    pub lfs: bool,
+
+    /// Warn about uncommitted files larger than a size, e.g. 10MB [default: 50MB]
+    #[arg(long = "large-file-size", value_name = "SIZE", value_parser = parse_size)]
+    pub large_file_size: Option<u64>,
+
+    /// Warn about uncommitted files matching a glob, in addition to .env,
+    /// *.pem, id_rsa and the other built-in secret patterns (repeatable)
+    #[arg(long = "secret-pattern", value_name = "GLOB", value_parser = parse_glob)]
+    pub secret_patterns: Vec<String>,
+
+    /// Report the size of each repo's git directory, its loose objects and
+    /// packfiles, whether `git gc --auto` would run, and whether it is a
+    /// shallow clone or sparse checkout
+    #[arg(long = "housekeeping", conflicts_with_all = ["watch", "daemon", "client"])]
+    pub housekeeping: bool,
+
+    /// Run `git gc` on the repos that need it, in parallel (implies --housekeeping)
+    #[arg(long = "gc", conflicts_with_all = ["watch", "daemon", "client"])]
+    pub gc: bool,
+
+    /// Sort repos by key
+    #[arg(long = "sort", value_name = "KEY", value_enum, default_value_t = SortKey::Path)]
+    pub sort: SortKey,
+
+    /// Group repos by key, printing a header per group
+    #[arg(
+        long = "group-by",
+        value_name = "KEY",
+        value_enum,
+        conflicts_with_all = ["tree", "watch", "daemon"]
+    )]
+    pub group_by: Option<GroupKey>,
+
+    /// Keep running and re-check repos when their files change
+    #[arg(short = 'W', long = "watch", conflicts_with_all = ["json", "tree"])]
+    pub watch: bool,
+
+    /// Quiet period in milliseconds before changed repos are re-checked
+    /// (watch and daemon mode)
+    #[arg(long = "debounce", value_name = "MS", default_value_t = DEFAULT_DEBOUNCE_MS)]
+    pub debounce: u64,
+
+    /// Run as a background daemon serving status over a Unix socket
+    #[arg(long = "daemon", conflicts_with_all = ["json", "tree", "watch", "client"])]
+    pub daemon: bool,
+
+    /// Query a running daemon instead of scanning
+    ///
+    /// `get` looks up the repository containing the path argument
+    /// (default: the current directory). Responses are printed as JSON lines.
+    #[arg(long = "client", value_name = "REQUEST", value_enum)]
+    pub client: Option<ClientRequest>,
+
+    /// Unix socket used by --daemon and --client
+    #[arg(long = "socket", value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+
+    /// Evaluate the rules in a TOML policy file and report violations
+    ///
+    /// The exit code becomes 0 if no rule above `info` is broken, 1 for
+    /// warnings and 2 for errors.
+    #[arg(long = "policy", value_name = "FILE", conflicts_with_all = ["watch", "daemon", "client"])]
+    pub policy: Option<PathBuf>,
+
+    /// How to compute the exit code [default: count]
+    ///
+    /// `count` exits with the number of dirty repos (capped at 255),
+    /// `boolean` with 0 if all repos are clean, 1 if any is dirty and 2 if a
+    /// repo couldn't be checked, and `zero` always with 0. `bitmask` adds up
+    /// bits for what was found:
+    ///
+    ///   1  a repo is dirty
+    ///   2  a repo is ahead of its upstream
+    ///   4  a repo couldn't be checked, or the run failed
+    ///   8  git timed out in a repo (see --git-timeout)
+    ///  16  a policy rule above `info` was broken
+    ///  32  a repo has conflicts (unmerged paths)
+    ///
+    /// Without this option, --policy runs exit with 0, 1 or 2 for the most
+    /// serious violation.
+    #[arg(
+        long = "exit-code",
+        value_name = "MODE",
+        value_enum,
+        verbatim_doc_comment
+    )]
+    pub exit_code: Option<ExitCodeMode>,
+
+    /// Limit every git command run in a repo to this many seconds
+    ///
+    /// A repo whose `git status` times out is reported as an error; a
+    /// slower lookup, such as the diff or LFS check, is left out.
+    #[arg(long = "git-timeout", value_name = "SECS")]
+    pub git_timeout: Option<u64>,
+
+    /// Write the full results of this run to a snapshot file
+    #[arg(long = "save-snapshot", value_name = "FILE")]
+    pub save_snapshot: Option<PathBuf>,
+
+    /// Compare two snapshot files without scanning
+    ///
+    /// Changed repos count as dirty for the exit code.
+    #[arg(
+        long = "diff",
+        value_names = ["OLD", "NEW"],
+        num_args = 2,
+        conflicts_with_all = ["tree", "watch", "daemon", "client", "changes"]
+    )]
+    pub diff: Option<Vec<PathBuf>>,
+
+    /// Report what changed since a snapshot
+    ///
+    /// Compares a fresh scan with the given snapshot file, or with the
+    /// automatic snapshot of the previous `--changes` run over the same
+    /// root and depth. Changed repos count as dirty for the exit code.
+    #[arg(
+        long = "changes",
+        value_name = "SNAPSHOT",
+        num_args = 0..=1,
+        require_equals = true,
+        conflicts_with_all = ["tree", "watch", "daemon", "client"]
+    )]
+    pub changes: Option<Option<PathBuf>>,
+
+    /// Show trends from the run history instead of scanning
+    ///
+    /// Reports how long repos have been dirty, the repos with the oldest
+    /// last commits and the dirty repo count over recent runs.
+    #[arg(
+        long = "history",
+        conflicts_with_all = ["tree", "watch", "daemon", "client", "diff", "changes"]
+    )]
+    pub history: bool,
+
+    /// Record this run in the history database, e.g. from a scheduled job
+    ///
+    /// Runs older than 180 days are deleted as new ones are recorded.
+    #[arg(long = "record-history", conflicts_with = "history")]
+    pub record_history: bool,
+
+    /// Don't read or update the persistent status and discovery caches
+    #[arg(long = "no-cache")]
+    pub no_cache: bool,
+
+    /// Walk the whole tree again instead of reusing the cached repo list
+    #[arg(long = "rescan", conflicts_with = "no_cache")]
+    pub rescan: bool,
+
+    /// Print status cache hits and misses after the scan
+    #[arg(long = "cache-stats", conflicts_with = "no_cache")]
+    pub cache_stats: bool,
+
+    /// When to use colors
+    #[arg(long = "color", value_name = "WHEN", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Use plain ASCII markers instead of emoji and box drawing
+    #[arg(long = "plain")]
+    pub plain: bool,
+
     /// Generate shell completion script
     #[arg(long = "generate-completion", value_name = "SHELL")]
     pub generate_completion: Option<Shell>,
@@ -58,6 +289,8 @@ impl Args {
     pub fn output_format(&self) -> OutputFormat {
         if self.json {
             OutputFormat::Json
+        } else if self.tree {
+            OutputFormat::Tree
         } else {
             OutputFormat::Human
         }
@@ -74,6 +307,14 @@ impl Args {
         }
     }
 
+    /// Get the daemon socket path
+    #[cfg(unix)]
+    pub fn socket_path(&self) -> PathBuf {
+        self.socket
+            .clone()
+            .unwrap_or_else(daemon::default_socket_path)
+    }
+
     /// Get maximum search depth with validation
     pub fn max_depth(&self) -> usize {
         self.maxdepth.unwrap_or(DEFAULT_MAX_DEPTH)
@@ -90,6 +331,22 @@ impl Args {
     }
 }
 
+/// Parses a size such as `10MB`, `512KiB` or a plain byte count
+fn parse_size(value: &str) -> std::result::Result<u64, String> {
+    value
+        .parse()
+        .ok()
+        .or_else(|| query::parse_size(value).map(|bytes| bytes as u64))
+        .ok_or_else(|| format!("invalid size '{value}', expected e.g. 10MB, 512KiB or 1000"))
+}
+
+/// Checks that a glob pattern is valid
+fn parse_glob(value: &str) -> std::result::Result<String, String> {
+    globset::Glob::new(value)
+        .map(|_| value.to_string())
+        .map_err(|e| e.to_string())
+}
+
 /// Generate shell completion script
 fn generate_completion(shell: Shell) {
     let mut cmd = Args::command();
@@ -110,7 +367,7 @@ mod tests {
             verbose: false,
             json: false,
             show_branch: false,
-            generate_completion: None,
+            ..Default::default()
         };
         assert_eq!(args.verbosity(), Verbosity::Quiet);
 
@@ -131,12 +388,28 @@ mod tests {
             verbose: false,
             json: false,
             show_branch: false,
-            generate_completion: None,
+            ..Default::default()
         };
         assert_eq!(args.output_format(), OutputFormat::Human);
 
         let args = Args { json: true, ..args };
         assert_eq!(args.output_format(), OutputFormat::Json);
+
+        let args = Args {
+            json: false,
+            tree: true,
+            ..args
+        };
+        assert_eq!(args.output_format(), OutputFormat::Tree);
+    }
+
+    #[test]
+    fn test_group_by_conflicts_with_other_views() {
+        assert!(Args::try_parse_from(["check-git-status", "--group-by", "status"]).is_ok());
+        for view in ["--tree", "--watch", "--daemon"] {
+            let result = Args::try_parse_from(["check-git-status", "--group-by", "status", view]);
+            assert!(result.is_err(), "{view}");
+        }
     }
 
     #[test]
@@ -148,7 +421,7 @@ mod tests {
             verbose: false,
             json: false,
             show_branch: false,
-            generate_completion: None,
+            ..Default::default()
         };
         assert_eq!(args.max_depth(), 5);
 
@@ -169,7 +442,7 @@ mod tests {
             verbose: false,
             json: false,
             show_branch: false,
-            generate_completion: None,
+            ..Default::default()
         };
         let result = args.root_path();
         assert!(result.is_ok());
@@ -185,7 +458,7 @@ mod tests {
             verbose: false,
             json: false,
             show_branch: false,
-            generate_completion: None,
+            ..Default::default()
         };
         assert!(!args.handle_completion());
 
@@ -197,4 +470,12 @@ mod tests {
         // We just test the return value logic
         assert!(args.generate_completion.is_some());
     }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1000"), Ok(1000));
+        assert_eq!(parse_size("10MB"), Ok(10_000_000));
+        assert_eq!(parse_size("1KiB"), Ok(1024));
+        assert!(parse_size("ten").is_err());
+    }
 }
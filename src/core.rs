@@ -3,30 +3,281 @@
 //! This module provides the core functionality for discovering and checking
 //! git repositories, including parallel processing and validation.
 
+use crate::analysis::FileWarning;
+use crate::bare;
+use crate::cache::{self, Fingerprint, StatusCache};
+use crate::default_branch::{self, DefaultBranch};
+use crate::diffstat::{self, DiffStats};
 use crate::error::{Error, Result};
+use crate::housekeeping::Housekeeping;
+use crate::lfs::{self, LfsStatus};
+use crate::remote::{self, Remote};
+use crate::submodule::{self, Submodule};
+use crate::worktree::{self, Worktree};
+use ignore::WalkBuilder;
 use rayon::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
 /// Maximum allowed depth for repository scanning
 const MAX_DEPTH_LIMIT: usize = 100;
 
+/// Timeout for each git command run in a repository in milliseconds, or 0
+/// for none
+static GIT_TIMEOUT_MS: AtomicU64 = AtomicU64::new(0);
+
+/// How often a running git command is polled while a timeout applies
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Sets how long a git command may run in a repository before giving up
+pub fn set_git_timeout(timeout: Option<Duration>) {
+    let millis = timeout.map_or(0, |t| t.as_millis().max(1) as u64);
+    GIT_TIMEOUT_MS.store(millis, Ordering::Relaxed);
+}
+
+fn git_timeout() -> Option<Duration> {
+    match GIT_TIMEOUT_MS.load(Ordering::Relaxed) {
+        0 => None,
+        millis => Some(Duration::from_millis(millis)),
+    }
+}
+
+/// Optional details looked up while checking a repository
+///
+/// Each costs extra git commands per repository, so only the ones an output
+/// or filter reads are looked up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Details {
+    /// Name of the checked-out branch
+    pub branch: bool,
+    /// Time, author and subject of the last commit
+    pub last_commit: bool,
+    /// Configured remotes
+    pub remotes: bool,
+    /// Divergence from the default branch
+    pub default_branch: bool,
+    /// Lines added and removed
+    pub diff: bool,
+}
+
+impl Details {
+    /// Every detail
+    pub const ALL: Details = Details {
+        branch: true,
+        last_commit: true,
+        remotes: true,
+        default_branch: true,
+        diff: true,
+    };
+
+    /// Returns true if these details include all of `other`
+    pub fn covers(&self, other: &Details) -> bool {
+        (self.branch || !other.branch)
+            && (self.last_commit || !other.last_commit)
+            && (self.remotes || !other.remotes)
+            && (self.default_branch || !other.default_branch)
+            && (self.diff || !other.diff)
+    }
+}
+
+/// Additional repository metadata shared by every status variant
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RepoInfo {
+    /// Whether the repository has a working tree
+    #[serde(skip_serializing_if = "RepoKind::is_work_tree")]
+    pub kind: RepoKind,
+
+    /// Commits on HEAD not yet on the upstream branch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ahead: Option<usize>,
+
+    /// Commits on the upstream branch not yet on HEAD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub behind: Option<usize>,
+
+    /// Divergence of the checked-out branch from the default branch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_branch: Option<DefaultBranch>,
+
+    /// Last commit on HEAD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_commit: Option<LastCommit>,
+
+    /// Configured remotes
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub remotes: Vec<Remote>,
+
+    /// What HEAD points at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head: Option<HeadState>,
+
+    /// Number of stash entries
+    #[serde(skip_serializing_if = "is_zero")]
+    pub stashes: usize,
+
+    /// Unix timestamp of the oldest stash entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oldest_stash: Option<i64>,
+
+    /// Size in bytes of the largest untracked file, found only when a query
+    /// needs it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub largest_untracked: Option<u64>,
+
+    /// Submodules, including nested ones
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub submodules: Vec<Submodule>,
+
+    /// Linked worktrees
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub worktrees: Vec<Worktree>,
+
+    /// Local branches without an upstream, reported for bare repositories
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing_upstream: Vec<String>,
+
+    /// Git LFS problems, for repositories that use LFS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lfs: Option<LfsStatus>,
+
+    /// Uncommitted files that are too large or look like secrets
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub file_warnings: Vec<FileWarning>,
+
+    /// Size and maintenance state, reported in housekeeping mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub housekeeping: Option<Housekeeping>,
+}
+
+/// Whether a repository has a working tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepoKind {
+    /// A repository with a working tree and a `.git` directory
+    #[default]
+    #[serde(rename = "worktree")]
+    WorkTree,
+    /// A repository without a working tree, such as a mirror
+    Bare,
+}
+
+impl RepoKind {
+    fn is_work_tree(&self) -> bool {
+        *self == RepoKind::WorkTree
+    }
+}
+
+/// What HEAD points at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum HeadState {
+    /// A branch with at least one commit
+    Branch { name: String },
+    /// A commit rather than a branch
+    Detached {
+        /// Abbreviated commit hash
+        commit: String,
+        /// A tag pointing at the commit
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tag: Option<String>,
+    },
+    /// A branch without commits yet, e.g. right after `git init`
+    Unborn { name: String },
+}
+
+impl HeadState {
+    /// Gets the branch name, or `None` when detached
+    pub fn branch(&self) -> Option<&str> {
+        match self {
+            HeadState::Branch { name } | HeadState::Unborn { name } => Some(name),
+            HeadState::Detached { .. } => None,
+        }
+    }
+}
+
+/// The last commit on HEAD
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastCommit {
+    /// Unix timestamp of the commit, written as RFC 3339
+    #[serde(with = "rfc3339")]
+    pub timestamp: i64,
+    pub author: String,
+    pub subject: String,
+}
+
+#[cfg(test)]
+impl LastCommit {
+    /// A commit at the given time, for tests that only care about ages
+    pub fn at(timestamp: i64) -> LastCommit {
+        LastCommit {
+            timestamp,
+            author: "Test User".to_string(),
+            subject: "Commit".to_string(),
+        }
+    }
+}
+
+/// Serializes Unix timestamps as RFC 3339 strings
+mod rfc3339 {
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    pub fn serialize<S: Serializer>(timestamp: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+        let time = UNIX_EPOCH + Duration::from_secs((*timestamp).max(0) as u64);
+        serializer.collect_str(&humantime::format_rfc3339_seconds(time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let time = humantime::parse_rfc3339(&text).map_err(D::Error::custom)?;
+        let secs = time.duration_since(UNIX_EPOCH).map_err(D::Error::custom)?;
+        Ok(secs.as_secs() as i64)
+    }
+}
+
+/// Lets serde skip fields that are `false`
+pub(crate) fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Lets serde skip counts that are zero
+pub(crate) fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
 /// Represents the status of a git repository
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "status", rename_all = "lowercase")]
 pub enum RepoStatus {
     Clean {
         path: PathBuf,
         #[serde(skip_serializing_if = "Option::is_none")]
         branch: Option<String>,
+        #[serde(flatten)]
+        info: RepoInfo,
     },
     Dirty {
         path: PathBuf,
         changes: String,
+        /// Lines added and removed, staged and unstaged
+        #[serde(default, skip_serializing_if = "DiffStats::is_empty")]
+        diff: DiffStats,
+        /// Unmerged paths, which also appear in `changes`
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        conflicts: Vec<PathBuf>,
         #[serde(skip_serializing_if = "Option::is_none")]
         branch: Option<String>,
+        #[serde(flatten)]
+        info: RepoInfo,
     },
 }
 
@@ -38,6 +289,61 @@ impl RepoStatus {
         }
     }
 
+    pub fn branch(&self) -> Option<&str> {
+        match self {
+            RepoStatus::Clean { branch, .. } => branch.as_deref(),
+            RepoStatus::Dirty { branch, .. } => branch.as_deref(),
+        }
+    }
+
+    /// Drops the branch name, e.g. when it wasn't requested
+    pub fn clear_branch(&mut self) {
+        match self {
+            RepoStatus::Clean { branch, .. } => *branch = None,
+            RepoStatus::Dirty { branch, .. } => *branch = None,
+        }
+    }
+
+    pub fn info(&self) -> &RepoInfo {
+        match self {
+            RepoStatus::Clean { info, .. } => info,
+            RepoStatus::Dirty { info, .. } => info,
+        }
+    }
+
+    pub fn info_mut(&mut self) -> &mut RepoInfo {
+        match self {
+            RepoStatus::Clean { info, .. } => info,
+            RepoStatus::Dirty { info, .. } => info,
+        }
+    }
+
+    /// Number of entries reported by `git status --porcelain`
+    pub fn change_count(&self) -> usize {
+        match self {
+            RepoStatus::Clean { .. } => 0,
+            RepoStatus::Dirty { changes, .. } => {
+                changes.lines().filter(|l| !l.trim().is_empty()).count()
+            }
+        }
+    }
+
+    /// Unmerged paths, e.g. in the middle of a merge or rebase
+    pub fn conflicts(&self) -> &[PathBuf] {
+        match self {
+            RepoStatus::Clean { .. } => &[],
+            RepoStatus::Dirty { conflicts, .. } => conflicts,
+        }
+    }
+
+    /// Diff statistics, empty for clean repositories
+    pub fn diff(&self) -> DiffStats {
+        match self {
+            RepoStatus::Clean { .. } => DiffStats::default(),
+            RepoStatus::Dirty { diff, .. } => *diff,
+        }
+    }
+
     pub fn is_dirty(&self) -> bool {
         matches!(self, RepoStatus::Dirty { .. })
     }
@@ -95,7 +401,8 @@ pub fn validate_depth(depth: usize) -> Result<usize> {
 /// Finds all git repositories within the given root directory
 ///
 /// Recursively searches for `.git` directories up to the specified depth
-/// and returns the parent directories (the repository roots).
+/// and returns the parent directories (the repository roots). Bare
+/// repositories found at the same depth are returned as they are.
 ///
 /// # Arguments
 ///
@@ -106,44 +413,407 @@ pub fn validate_depth(depth: usize) -> Result<usize> {
 ///
 /// A vector of paths to git repository roots
 pub fn find_git_repos(root: &Path, max_depth: usize) -> Vec<PathBuf> {
-    WalkDir::new(root)
-        .max_depth(max_depth)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_dir() && e.file_name() == ".git")
-        .filter_map(|e| e.path().parent().map(|p| p.to_path_buf()))
+    let mut repos = Vec::new();
+    let mut walker = WalkDir::new(root).max_depth(max_depth).into_iter();
+    while let Some(entry) = walker.next() {
+        let Ok(entry) = entry else { continue };
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        if entry.file_name() == ".git" {
+            repos.extend(entry.path().parent().map(Path::to_path_buf));
+        } else if bare::is_bare_repo(entry.path()) {
+            repos.push(entry.path().to_path_buf());
+        } else {
+            continue;
+        }
+        // Git directories hold nothing to find, and `.git/modules` would
+        // otherwise look like bare repositories
+        walker.skip_current_dir();
+    }
+    repos
+}
+
+/// Walks the working tree of a repository
+///
+/// Like `git status`, this skips `.git`, ignored paths and nested
+/// repositories, so build output such as `target/` or `node_modules` is
+/// never walked.
+pub fn walk_work_tree(repo: &Path) -> ignore::Walk {
+    WalkBuilder::new(repo)
+        .hidden(false)
+        .ignore(false)
+        .parents(false)
+        .filter_entry(|e| {
+            e.file_name() != ".git" && !(e.depth() > 0 && e.path().join(".git").exists())
+        })
+        .build()
+}
+
+/// Parses the `## branch...upstream [ahead N, behind M]` header line
+///
+/// Returns `(ahead, behind)`, both `None` when the branch has no upstream
+/// or the upstream is gone.
+fn parse_tracking(header: &str) -> (Option<usize>, Option<usize>) {
+    if !header.contains("...") || header.ends_with("[gone]") {
+        return (None, None);
+    }
+
+    let mut ahead = 0;
+    let mut behind = 0;
+    if let Some(start) = header.rfind('[') {
+        let counts = header[start + 1..].trim_end_matches(']');
+        for part in counts.split(", ") {
+            if let Some(n) = part.strip_prefix("ahead ") {
+                ahead = n.parse().unwrap_or(0);
+            } else if let Some(n) = part.strip_prefix("behind ") {
+                behind = n.parse().unwrap_or(0);
+            }
+        }
+    }
+
+    (Some(ahead), Some(behind))
+}
+
+/// Parses `git log --format=%ct%x00%an%x00%s` output
+fn parse_last_commit(line: &str) -> Option<LastCommit> {
+    let mut fields = line.trim_end_matches('\n').splitn(3, '\0');
+    Some(LastCommit {
+        timestamp: fields.next()?.parse().ok()?,
+        author: fields.next()?.to_string(),
+        subject: fields.next()?.to_string(),
+    })
+}
+
+/// Gets the time, author and subject of the last commit on HEAD
+pub(crate) fn get_last_commit(repo_path: &Path) -> Option<LastCommit> {
+    let output = run_git(
+        Command::new("git")
+            .arg("-C")
+            .arg(repo_path)
+            .arg("log")
+            .arg("-1")
+            .arg("--format=%ct%x00%an%x00%s"),
+        None,
+    )
+    .ok()??;
+
+    if output.status.success() {
+        parse_last_commit(&String::from_utf8_lossy(&output.stdout))
+    } else {
+        None
+    }
+}
+
+/// Gets the Unix timestamp of a reflog line
+///
+/// Lines look like `<old> <new> <name> <<email>> <timestamp> <tz>\t<message>`.
+fn parse_reflog_time(line: &str) -> Option<i64> {
+    let (entry, _) = line.split_once('\t')?;
+    let mut parts = entry.rsplitn(3, ' ');
+    parts.next()?;
+    parts.next()?.parse().ok()
+}
+
+/// Finds the git directory shared by all worktrees of a repository
+///
+/// That is `.git` itself in most repositories; only where `.git` is a file,
+/// as in linked worktrees and submodules, git is asked where it points.
+pub(crate) fn common_git_dir(repo_path: &Path) -> PathBuf {
+    let dot_git = repo_path.join(".git");
+    if dot_git.is_dir() {
+        return dot_git;
+    }
+    git_output(repo_path, &["rev-parse", "--git-common-dir"])
+        .map_or(dot_git, |dir| repo_path.join(dir))
+}
+
+/// Gets the number of stash entries and the time of the oldest one
+///
+/// Reads the stash reflog directly instead of running `git stash list`.
+fn get_stash_info(repo_path: &Path) -> (usize, Option<i64>) {
+    let Ok(log) = fs::read_to_string(common_git_dir(repo_path).join("logs/refs/stash")) else {
+        return (0, None);
+    };
+    let times: Vec<i64> = log.lines().filter_map(parse_reflog_time).collect();
+    (times.len(), times.iter().min().copied())
+}
+
+/// Returns true if a `git status --porcelain` status code marks an unmerged
+/// path: `DD`, `AU`, `UD`, `UA`, `DU`, `AA` or `UU`
+pub fn is_conflict_code(code: &str) -> bool {
+    matches!(code, "DD" | "AA") || (code.len() == 2 && code.contains('U'))
+}
+
+/// Lists the unmerged paths in `git status --porcelain` output
+fn parse_conflicts(changes: &str) -> Vec<PathBuf> {
+    changes
+        .lines()
+        .filter(|line| line.get(..2).is_some_and(is_conflict_code))
+        .filter_map(|line| line.get(3..))
+        .map(|field| PathBuf::from(porcelain_path(field)))
         .collect()
 }
 
-/// Gets the current branch name for a repository
-fn get_branch_name(repo_path: &Path) -> Option<String> {
-    let output = Command::new("git")
-        .arg("-C")
-        .arg(repo_path)
-        .arg("rev-parse")
-        .arg("--abbrev-ref")
-        .arg("HEAD")
-        .output()
-        .ok()?;
+/// Extracts the path from the path field of a `git status --porcelain` line
+///
+/// Renames and copies yield their new path.
+pub(crate) fn porcelain_path(field: &str) -> String {
+    let (path, rest) = split_porcelain_path(field);
+    match rest.strip_prefix(" -> ") {
+        Some(new) => split_porcelain_path(new).0,
+        None => path,
+    }
+}
+
+/// Splits the first path off a porcelain path field
+///
+/// Git C-quotes paths with spaces, quotes, control characters or, unless
+/// `core.quotePath` is off, non-ASCII bytes, which it writes as octal
+/// escapes. Unquoted paths run until an ` -> ` rename arrow.
+fn split_porcelain_path(field: &str) -> (String, &str) {
+    let Some(quoted) = field.strip_prefix('"') else {
+        let end = field.find(" -> ").unwrap_or(field.len());
+        return (field[..end].to_string(), &field[end..]);
+    };
+
+    let mut bytes = Vec::new();
+    let mut chars = quoted.char_indices().peekable();
+    let mut rest = "";
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                rest = &quoted[i + 1..];
+                break;
+            }
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some(digit @ '0'..='7') => {
+                    let mut value = digit.to_digit(8).unwrap_or(0);
+                    for _ in 0..2 {
+                        let Some((_, digit)) = chars.next_if(|(_, c)| c.is_digit(8)) else {
+                            break;
+                        };
+                        value = value * 8 + digit.to_digit(8).unwrap_or(0);
+                    }
+                    bytes.push(value as u8);
+                }
+                Some(escaped) => bytes.push(match escaped {
+                    'a' => 0x07,
+                    'b' => 0x08,
+                    't' => b'\t',
+                    'n' => b'\n',
+                    'v' => 0x0b,
+                    'f' => 0x0c,
+                    'r' => b'\r',
+                    other => other as u8,
+                }),
+                None => break,
+            },
+            c => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), rest)
+}
+
+/// Parses the HEAD state from a `git status --branch` header
+///
+/// Detached heads are returned with an empty commit, to be filled in by
+/// `get_detached_head`, since the header doesn't name the commit.
+fn parse_head(header: &str) -> Option<HeadState> {
+    if header.starts_with("HEAD (no branch)") {
+        return Some(HeadState::Detached {
+            commit: String::new(),
+            tag: None,
+        });
+    }
+    for prefix in ["No commits yet on ", "Initial commit on "] {
+        if let Some(name) = header.strip_prefix(prefix) {
+            return Some(HeadState::Unborn {
+                name: name.to_string(),
+            });
+        }
+    }
+    let name = header.split("...").next()?.split(' ').next()?;
+    (!name.is_empty()).then(|| HeadState::Branch {
+        name: name.to_string(),
+    })
+}
+
+/// Runs a git command and returns its trimmed output if it succeeds in time
+pub(crate) fn git_output(repo_path: &Path, args: &[&str]) -> Option<String> {
+    git_stdout(repo_path, args).map(|s| s.trim().to_string())
+}
+
+/// Runs a git command and returns its output as is if it succeeds in time
+///
+/// For output whose leading whitespace is significant, such as the status
+/// flag of `git submodule status`.
+pub(crate) fn git_stdout(repo_path: &Path, args: &[&str]) -> Option<String> {
+    let output = run_git(
+        Command::new("git").arg("-C").arg(repo_path).args(args),
+        None,
+    )
+    .ok()??;
 
     if output.status.success() {
-        String::from_utf8(output.stdout)
-            .ok()
-            .map(|s| s.trim().to_string())
+        String::from_utf8(output.stdout).ok()
     } else {
         None
     }
 }
 
+/// Gets the abbreviated commit and first tag of a detached HEAD
+pub(crate) fn get_detached_head(repo_path: &Path) -> HeadState {
+    HeadState::Detached {
+        commit: git_output(repo_path, &["rev-parse", "--short", "HEAD"]).unwrap_or_default(),
+        tag: git_output(repo_path, &["tag", "--points-at", "HEAD"])
+            .and_then(|tags| tags.lines().next().map(str::to_string)),
+    }
+}
+
+/// Runs a git command to completion, feeding it `input`, and kills it once
+/// the git timeout has passed
+///
+/// Returns `Ok(None)` if it timed out. Input is written and output drained
+/// on separate threads so a chatty command can't block on a full pipe while
+/// it is being waited for.
+pub(crate) fn run_git(
+    command: &mut Command,
+    input: Option<Vec<u8>>,
+) -> std::io::Result<Option<Output>> {
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let writer = child.stdin.take().map(|mut stdin| {
+        thread::spawn(move || {
+            let _ = stdin.write_all(&input.unwrap_or_default());
+        })
+    });
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            buffer
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as _));
+
+    let deadline = git_timeout().map(|timeout| Instant::now() + timeout);
+    let status = loop {
+        let Some(deadline) = deadline else {
+            break child.wait()?;
+        };
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    Ok(Some(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    }))
+}
+
+/// Parses `git for-each-ref` output in the format of
+/// `get_commit_and_default_branch` into `(refname, symref, commit)` triples
+fn parse_refs(output: &str) -> Vec<(&str, &str, &str)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (refname, rest) = line.split_once('\0')?;
+            let (symref, commit) = rest.split_once('\0')?;
+            Some((refname, symref, commit))
+        })
+        .collect()
+}
+
+/// Gets the last commit on HEAD and the divergence from the default branch,
+/// as far as `details` asks for them
+///
+/// The checked-out branch and every ref that may name the default branch
+/// are read with a single `git for-each-ref`. Only a detached HEAD needs
+/// `git log` to find its last commit.
+fn get_commit_and_default_branch(
+    repo_path: &Path,
+    head: Option<&HeadState>,
+    remote: Option<&str>,
+    dirty: bool,
+    details: Details,
+) -> (Option<LastCommit>, Option<DefaultBranch>) {
+    let branch = match head {
+        Some(HeadState::Branch { name }) => name,
+        Some(HeadState::Detached { .. }) if details.last_commit => {
+            return (get_last_commit(repo_path), None);
+        }
+        _ => return (None, None),
+    };
+    if !details.last_commit && !details.default_branch {
+        return (None, None);
+    }
+
+    let branch_ref = format!("refs/heads/{branch}");
+    let mut patterns = Vec::new();
+    if details.last_commit {
+        patterns.push(branch_ref.clone());
+    }
+    if details.default_branch {
+        patterns.extend(default_branch::candidate_refs(remote));
+    }
+    let mut args = vec![
+        "for-each-ref",
+        "--format=%(refname)%00%(symref)%00%(committerdate:unix)%00%(authorname)%00%(subject)",
+    ];
+    args.extend(patterns.iter().map(String::as_str));
+    let output = git_output(repo_path, &args).unwrap_or_default();
+    let refs = parse_refs(&output);
+
+    let last_commit = refs
+        .iter()
+        .find(|(refname, _, _)| *refname == branch_ref)
+        .and_then(|(_, _, commit)| parse_last_commit(commit));
+    let default_branch = if details.default_branch {
+        let names: Vec<(&str, &str)> = refs
+            .iter()
+            .map(|&(refname, symref, _)| (refname, symref))
+            .collect();
+        default_branch::pick_default_branch(&names, remote).and_then(|name| {
+            default_branch::check_default_branch(repo_path, branch, name, remote, dirty)
+        })
+    } else {
+        None
+    };
+    (last_commit, default_branch)
+}
+
 /// Checks the status of a single git repository
 ///
-/// Executes `git status --porcelain` to determine if the repository has
-/// uncommitted changes, and optionally retrieves the current branch name.
+/// Executes `git status --porcelain --branch` to determine if the repository
+/// has uncommitted changes and how far it is from its upstream, and looks
+/// up the requested details. Optional locks are disabled so the check never
+/// rewrites the index.
 ///
 /// # Arguments
 ///
 /// * `repo_path` - Path to the git repository
-/// * `include_branch` - Whether to include branch name in the result
+/// * `details` - Which optional details to look up
 ///
 /// # Returns
 ///
@@ -151,17 +821,29 @@ fn get_branch_name(repo_path: &Path) -> Option<String> {
 ///
 /// # Errors
 ///
-/// Returns `Error::GitCommandFailed` if git command execution fails
-pub fn check_repo_status(repo_path: &Path, include_branch: bool) -> Result<RepoStatus> {
-    let output = Command::new("git")
+/// Returns `Error::GitCommandFailed` if git command execution fails, or
+/// `Error::GitTimeout` if it runs longer than the configured timeout
+pub fn check_repo_status(repo_path: &Path, details: Details) -> Result<RepoStatus> {
+    if bare::is_bare_repo(repo_path) {
+        return Ok(bare::check_bare_repo(repo_path, details));
+    }
+    let mut command = Command::new("git");
+    command
         .arg("-C")
         .arg(repo_path)
+        .arg("--no-optional-locks")
         .arg("status")
         .arg("--porcelain")
-        .output()
-        .map_err(|e| Error::GitCommandFailed {
+        .arg("--branch");
+    let failed = |e: std::io::Error| Error::GitCommandFailed {
+        repo: repo_path.to_path_buf(),
+        message: e.to_string(),
+    };
+    let output = run_git(&mut command, None)
+        .map_err(failed)?
+        .ok_or_else(|| Error::GitTimeout {
             repo: repo_path.to_path_buf(),
-            message: e.to_string(),
+            timeout: git_timeout().unwrap_or_default(),
         })?;
 
     if !output.status.success() {
@@ -172,22 +854,75 @@ pub fn check_repo_status(repo_path: &Path, include_branch: bool) -> Result<RepoS
     }
 
     let status_output = String::from_utf8_lossy(&output.stdout);
-    let branch = if include_branch {
-        get_branch_name(repo_path)
-    } else {
-        None
+    let mut changes = String::new();
+    let mut tracking = (None, None);
+    let mut head = None;
+    for line in status_output.lines() {
+        match line.strip_prefix("## ") {
+            Some(header) => {
+                tracking = parse_tracking(header);
+                head = parse_head(header);
+            }
+            None => {
+                changes.push_str(line);
+                changes.push('\n');
+            }
+        }
+    }
+
+    if let Some(HeadState::Detached { .. }) = head {
+        head = Some(get_detached_head(repo_path));
+    }
+    let branch = head
+        .as_ref()
+        .and_then(HeadState::branch)
+        .filter(|_| details.branch)
+        .map(str::to_string);
+    let dirty = !changes.trim().is_empty();
+    let (stashes, oldest_stash) = get_stash_info(repo_path);
+    let mut remotes =
+        (details.remotes || details.default_branch).then(|| remote::get_remotes(repo_path));
+    let lfs = lfs::check_lfs(repo_path, || {
+        let remotes = remotes.get_or_insert_with(|| remote::get_remotes(repo_path));
+        remote::primary(remotes).map(|r| r.name.clone())
+    });
+    let remotes = remotes.unwrap_or_default();
+    let primary = remote::primary(&remotes).map(|r| r.name.as_str());
+    let (last_commit, default_branch) =
+        get_commit_and_default_branch(repo_path, head.as_ref(), primary, dirty, details);
+    let info = RepoInfo {
+        ahead: tracking.0,
+        behind: tracking.1,
+        default_branch,
+        last_commit,
+        remotes: if details.remotes { remotes } else { Vec::new() },
+        head,
+        stashes,
+        oldest_stash,
+        submodules: submodule::get_submodules(repo_path),
+        worktrees: worktree::get_worktrees(repo_path, &repo_path.join(".git")),
+        lfs,
+        ..RepoInfo::default()
     };
 
-    if status_output.trim().is_empty() {
+    if !dirty {
         Ok(RepoStatus::Clean {
             path: repo_path.to_path_buf(),
             branch,
+            info,
         })
     } else {
         Ok(RepoStatus::Dirty {
             path: repo_path.to_path_buf(),
-            changes: status_output.into_owned(),
+            conflicts: parse_conflicts(&changes),
+            changes,
+            diff: if details.diff {
+                diffstat::get_diff_stats(repo_path)
+            } else {
+                DiffStats::default()
+            },
             branch,
+            info,
         })
     }
 }
@@ -195,44 +930,69 @@ pub fn check_repo_status(repo_path: &Path, include_branch: bool) -> Result<RepoS
 /// Checks multiple repositories in parallel using rayon
 ///
 /// Leverages parallel processing to check repository status concurrently,
-/// improving performance on systems with multiple cores.
+/// improving performance on systems with multiple cores. When a cache is
+/// given, repositories whose fingerprint is unchanged are served from it
+/// and freshly checked repositories are added to it.
 ///
 /// # Arguments
 ///
 /// * `repos` - Slice of repository paths to check
-/// * `include_branch` - Whether to include branch names in results
+/// * `details` - Which optional details to look up
+/// * `cache` - Optional persistent status cache
 ///
 /// # Returns
 ///
 /// A tuple containing:
-/// - A vector of `RepoStatus` for successfully checked repositories
+/// - A vector of `RepoStatus` for successfully checked repositories,
+///   sorted by path
 /// - A vector of `Error` for failed repository checks
 pub fn check_repos_parallel(
     repos: &[PathBuf],
-    include_branch: bool,
+    details: Details,
+    mut cache: Option<&mut StatusCache>,
 ) -> (Vec<RepoStatus>, Vec<Error>) {
-    let results: Vec<Result<RepoStatus>> = repos
+    let shared = cache.as_deref();
+    let results: Vec<(Result<RepoStatus>, Option<Fingerprint>, bool)> = repos
         .par_iter()
-        .map(|repo| check_repo_status(repo, include_branch))
+        .map(|repo| {
+            let fingerprint = shared.and_then(|_| cache::fingerprint(repo));
+            let cached = shared
+                .zip(fingerprint.as_ref())
+                .and_then(|(c, fp)| c.lookup(repo, fp, details));
+            match cached {
+                Some(status) => (Ok(status), None, true),
+                None => (check_repo_status(repo, details), fingerprint, false),
+            }
+        })
         .collect();
 
     let mut statuses = Vec::new();
     let mut errors = Vec::new();
 
-    for result in results {
+    for (result, fingerprint, hit) in results {
         match result {
-            Ok(status) => statuses.push(status),
+            Ok(status) => {
+                if let Some(cache) = cache.as_deref_mut() {
+                    if hit {
+                        cache.record_hit();
+                    } else if let Some(fingerprint) = fingerprint {
+                        cache.insert(fingerprint, details, status.clone());
+                    }
+                }
+                statuses.push(status);
+            }
             Err(e) => errors.push(e),
         }
     }
 
+    statuses.sort_by(|a, b| a.path().cmp(b.path()));
     (statuses, errors)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs;
+    use crate::diffstat::DiffStats;
 
     #[test]
     fn test_validate_depth() {
@@ -248,15 +1008,143 @@ mod tests {
         let clean = RepoStatus::Clean {
             path: PathBuf::from("/test"),
             branch: Some("main".to_string()),
+            info: RepoInfo::default(),
         };
         assert!(!clean.is_dirty());
+        assert_eq!(clean.change_count(), 0);
 
         let dirty = RepoStatus::Dirty {
             path: PathBuf::from("/test"),
             changes: "M file.txt".to_string(),
+            diff: DiffStats::default(),
+            conflicts: Vec::new(),
             branch: Some("dev".to_string()),
+            info: RepoInfo::default(),
         };
         assert!(dirty.is_dirty());
+        assert_eq!(dirty.change_count(), 1);
+        assert_eq!(dirty.branch(), Some("dev"));
+    }
+
+    #[test]
+    fn test_parse_tracking() {
+        assert_eq!(parse_tracking("main"), (None, None));
+        assert_eq!(parse_tracking("main...origin/main"), (Some(0), Some(0)));
+        assert_eq!(
+            parse_tracking("main...origin/main [ahead 2, behind 5]"),
+            (Some(2), Some(5))
+        );
+        assert_eq!(
+            parse_tracking("main...origin/main [behind 1]"),
+            (Some(0), Some(1))
+        );
+        assert_eq!(parse_tracking("main...origin/main [gone]"), (None, None));
+        assert_eq!(parse_tracking("No commits yet on main"), (None, None));
+    }
+
+    #[test]
+    fn test_parse_head() {
+        let branch = |name: &str| {
+            Some(HeadState::Branch {
+                name: name.to_string(),
+            })
+        };
+        assert_eq!(parse_head("main"), branch("main"));
+        assert_eq!(
+            parse_head("feat/x...origin/feat/x [ahead 1]"),
+            branch("feat/x")
+        );
+        assert_eq!(
+            parse_head("No commits yet on trunk"),
+            Some(HeadState::Unborn {
+                name: "trunk".to_string()
+            })
+        );
+        assert_eq!(
+            parse_head("Initial commit on master").and_then(|h| h.branch().map(str::to_string)),
+            Some("master".to_string())
+        );
+        let detached = parse_head("HEAD (no branch)").unwrap();
+        assert!(matches!(detached, HeadState::Detached { .. }));
+        assert_eq!(detached.branch(), None);
+    }
+
+    #[test]
+    fn test_head_state_json() {
+        let head = HeadState::Detached {
+            commit: "1a2b3c4".to_string(),
+            tag: Some("v1.0".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_value(&head).unwrap(),
+            serde_json::json!({"state": "detached", "commit": "1a2b3c4", "tag": "v1.0"})
+        );
+    }
+
+    #[test]
+    fn test_parse_last_commit() {
+        let commit = parse_last_commit("1700000000\0Jane Doe\0Fix: a\0b\n").unwrap();
+        assert_eq!(commit.timestamp, 1_700_000_000);
+        assert_eq!(commit.author, "Jane Doe");
+        assert_eq!(commit.subject, "Fix: a\0b");
+        assert_eq!(parse_last_commit(""), None);
+
+        let json = serde_json::to_value(&commit).unwrap();
+        assert_eq!(json["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(serde_json::from_value::<LastCommit>(json).unwrap(), commit);
+    }
+
+    #[test]
+    fn test_parse_refs() {
+        let output = "refs/heads/feature\0\x001700000000\0Jane Doe\0Add parser\n\
+                      refs/remotes/origin/HEAD\0refs/remotes/origin/main\x001690000000\0Bob\0Init";
+        let refs = parse_refs(output);
+        assert_eq!(
+            refs,
+            [
+                ("refs/heads/feature", "", "1700000000\0Jane Doe\0Add parser"),
+                (
+                    "refs/remotes/origin/HEAD",
+                    "refs/remotes/origin/main",
+                    "1690000000\0Bob\0Init"
+                ),
+            ]
+        );
+        assert_eq!(parse_refs(""), []);
+    }
+
+    #[test]
+    fn test_run_git_feeds_input() {
+        let output = run_git(
+            Command::new("git").args(["hash-object", "--stdin"]),
+            Some(b"hello\n".to_vec()),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+    }
+
+    #[test]
+    fn test_details_covers() {
+        let diff = Details {
+            diff: true,
+            ..Details::default()
+        };
+        assert!(Details::ALL.covers(&diff));
+        assert!(diff.covers(&Details::default()));
+        assert!(!diff.covers(&Details::ALL));
+        assert!(!Details::default().covers(&diff));
+    }
+
+    #[test]
+    fn test_parse_reflog_time() {
+        let line = "0000000 1111111 Test User <test@test.com> 1700000000 +0100\tWIP on main: abc";
+        assert_eq!(parse_reflog_time(line), Some(1700000000));
+        assert_eq!(parse_reflog_time("garbage"), None);
     }
 
     #[test]
@@ -279,7 +1167,65 @@ mod tests {
 
     #[test]
     fn test_check_repo_status_invalid_path() {
-        let result = check_repo_status(Path::new("/invalid/path"), false);
+        let result = check_repo_status(Path::new("/invalid/path"), Details::default());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_porcelain_path() {
+        assert_eq!(porcelain_path("src/main.rs"), "src/main.rs");
+        assert_eq!(porcelain_path("\"with space\""), "with space");
+        assert_eq!(porcelain_path("old.txt -> new.txt"), "new.txt");
+        assert_eq!(porcelain_path("old.txt -> \"new file\""), "new file");
+        assert_eq!(porcelain_path("\"a -> b\" -> c"), "c");
+        assert_eq!(porcelain_path("\"a -> b\""), "a -> b");
+        assert_eq!(
+            porcelain_path("\"say \\\"hi\\\"\\tnow\""),
+            "say \"hi\"\tnow"
+        );
+        assert_eq!(porcelain_path("\"caf\\303\\251.txt\""), "café.txt");
+    }
+
+    #[test]
+    fn test_parse_conflicts() {
+        let changes = "UU src/lib.rs\nAA both_added.txt\nDU \"caf\\303\\251 menu\"\n M ok.rs\nD  gone.rs\n?? new.rs\n";
+        assert_eq!(
+            parse_conflicts(changes),
+            [
+                PathBuf::from("src/lib.rs"),
+                PathBuf::from("both_added.txt"),
+                PathBuf::from("café menu"),
+            ]
+        );
+        assert!(is_conflict_code("DD"));
+        assert!(!is_conflict_code("MM"));
+    }
+
+    #[test]
+    fn test_stashes_of_linked_worktree() {
+        let temp = tempfile::tempdir().unwrap();
+        let repo = temp.path().join("repo");
+        let linked = temp.path().join("linked");
+        fs::create_dir_all(&repo).unwrap();
+        let git = |dir: &Path, args: &[&str]| {
+            let output = Command::new("git")
+                .args(["-c", "user.name=Test", "-c", "user.email=test@test.com"])
+                .args(args)
+                .current_dir(dir)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+        };
+        git(&repo, &["init"]);
+        git(&repo, &["commit", "--allow-empty", "-m", "Initial"]);
+        fs::write(repo.join("a.txt"), "a").unwrap();
+        git(&repo, &["stash", "--include-untracked"]);
+        git(&repo, &["worktree", "add", linked.to_str().unwrap()]);
+
+        assert_eq!(
+            common_git_dir(&linked).canonicalize().unwrap(),
+            repo.join(".git").canonicalize().unwrap()
+        );
+        assert_eq!(get_stash_info(&linked).0, 1);
+    }
 }
@@ -0,0 +1,473 @@
+//! Background daemon and client
+//!
+//! The daemon keeps an up-to-date map of repository statuses for a scan
+//! root, refreshed by filesystem watching, and answers queries over a Unix
+//! domain socket. Requests and responses are JSON objects, one per line:
+//!
+//! ```text
+//! {"cmd":"list"}                 -> {"ok":true,"repositories":[...]}
+//! {"cmd":"get","path":"/p/api"}  -> {"ok":true,"repository":{...}}
+//! {"cmd":"refresh"}              -> {"ok":true,"repositories":[...]}
+//! {"cmd":"subscribe"}            -> {"ok":true}, then one
+//!                                   {"ok":true,"event":"changed","repository":{...}}
+//!                                   line per status change
+//! ```
+//!
+//! Errors are reported as `{"ok":false,"error":"..."}`.
+
+use crate::core::{self, Details, RepoStatus};
+use crate::error::{Error, Result};
+use crate::output;
+use crate::watch::{self, RepoWatcher};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// File name of the default socket
+const SOCKET_NAME: &str = "check-git-status.sock";
+
+/// Requests understood by the daemon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum Request {
+    List,
+    Get { path: PathBuf },
+    Subscribe,
+    Refresh,
+}
+
+/// Response sent by the daemon
+#[derive(Debug, Default, Serialize)]
+struct Response {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repository: Option<RepoStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repositories: Option<Vec<RepoStatus>>,
+}
+
+impl Response {
+    fn error(message: impl Into<String>) -> Response {
+        Response {
+            ok: false,
+            error: Some(message.into()),
+            ..Response::default()
+        }
+    }
+}
+
+/// Gets the default socket path
+///
+/// Uses the runtime directory (`$XDG_RUNTIME_DIR`) when available, falling
+/// back to the cache directory and finally the temp directory.
+pub fn default_socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(SOCKET_NAME)
+}
+
+/// Settings for the daemon
+pub struct DaemonOptions {
+    pub root: PathBuf,
+    pub max_depth: usize,
+    pub details: Details,
+    pub debounce: Duration,
+    pub socket: PathBuf,
+}
+
+/// Shared daemon state
+struct State {
+    repos: Mutex<BTreeMap<PathBuf, RepoStatus>>,
+    subscribers: Mutex<Vec<Sender<RepoStatus>>>,
+    watcher: RepoWatcher,
+    options: DaemonOptions,
+}
+
+impl State {
+    /// Stores new statuses and notifies subscribers of the ones that changed
+    fn apply(&self, statuses: Vec<RepoStatus>) {
+        let mut repos = self.repos.lock().expect("state lock poisoned");
+        let mut changed = Vec::new();
+        for status in statuses {
+            let path = status.path().to_path_buf();
+            if repos.get(&path) != Some(&status) {
+                changed.push(status.clone());
+            }
+            repos.insert(path, status);
+        }
+        drop(repos);
+
+        if !changed.is_empty() {
+            let mut subscribers = self.subscribers.lock().expect("state lock poisoned");
+            subscribers.retain(|tx| changed.iter().all(|s| tx.send(s.clone()).is_ok()));
+        }
+    }
+
+    /// Re-checks the given repositories, dropping the ones that vanished
+    fn recheck(&self, repos: &[PathBuf]) {
+        let (statuses, errors) = core::check_repos_parallel(repos, self.options.details, None);
+        for error in &errors {
+            output::print_warning(&error.to_string());
+        }
+
+        let mut known = self.repos.lock().expect("state lock poisoned");
+        known.retain(|path, _| {
+            let exists = path.exists();
+            if !exists {
+                self.watcher.unwatch_repo(path);
+            }
+            exists
+        });
+        drop(known);
+        self.apply(statuses);
+    }
+
+    /// Re-discovers repositories under the root and re-checks all of them
+    fn refresh(&self) {
+        let repos = core::find_git_repos(&self.options.root, self.options.max_depth);
+        for repo in &repos {
+            if let Err(e) = self.watcher.watch_repo(repo) {
+                output::print_warning(&e.to_string());
+            }
+        }
+        self.recheck(&repos);
+    }
+
+    fn handle(&self, request: Request) -> Response {
+        match request {
+            Request::List | Request::Refresh => {
+                if request == Request::Refresh {
+                    self.refresh();
+                }
+                let repos = self.repos.lock().expect("state lock poisoned");
+                Response {
+                    ok: true,
+                    repositories: Some(repos.values().cloned().collect()),
+                    ..Response::default()
+                }
+            }
+            Request::Get { path } => {
+                let repos = self.repos.lock().expect("state lock poisoned");
+                match watch::repo_for_path(repos.keys(), &path).and_then(|p| repos.get(p)) {
+                    Some(status) => Response {
+                        ok: true,
+                        repository: Some(status.clone()),
+                        ..Response::default()
+                    },
+                    None => Response::error(format!("unknown repository: {}", path.display())),
+                }
+            }
+            Request::Subscribe => Response {
+                ok: true,
+                ..Response::default()
+            },
+        }
+    }
+}
+
+fn write_line(stream: &mut UnixStream, response: &Response) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(response).map_err(std::io::Error::other)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
+
+/// Serves one client connection until it disconnects
+fn serve(state: &State, stream: UnixStream) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                write_line(
+                    &mut writer,
+                    &Response::error(format!("invalid request: {}", e)),
+                )?;
+                continue;
+            }
+        };
+
+        let subscribe = request == Request::Subscribe;
+        write_line(&mut writer, &state.handle(request))?;
+
+        if subscribe {
+            let (tx, rx) = mpsc::channel();
+            state
+                .subscribers
+                .lock()
+                .expect("state lock poisoned")
+                .push(tx);
+            for status in rx {
+                let event = Response {
+                    ok: true,
+                    event: Some("changed"),
+                    repository: Some(status),
+                    ..Response::default()
+                };
+                write_line(&mut writer, &event)?;
+            }
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Binds the socket, replacing a stale socket file left by a dead daemon
+///
+/// The socket is bound inside a private directory, restricted to the owner,
+/// and only then moved into place, so it is never reachable by other users.
+fn bind(socket: &Path) -> Result<UnixListener> {
+    if let Ok(metadata) = fs::symlink_metadata(socket) {
+        if !metadata.file_type().is_socket() {
+            return Err(Error::Daemon(format!(
+                "{} exists and is not a socket",
+                socket.display()
+            )));
+        }
+        if UnixStream::connect(socket).is_ok() {
+            return Err(Error::Daemon(format!(
+                "another daemon is already listening on {}",
+                socket.display()
+            )));
+        }
+        fs::remove_file(socket)?;
+    }
+    let parent = socket
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+
+    let failed =
+        |e: std::io::Error| Error::Daemon(format!("could not bind {}: {}", socket.display(), e));
+    let private = tempfile::Builder::new()
+        .prefix(".check-git-status")
+        .tempdir_in(parent)
+        .map_err(failed)?;
+    let staged = private.path().join(SOCKET_NAME);
+    let listener = UnixListener::bind(&staged).map_err(failed)?;
+    fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))?;
+    fs::rename(&staged, socket).map_err(failed)?;
+    Ok(listener)
+}
+
+/// Runs the daemon until interrupted
+///
+/// # Errors
+///
+/// Returns `Error::Daemon` if the socket can't be bound and `Error::Watch`
+/// if filesystem watching fails
+pub fn run(options: DaemonOptions) -> Result<()> {
+    let listener = bind(&options.socket)?;
+    let repos = core::find_git_repos(&options.root, options.max_depth);
+    let state = Arc::new(State {
+        repos: Mutex::new(BTreeMap::new()),
+        subscribers: Mutex::new(Vec::new()),
+        watcher: RepoWatcher::new(&repos, options.debounce)?,
+        options,
+    });
+    state.recheck(&repos);
+
+    output::print_info(&format!(
+        "Daemon watching {} repos, listening on {}",
+        repos.len(),
+        state.options.socket.display()
+    ));
+
+    let worker = Arc::clone(&state);
+    thread::spawn(move || {
+        loop {
+            match worker.watcher.wait_for_changes() {
+                Ok(changed) => worker.recheck(&changed.into_iter().collect::<Vec<_>>()),
+                Err(e) => {
+                    output::print_error(&e.to_string());
+                    std::process::exit(1);
+                }
+            }
+        }
+    });
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let state = Arc::clone(&state);
+                thread::spawn(move || {
+                    // Disconnected clients are not an error for the daemon
+                    let _ = serve(&state, stream);
+                });
+            }
+            Err(e) => output::print_warning(&format!("connection failed: {}", e)),
+        }
+    }
+    Ok(())
+}
+
+/// Sends a request to a running daemon and prints the responses to stdout
+///
+/// Returns the process exit code: 0 if the daemon answered successfully,
+/// 1 if it reported an error.
+///
+/// # Errors
+///
+/// Returns `Error::Daemon` if the daemon can't be reached
+pub fn run_client(socket: &Path, request: &Request) -> Result<i32> {
+    let mut stream = UnixStream::connect(socket).map_err(|e| {
+        Error::Daemon(format!(
+            "could not connect to {} ({}); is the daemon running?",
+            socket.display(),
+            e
+        ))
+    })?;
+
+    let mut line = serde_json::to_string(request).map_err(|e| Error::Other(e.to_string()))?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let subscribe = *request == Request::Subscribe;
+    let mut stdout = std::io::stdout().lock();
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        writeln!(stdout, "{}", line)?;
+        stdout.flush()?;
+
+        if !subscribe {
+            let ok = serde_json::from_str::<serde_json::Value>(&line)
+                .ok()
+                .and_then(|v| v.get("ok").and_then(|ok| ok.as_bool()))
+                .unwrap_or(false);
+            return Ok(if ok { 0 } else { 1 });
+        }
+    }
+
+    Err(Error::Daemon("daemon closed the connection".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::RepoInfo;
+    use crate::diffstat::DiffStats;
+
+    fn state_with(statuses: Vec<RepoStatus>) -> State {
+        let state = State {
+            repos: Mutex::new(BTreeMap::new()),
+            subscribers: Mutex::new(Vec::new()),
+            watcher: RepoWatcher::new(&[], Duration::from_millis(10)).unwrap(),
+            options: DaemonOptions {
+                root: PathBuf::from("/p"),
+                max_depth: 1,
+                details: Details::default(),
+                debounce: Duration::from_millis(10),
+                socket: PathBuf::from("/unused"),
+            },
+        };
+        state.apply(statuses);
+        state
+    }
+
+    fn clean(path: &str) -> RepoStatus {
+        RepoStatus::Clean {
+            path: PathBuf::from(path),
+            branch: None,
+            info: RepoInfo::default(),
+        }
+    }
+
+    #[test]
+    fn test_request_protocol() {
+        let request: Request = serde_json::from_str(r#"{"cmd":"get","path":"/p/api"}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::Get {
+                path: PathBuf::from("/p/api")
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&Request::List).unwrap(),
+            r#"{"cmd":"list"}"#
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"cmd":"explode"}"#).is_err());
+    }
+
+    #[test]
+    fn test_get_finds_enclosing_repo() {
+        let state = state_with(vec![clean("/p/api"), clean("/p/web")]);
+
+        let response = state.handle(Request::Get {
+            path: PathBuf::from("/p/api/src"),
+        });
+        assert!(response.ok);
+        assert_eq!(response.repository.unwrap().path(), Path::new("/p/api"));
+
+        let response = state.handle(Request::Get {
+            path: PathBuf::from("/elsewhere"),
+        });
+        assert!(!response.ok);
+        assert!(response.error.unwrap().contains("unknown repository"));
+    }
+
+    #[test]
+    fn test_subscribers_receive_only_changes() {
+        let state = state_with(vec![clean("/p/api")]);
+        let (tx, rx) = mpsc::channel();
+        state.subscribers.lock().unwrap().push(tx);
+
+        state.apply(vec![clean("/p/api")]);
+        assert!(rx.try_recv().is_err());
+
+        let dirty = RepoStatus::Dirty {
+            path: PathBuf::from("/p/api"),
+            changes: " M a\n".to_string(),
+            diff: DiffStats::default(),
+            conflicts: Vec::new(),
+            branch: None,
+            info: RepoInfo::default(),
+        };
+        state.apply(vec![dirty.clone()]);
+        assert_eq!(rx.try_recv().unwrap(), dirty);
+    }
+
+    #[test]
+    fn test_default_socket_path() {
+        assert!(default_socket_path().ends_with(SOCKET_NAME));
+    }
+
+    #[test]
+    fn test_bind() {
+        let temp = tempfile::tempdir().unwrap();
+        let socket = temp.path().join("run/daemon.sock");
+
+        let listener = bind(&socket).unwrap();
+        let mode = fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(UnixStream::connect(&socket).is_ok());
+        assert!(bind(&socket).is_err());
+
+        // A socket left behind by a dead daemon is replaced
+        drop(listener);
+        assert!(bind(&socket).is_ok());
+        assert_eq!(fs::read_dir(temp.path().join("run")).unwrap().count(), 1);
+
+        let file = temp.path().join("notes.txt");
+        fs::write(&file, "keep me").unwrap();
+        assert!(bind(&file).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "keep me");
+    }
+}
@@ -0,0 +1,219 @@
+//! Divergence from the default branch
+//!
+//! This module reports how far the checked-out branch has drifted from the
+//! repository's default branch, as opposed to its own upstream. The default
+//! branch is whatever the primary remote's `HEAD` points at, falling back to
+//! a `main` or `master` branch on that remote or locally. A clean branch
+//! whose own commits have all landed on the default branch has been merged
+//! and can be deleted.
+
+use crate::core::{self, git_output};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Branch names tried, in order, when the remote's `HEAD` isn't set
+const FALLBACK_BRANCHES: [&str; 2] = ["main", "master"];
+
+/// How the checked-out branch relates to the default branch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DefaultBranch {
+    /// The default branch compared against, e.g. `origin/main`
+    pub name: String,
+    /// Commits on HEAD not on the default branch
+    pub ahead: usize,
+    /// Commits on the default branch not on HEAD
+    pub behind: usize,
+    /// Whether the branch's own commits are all on the default branch, so
+    /// it was merged and can be deleted
+    #[serde(default, skip_serializing_if = "core::is_false")]
+    pub merged: bool,
+}
+
+/// Returns true if a reflog records a commit made on the branch, as opposed
+/// to it being created, reset or fast-forwarded
+///
+/// Lines look like `<old> <new> <name> <<email>> <timestamp> <tz>\t<message>`.
+fn reflog_has_commit(reflog: &str) -> bool {
+    reflog
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .any(|(_, message)| message.starts_with("commit") || message.starts_with("cherry-pick"))
+}
+
+/// Returns true if the branch holds a commit of its own
+///
+/// That is the case when its reflog records a commit, or when its tip isn't
+/// on the first-parent line of the default branch, so it came in through a
+/// merge rather than being where the branch was created.
+fn has_own_commit(repo_path: &Path, branch: &str, default: &str) -> bool {
+    let reflog = fs::read_to_string(
+        core::common_git_dir(repo_path)
+            .join("logs/refs/heads")
+            .join(branch),
+    );
+    if reflog.is_ok_and(|log| reflog_has_commit(&log)) {
+        return true;
+    }
+    let (Some(tip), Some(first_parents)) = (
+        git_output(repo_path, &["rev-parse", "HEAD"]),
+        git_output(
+            repo_path,
+            &["rev-list", "--first-parent", default, "--not", "HEAD^@"],
+        ),
+    ) else {
+        return false;
+    };
+    !first_parents.lines().any(|commit| commit == tip)
+}
+
+/// Parses `git rev-list --left-right --count` output, `<left>\t<right>`
+fn parse_counts(output: &str) -> Option<(usize, usize)> {
+    let (left, right) = output.trim().split_once('\t')?;
+    Some((left.parse().ok()?, right.parse().ok()?))
+}
+
+/// Returns true if a branch is the default branch or tracks it by name
+fn is_default(branch: &str, default: &str, remote: Option<&str>) -> bool {
+    branch == default
+        || remote.is_some_and(|remote| {
+            default
+                .strip_prefix(remote)
+                .and_then(|rest| rest.strip_prefix('/'))
+                == Some(branch)
+        })
+}
+
+/// Lists the refs that may name the default branch, most preferred first
+///
+/// They are looked up in one `git for-each-ref` call together with HEAD's
+/// branch, so finding the default branch doesn't cost a command per guess.
+pub fn candidate_refs(remote: Option<&str>) -> Vec<String> {
+    let remote_refs = remote.into_iter().flat_map(|remote| {
+        std::iter::once("HEAD")
+            .chain(FALLBACK_BRANCHES)
+            .map(move |branch| format!("refs/remotes/{remote}/{branch}"))
+    });
+    let local_refs = FALLBACK_BRANCHES
+        .iter()
+        .map(|branch| format!("refs/heads/{branch}"));
+    remote_refs.chain(local_refs).collect()
+}
+
+/// Shortens a full ref name, e.g. `refs/remotes/origin/main` to `origin/main`
+fn short_name(refname: &str) -> &str {
+    refname
+        .strip_prefix("refs/remotes/")
+        .or_else(|| refname.strip_prefix("refs/heads/"))
+        .unwrap_or(refname)
+}
+
+/// Picks the default branch from the existing refs, given as
+/// `(refname, symref)` pairs
+///
+/// Returns a short ref name such as `origin/main`, or `main` when there is
+/// no remote to ask.
+pub fn pick_default_branch(refs: &[(&str, &str)], remote: Option<&str>) -> Option<String> {
+    candidate_refs(remote).iter().find_map(|candidate| {
+        let (refname, symref) = refs.iter().find(|(refname, _)| refname == candidate)?;
+        if !symref.is_empty() {
+            Some(short_name(symref).to_string())
+        } else if !refname.ends_with("/HEAD") {
+            Some(short_name(refname).to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Compares the checked-out branch with the default branch
+///
+/// A branch counts as merged when it is clean, has no commits beyond the
+/// default branch, isn't at the same commit, and has a commit of its own, so
+/// a branch that was just created is never flagged. Returns `None` when the
+/// repository has no default branch or the branch is the default branch
+/// itself, whose drift its upstream already covers.
+pub fn check_default_branch(
+    repo_path: &Path,
+    branch: &str,
+    name: String,
+    remote: Option<&str>,
+    dirty: bool,
+) -> Option<DefaultBranch> {
+    if is_default(branch, &name, remote) {
+        return None;
+    }
+    let range = format!("HEAD...{name}");
+    let counts = git_output(repo_path, &["rev-list", "--left-right", "--count", &range])?;
+    let (ahead, behind) = parse_counts(&counts)?;
+    let merged = !dirty && ahead == 0 && behind > 0 && has_own_commit(repo_path, branch, &name);
+    Some(DefaultBranch {
+        name,
+        ahead,
+        behind,
+        merged,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_counts() {
+        assert_eq!(parse_counts("3\t12\n"), Some((3, 12)));
+        assert_eq!(parse_counts("0\t0"), Some((0, 0)));
+        assert_eq!(parse_counts(""), None);
+        assert_eq!(parse_counts("x\t1"), None);
+    }
+
+    #[test]
+    fn test_is_default() {
+        assert!(is_default("main", "origin/main", Some("origin")));
+        assert!(is_default("main", "main", None));
+        assert!(!is_default("feature", "origin/main", Some("origin")));
+        assert!(!is_default(
+            "origin/main",
+            "upstream/main",
+            Some("upstream")
+        ));
+        assert!(!is_default("main", "origin/master", Some("origin")));
+    }
+
+    #[test]
+    fn test_pick_default_branch() {
+        let refs = [
+            ("refs/heads/master", ""),
+            ("refs/remotes/origin/HEAD", "refs/remotes/origin/trunk"),
+            ("refs/remotes/origin/master", ""),
+        ];
+        assert_eq!(
+            pick_default_branch(&refs, Some("origin")),
+            Some("origin/trunk".to_string())
+        );
+        assert_eq!(
+            pick_default_branch(&refs[..1], Some("origin")),
+            Some("master".to_string())
+        );
+        assert_eq!(
+            pick_default_branch(&[refs[0], refs[2]], Some("origin")),
+            Some("origin/master".to_string())
+        );
+        assert_eq!(pick_default_branch(&refs, None), Some("master".to_string()));
+        assert_eq!(pick_default_branch(&[], Some("origin")), None);
+    }
+
+    #[test]
+    fn test_reflog_has_commit() {
+        let line = |message: &str| {
+            format!("0000000 1a2b3c4 Test User <t@example.com> 1700000000 +0100\t{message}\n")
+        };
+        assert!(!reflog_has_commit(&line("branch: Created from HEAD")));
+        assert!(reflog_has_commit(
+            &(line("branch: Created from HEAD") + &line("commit: Add parser"))
+        ));
+        assert!(reflog_has_commit(&line("commit (amend): Fix parser")));
+        assert!(!reflog_has_commit(&line("merge main: Fast-forward")));
+        assert!(!reflog_has_commit(""));
+    }
+}
@@ -0,0 +1,133 @@
+//! Diff statistics for dirty repositories
+//!
+//! This module counts the files changed and lines added and removed in a
+//! repository's staged and unstaged changes, from `git diff --numstat`, so
+//! one changed character can be told apart from thousands of changed lines.
+//! Untracked files aren't part of a diff and aren't counted.
+
+use crate::core;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+/// Files changed and lines added and removed in one diff
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LineStats {
+    pub files: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Staged and unstaged changes of a repository
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffStats {
+    /// Changes in the index, from `git diff --cached`
+    pub staged: LineStats,
+    /// Changes in the working tree, from `git diff`
+    pub unstaged: LineStats,
+}
+
+impl DiffStats {
+    pub fn is_empty(&self) -> bool {
+        *self == DiffStats::default()
+    }
+
+    pub fn added(&self) -> usize {
+        self.staged.added + self.unstaged.added
+    }
+
+    pub fn removed(&self) -> usize {
+        self.staged.removed + self.unstaged.removed
+    }
+
+    /// Lines added and removed, staged or not
+    pub fn lines_changed(&self) -> usize {
+        self.added() + self.removed()
+    }
+}
+
+/// Parses `git diff --numstat` output
+///
+/// Each line is `<added>\t<removed>\t<path>`. Binary files show `-` for
+/// both counts and only count as a changed file.
+fn parse_numstat(output: &str) -> LineStats {
+    let mut stats = LineStats::default();
+    for line in output.lines() {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(added), Some(removed), Some(_)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        stats.files += 1;
+        stats.added += added.parse().unwrap_or(0);
+        stats.removed += removed.parse().unwrap_or(0);
+    }
+    stats
+}
+
+/// Runs `git diff --numstat` with extra arguments and parses its output
+fn numstat(repo_path: &Path, args: &[&str]) -> LineStats {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(repo_path)
+        .arg("--no-optional-locks")
+        .arg("diff")
+        .arg("--numstat")
+        .args(args);
+    core::run_git(&mut command, None)
+        .ok()
+        .flatten()
+        .filter(|output| output.status.success())
+        .map(|output| parse_numstat(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+}
+
+/// Gets the staged and unstaged diff statistics of a repository
+pub fn get_diff_stats(repo_path: &Path) -> DiffStats {
+    DiffStats {
+        staged: numstat(repo_path, &["--cached"]),
+        unstaged: numstat(repo_path, &[]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_numstat() {
+        let output = "120\t4\tsrc/main.rs\n0\t30\tREADME.md\n-\t-\tassets/logo.png\n";
+        assert_eq!(
+            parse_numstat(output),
+            LineStats {
+                files: 3,
+                added: 120,
+                removed: 34,
+            }
+        );
+        assert_eq!(parse_numstat(""), LineStats::default());
+    }
+
+    #[test]
+    fn test_totals() {
+        let stats = DiffStats {
+            staged: LineStats {
+                files: 1,
+                added: 100,
+                removed: 4,
+            },
+            unstaged: LineStats {
+                files: 2,
+                added: 20,
+                removed: 30,
+            },
+        };
+        assert_eq!((stats.added(), stats.removed()), (120, 34));
+        assert_eq!(stats.lines_changed(), 154);
+        assert!(!stats.is_empty());
+        assert!(DiffStats::default().is_empty());
+    }
+}
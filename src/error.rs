@@ -2,6 +2,7 @@
 
 use std::fmt;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Custom error types for check-git-status
 ///
@@ -21,6 +22,31 @@ pub enum Error {
     /// Git command failed
     GitCommandFailed { repo: PathBuf, message: String },
 
+    /// Git command took longer than the configured timeout
+    GitTimeout { repo: PathBuf, timeout: Duration },
+
+    /// Query expression could not be parsed
+    InvalidQuery {
+        query: String,
+        position: usize,
+        message: String,
+    },
+
+    /// Filesystem watching failed
+    Watch(String),
+
+    /// Daemon could not be started or reached
+    Daemon(String),
+
+    /// Snapshot file could not be read
+    Snapshot { path: PathBuf, message: String },
+
+    /// History database could not be opened or queried
+    History(String),
+
+    /// Policy file could not be loaded
+    Policy { path: PathBuf, message: String },
+
     /// IO error occurred
     Io(std::io::Error),
 
@@ -43,6 +69,44 @@ impl fmt::Display for Error {
             Error::GitCommandFailed { repo, message } => {
                 write!(f, "Git command failed in {}: {}", repo.display(), message)
             }
+            Error::GitTimeout { repo, timeout } => {
+                write!(
+                    f,
+                    "Git command timed out in {} after {}s",
+                    repo.display(),
+                    timeout.as_secs()
+                )
+            }
+            Error::InvalidQuery {
+                query,
+                position,
+                message,
+            } => {
+                let column = query[..*position].chars().count();
+                write!(
+                    f,
+                    "Invalid query at column {}: {}\n  {}\n  {}^",
+                    column + 1,
+                    message,
+                    query,
+                    " ".repeat(column)
+                )
+            }
+            Error::Watch(msg) => {
+                write!(f, "Watch error: {}", msg)
+            }
+            Error::Daemon(msg) => {
+                write!(f, "Daemon error: {}", msg)
+            }
+            Error::Snapshot { path, message } => {
+                write!(f, "Invalid snapshot {}: {}", path.display(), message)
+            }
+            Error::History(msg) => {
+                write!(f, "History error: {}", msg)
+            }
+            Error::Policy { path, message } => {
+                write!(f, "Invalid policy {}: {}", path.display(), message)
+            }
             Error::Io(e) => {
                 write!(f, "IO error: {}", e)
             }
@@ -99,6 +163,73 @@ mod tests {
         assert!(display.contains("command not found"));
     }
 
+    #[test]
+    fn test_error_display_git_timeout() {
+        let err = Error::GitTimeout {
+            repo: PathBuf::from("/test/repo"),
+            timeout: Duration::from_secs(5),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Git command timed out in /test/repo after 5s"
+        );
+    }
+
+    #[test]
+    fn test_error_display_invalid_query() {
+        let err = Error::InvalidQuery {
+            query: "dirty and".to_string(),
+            position: 9,
+            message: "expected a field or '('".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Invalid query at column 10: expected a field or '('\n  dirty and\n           ^"
+        );
+    }
+
+    #[test]
+    fn test_error_display_watch() {
+        let err = Error::Watch("too many open files".to_string());
+        assert_eq!(err.to_string(), "Watch error: too many open files");
+    }
+
+    #[test]
+    fn test_error_display_daemon() {
+        let err = Error::Daemon("is the daemon running?".to_string());
+        assert_eq!(err.to_string(), "Daemon error: is the daemon running?");
+    }
+
+    #[test]
+    fn test_error_display_snapshot() {
+        let err = Error::Snapshot {
+            path: PathBuf::from("/tmp/old.json"),
+            message: "unsupported version 7".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Invalid snapshot /tmp/old.json: unsupported version 7"
+        );
+    }
+
+    #[test]
+    fn test_error_display_history() {
+        let err = Error::History("database is locked".to_string());
+        assert_eq!(err.to_string(), "History error: database is locked");
+    }
+
+    #[test]
+    fn test_error_display_policy() {
+        let err = Error::Policy {
+            path: PathBuf::from("policy.toml"),
+            message: "rule 'x': unknown tag 'nope'".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Invalid policy policy.toml: rule 'x': unknown tag 'nope'"
+        );
+    }
+
     #[test]
     fn test_error_from_io_error() {
         let io_err = std::io::Error::new(std::io::ErrorKind::NotFound, "file not found");
@@ -0,0 +1,196 @@
+//! Exit code semantics
+//!
+//! This module maps the outcome of a run to a process exit code according
+//! to the mode selected with `--exit-code`.
+
+use crate::core::RepoStatus;
+use crate::error::Error;
+use crate::policy::Severity;
+use clap::ValueEnum;
+
+/// Bit set when any repository is dirty
+pub const DIRTY_BIT: i32 = 1;
+/// Bit set when any repository is ahead of its upstream
+pub const AHEAD_BIT: i32 = 2;
+/// Bit set when a repository couldn't be checked, or the run failed
+pub const ERROR_BIT: i32 = 4;
+/// Bit set when a git command timed out
+pub const TIMEOUT_BIT: i32 = 8;
+/// Bit set when a policy rule above `info` was broken
+pub const POLICY_BIT: i32 = 16;
+/// Bit set when any repository has unmerged paths
+pub const CONFLICT_BIT: i32 = 32;
+
+/// How the exit code is computed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExitCodeMode {
+    /// Number of dirty repos, capped at 255
+    #[default]
+    Count,
+    /// 0 if all clean, 1 if any repo is dirty, 2 on errors
+    Boolean,
+    /// Sum of bits: 1 dirty, 2 ahead, 4 errors, 8 timeouts, 16 policy
+    /// violations, 32 conflicts
+    Bitmask,
+    /// Always 0
+    Zero,
+}
+
+/// What a scan found, as far as exit codes are concerned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Outcome {
+    pub dirty: usize,
+    pub ahead: bool,
+    /// Whether any repository has unmerged paths
+    pub conflicts: bool,
+    pub errors: usize,
+    pub timeouts: usize,
+    /// Most serious policy violation, if a policy was evaluated
+    pub policy: Option<Severity>,
+}
+
+impl Outcome {
+    /// Summarizes the checked repositories and the failed checks
+    pub fn new(statuses: &[RepoStatus], errors: &[Error], policy: Option<Severity>) -> Outcome {
+        let timeouts = errors
+            .iter()
+            .filter(|e| matches!(e, Error::GitTimeout { .. }))
+            .count();
+        Outcome {
+            dirty: statuses.iter().filter(|s| s.is_dirty()).count(),
+            ahead: statuses
+                .iter()
+                .any(|s| s.info().ahead.is_some_and(|n| n > 0)),
+            conflicts: statuses.iter().any(|s| !s.conflicts().is_empty()),
+            errors: errors.len() - timeouts,
+            timeouts,
+            policy,
+        }
+    }
+
+    /// Exit code of a policy run without `--exit-code`
+    ///
+    /// That of the most serious violation, but 2 like an `error` violation
+    /// if a repository couldn't be checked.
+    pub fn policy_code(&self) -> i32 {
+        let failed = if self.errors + self.timeouts > 0 {
+            Severity::Error.exit_code()
+        } else {
+            0
+        };
+        self.policy.map_or(0, Severity::exit_code).max(failed)
+    }
+
+    fn policy_violated(&self) -> bool {
+        self.policy.is_some_and(|s| s > Severity::Info)
+    }
+}
+
+impl ExitCodeMode {
+    /// Computes the exit code of a completed run
+    pub fn code(self, outcome: &Outcome) -> i32 {
+        match self {
+            ExitCodeMode::Count => outcome.dirty.min(255) as i32,
+            ExitCodeMode::Boolean => {
+                if outcome.errors + outcome.timeouts > 0 {
+                    2
+                } else if outcome.dirty > 0 || outcome.policy_violated() {
+                    1
+                } else {
+                    0
+                }
+            }
+            ExitCodeMode::Bitmask => {
+                let bits = [
+                    (outcome.dirty > 0, DIRTY_BIT),
+                    (outcome.ahead, AHEAD_BIT),
+                    (outcome.errors > 0, ERROR_BIT),
+                    (outcome.timeouts > 0, TIMEOUT_BIT),
+                    (outcome.policy_violated(), POLICY_BIT),
+                    (outcome.conflicts, CONFLICT_BIT),
+                ];
+                bits.iter()
+                    .filter(|(set, _)| *set)
+                    .fold(0, |code, (_, bit)| code | bit)
+            }
+            ExitCodeMode::Zero => 0,
+        }
+    }
+
+    /// Exit code for a run that failed before producing results
+    pub fn failure_code(self) -> i32 {
+        match self {
+            ExitCodeMode::Count => 1,
+            ExitCodeMode::Boolean => 2,
+            ExitCodeMode::Bitmask => ERROR_BIT,
+            ExitCodeMode::Zero => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(dirty: usize, errors: usize, timeouts: usize) -> Outcome {
+        Outcome {
+            dirty,
+            errors,
+            timeouts,
+            ..Outcome::default()
+        }
+    }
+
+    #[test]
+    fn test_count_and_boolean() {
+        assert_eq!(ExitCodeMode::Count.code(&outcome(300, 0, 0)), 255);
+        assert_eq!(ExitCodeMode::Count.code(&outcome(3, 1, 0)), 3);
+        assert_eq!(ExitCodeMode::Boolean.code(&outcome(0, 0, 0)), 0);
+        assert_eq!(ExitCodeMode::Boolean.code(&outcome(5, 0, 0)), 1);
+        assert_eq!(ExitCodeMode::Boolean.code(&outcome(5, 0, 1)), 2);
+    }
+
+    #[test]
+    fn test_bitmask() {
+        let all = Outcome {
+            dirty: 1,
+            ahead: true,
+            conflicts: true,
+            errors: 1,
+            timeouts: 1,
+            policy: Some(Severity::Warning),
+        };
+        assert_eq!(ExitCodeMode::Bitmask.code(&all), 63);
+        assert_eq!(ExitCodeMode::Bitmask.code(&outcome(2, 0, 1)), 9);
+
+        let info_only = Outcome {
+            policy: Some(Severity::Info),
+            ..Outcome::default()
+        };
+        assert_eq!(ExitCodeMode::Bitmask.code(&info_only), 0);
+    }
+
+    #[test]
+    fn test_policy_code() {
+        let warning = Outcome {
+            policy: Some(Severity::Warning),
+            ..Outcome::default()
+        };
+        assert_eq!(warning.policy_code(), 1);
+        assert_eq!(Outcome::default().policy_code(), 0);
+        assert_eq!(outcome(0, 1, 0).policy_code(), 2);
+        let timed_out = Outcome {
+            timeouts: 1,
+            ..warning
+        };
+        assert_eq!(timed_out.policy_code(), 2);
+    }
+
+    #[test]
+    fn test_zero_and_failures() {
+        assert_eq!(ExitCodeMode::Zero.code(&outcome(5, 5, 5)), 0);
+        assert_eq!(ExitCodeMode::Zero.failure_code(), 0);
+        assert_eq!(ExitCodeMode::Boolean.failure_code(), 2);
+        assert_eq!(ExitCodeMode::Bitmask.failure_code(), ERROR_BIT);
+    }
+}
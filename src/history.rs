@@ -0,0 +1,535 @@
+//! Historical status database
+//!
+//! This module records the results of runs in a SQLite database, when asked
+//! to, and builds trend reports from it: how long repositories have been dirty,
+//! which repositories went longest without a commit, and the number of
+//! dirty repositories over time.
+
+use crate::error::{Error, Result};
+use crate::output::{self, PathStyle};
+use crate::snapshot::Snapshot;
+use colored::*;
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Database schema version, stored in `PRAGMA user_version`
+const SCHEMA_VERSION: i64 = 1;
+
+/// Number of runs shown in the sparkline
+const SPARKLINE_RUNS: usize = 40;
+
+/// Number of repositories listed per report section
+const REPORT_LIMIT: usize = 10;
+
+/// Runs older than this are deleted whenever a run is recorded
+const RETENTION: Duration = Duration::from_secs(180 * 24 * 60 * 60);
+
+/// How long to wait for another process holding the database lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        started INTEGER NOT NULL,
+        root TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS results (
+        run_id INTEGER NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+        path TEXT NOT NULL,
+        dirty INTEGER NOT NULL,
+        changes INTEGER NOT NULL,
+        branch TEXT,
+        ahead INTEGER,
+        behind INTEGER,
+        last_commit INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS runs_root ON runs(root, started);
+    CREATE INDEX IF NOT EXISTS results_run ON results(run_id);
+    CREATE INDEX IF NOT EXISTS results_path ON results(path, dirty);
+";
+
+fn db_error(e: rusqlite::Error) -> Error {
+    Error::History(e.to_string())
+}
+
+/// A repository that has been dirty since some run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DirtyRepo {
+    pub path: PathBuf,
+    /// Unix timestamp of the first run of the current dirty streak
+    pub since: i64,
+}
+
+/// A repository and the time of its last commit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StaleRepo {
+    pub path: PathBuf,
+    pub last_commit: i64,
+    pub dirty: bool,
+}
+
+/// Repository counts of one run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RunCount {
+    pub started: i64,
+    pub total: usize,
+    pub dirty: usize,
+}
+
+/// Trends for one scan root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub root: PathBuf,
+    /// Number of recorded runs
+    pub runs: usize,
+    /// Dirty repositories in the latest run, longest dirty first
+    pub dirty_since: Vec<DirtyRepo>,
+    /// Repositories in the latest run with the oldest last commits
+    pub least_recent_commits: Vec<StaleRepo>,
+    /// Counts of the most recent runs, oldest first
+    pub dirty_counts: Vec<RunCount>,
+}
+
+/// Run history backed by a SQLite database
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// Gets the default database location under the XDG data directory
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("check-git-status").join("history.sqlite3"))
+    }
+
+    /// Opens the database, creating it if needed
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::History` if the database can't be opened or was
+    /// created by a newer version
+    pub fn open(path: &Path) -> Result<History> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path).map_err(db_error)?;
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<History> {
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(db_error)?;
+        let version: i64 = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(db_error)?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::History(format!(
+                "database schema version {} is newer than supported ({})",
+                version, SCHEMA_VERSION
+            )));
+        }
+        conn.execute_batch("PRAGMA foreign_keys = ON;")
+            .and_then(|_| conn.execute_batch(SCHEMA))
+            .and_then(|_| conn.pragma_update(None, "user_version", SCHEMA_VERSION))
+            .map_err(db_error)?;
+        Ok(History { conn })
+    }
+
+    /// Records the results of a run, deleting runs older than the retention
+    /// period
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::History` if the results can't be written
+    pub fn record(&mut self, snapshot: &Snapshot) -> Result<()> {
+        let tx = self.conn.transaction().map_err(db_error)?;
+        tx.execute(
+            "INSERT INTO runs (started, root) VALUES (?1, ?2)",
+            params![snapshot.created, snapshot.root.to_string_lossy()],
+        )
+        .map_err(db_error)?;
+        let run_id = tx.last_insert_rowid();
+        tx.execute(
+            "DELETE FROM runs WHERE started < ?1",
+            params![snapshot.created - RETENTION.as_secs() as i64],
+        )
+        .map_err(db_error)?;
+
+        {
+            let mut insert = tx
+                .prepare(
+                    "INSERT INTO results
+                     (run_id, path, dirty, changes, branch, ahead, behind, last_commit)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                )
+                .map_err(db_error)?;
+            for status in &snapshot.repositories {
+                let info = status.info();
+                insert
+                    .execute(params![
+                        run_id,
+                        status.path().to_string_lossy(),
+                        status.is_dirty(),
+                        status.change_count() as i64,
+                        status.branch(),
+                        info.ahead.map(|n| n as i64),
+                        info.behind.map(|n| n as i64),
+                        info.last_commit.as_ref().map(|c| c.timestamp),
+                    ])
+                    .map_err(db_error)?;
+            }
+        }
+        tx.commit().map_err(db_error)
+    }
+
+    /// Builds the trend report for a scan root
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::History` if the database can't be queried
+    pub fn report(&self, root: &Path) -> Result<Report> {
+        let root_str = root.to_string_lossy();
+        let runs: usize = self
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM runs WHERE root = ?1",
+                params![root_str],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(db_error)? as usize;
+
+        let mut report = Report {
+            root: root.to_path_buf(),
+            runs,
+            dirty_since: Vec::new(),
+            least_recent_commits: Vec::new(),
+            dirty_counts: self.dirty_counts(&root_str)?,
+        };
+        let Some(latest) = self.latest_run(&root_str)? else {
+            return Ok(report);
+        };
+
+        report.dirty_since = self.dirty_streaks(&root_str, latest)?;
+        report.least_recent_commits = self.least_recent_commits(latest)?;
+        Ok(report)
+    }
+
+    fn latest_run(&self, root: &str) -> Result<Option<i64>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM runs WHERE root = ?1 ORDER BY started DESC, id DESC LIMIT 1")
+            .map_err(db_error)?;
+        let mut rows = stmt.query(params![root]).map_err(db_error)?;
+        match rows.next().map_err(db_error)? {
+            Some(row) => Ok(Some(row.get(0).map_err(db_error)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn dirty_counts(&self, root: &str) -> Result<Vec<RunCount>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT runs.started, COUNT(results.path), COALESCE(SUM(results.dirty), 0)
+                 FROM runs LEFT JOIN results ON results.run_id = runs.id
+                 WHERE runs.root = ?1
+                 GROUP BY runs.id
+                 ORDER BY runs.started DESC, runs.id DESC
+                 LIMIT ?2",
+            )
+            .map_err(db_error)?;
+        let mut counts = stmt
+            .query_map(params![root, SPARKLINE_RUNS as i64], |row| {
+                Ok(RunCount {
+                    started: row.get(0)?,
+                    total: row.get::<_, i64>(1)? as usize,
+                    dirty: row.get::<_, i64>(2)? as usize,
+                })
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(db_error)?;
+        counts.reverse();
+        Ok(counts)
+    }
+
+    /// Finds when the current dirty streak of each dirty repository began
+    ///
+    /// A streak begins with the first dirty run after the last clean one.
+    /// Only the history of repositories dirty in the latest run is read.
+    fn dirty_streaks(&self, root: &str, latest: i64) -> Result<Vec<DirtyRepo>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "WITH dirty_now AS (
+                     SELECT path FROM results WHERE run_id = ?2 AND dirty
+                 ),
+                 history AS (
+                     SELECT results.path, results.dirty, runs.started
+                     FROM results
+                     JOIN runs ON results.run_id = runs.id
+                     JOIN dirty_now ON dirty_now.path = results.path
+                     WHERE runs.root = ?1
+                 ),
+                 last_clean AS (
+                     SELECT path, MAX(started) AS started
+                     FROM history WHERE NOT dirty GROUP BY path
+                 )
+                 SELECT history.path, MIN(history.started) AS since
+                 FROM history LEFT JOIN last_clean ON last_clean.path = history.path
+                 WHERE history.dirty
+                   AND (last_clean.started IS NULL OR history.started > last_clean.started)
+                 GROUP BY history.path
+                 ORDER BY since, history.path",
+            )
+            .map_err(db_error)?;
+        stmt.query_map(params![root, latest], |row| {
+            Ok(DirtyRepo {
+                path: PathBuf::from(row.get::<_, String>(0)?),
+                since: row.get(1)?,
+            })
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(db_error)
+    }
+
+    fn least_recent_commits(&self, latest: i64) -> Result<Vec<StaleRepo>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT path, last_commit, dirty FROM results
+                 WHERE run_id = ?1 AND last_commit IS NOT NULL
+                 ORDER BY last_commit, path
+                 LIMIT ?2",
+            )
+            .map_err(db_error)?;
+        stmt.query_map(params![latest, REPORT_LIMIT as i64], |row| {
+            Ok(StaleRepo {
+                path: PathBuf::from(row.get::<_, String>(0)?),
+                last_commit: row.get(1)?,
+                dirty: row.get(2)?,
+            })
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(db_error)
+    }
+}
+
+/// Renders values as a sparkline scaled to the largest value
+pub fn sparkline(values: &[usize], ticks: &[char]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&v| {
+            let index = (v * (ticks.len() - 1)).checked_div(max).unwrap_or(0);
+            ticks[index]
+        })
+        .collect()
+}
+
+/// Formats the time between two unix timestamps, e.g. "3 weeks"
+fn format_age(since: i64, now: i64) -> String {
+    output::humanize_age(Duration::from_secs((now - since).max(0) as u64))
+}
+
+/// Formats a repository path relative to the scan root
+fn display_path(path: &Path, root: &Path) -> String {
+    output::styled_path(path, root, PathStyle::Relative)
+}
+
+/// Renders the report as human-readable lines, measuring ages from `now`
+pub fn render(report: &Report, now: i64) -> Vec<String> {
+    let symbols = output::symbols();
+    let mut lines = vec![format!(
+        "{} History for {} ({} run{})",
+        symbols.search.cyan(),
+        report.root.display().to_string().bright_blue(),
+        report.runs,
+        if report.runs == 1 { "" } else { "s" }
+    )];
+    if report.runs == 0 {
+        return lines;
+    }
+
+    let dirty: Vec<usize> = report.dirty_counts.iter().map(|c| c.dirty).collect();
+    if let (Some(first), Some(last)) = (report.dirty_counts.first(), report.dirty_counts.last()) {
+        lines.push(format!(
+            "Dirty repos {} {} (max {}, now {})",
+            sparkline(&dirty, symbols.spark).yellow(),
+            format!(
+                "{} {} {}",
+                output::format_time(first.started),
+                symbols.arrow,
+                output::format_time(last.started)
+            )
+            .bright_black(),
+            dirty.iter().max().unwrap_or(&0),
+            last.dirty
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("Dirty the longest:".bold().to_string());
+    if report.dirty_since.is_empty() {
+        lines.push(format!("  {} no dirty repos", symbols.clean.green()));
+    }
+    for repo in report.dirty_since.iter().take(REPORT_LIMIT) {
+        lines.push(format!(
+            "  {} {} dirty for {} (since {})",
+            symbols.dirty.yellow(),
+            display_path(&repo.path, &report.root).yellow().bold(),
+            format_age(repo.since, now),
+            output::format_time(repo.since)
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("Longest without a commit:".bold().to_string());
+    for repo in &report.least_recent_commits {
+        let marker = if repo.dirty {
+            symbols.dirty.yellow()
+        } else {
+            symbols.clean.green()
+        };
+        lines.push(format!(
+            "  {} {} last commit {} ago",
+            marker,
+            display_path(&repo.path, &report.root),
+            format_age(repo.last_commit, now)
+        ));
+    }
+    lines
+}
+
+/// Prints the report in human-readable form
+pub fn print_report(report: &Report) {
+    for line in render(report, output::unix_now()) {
+        eprintln!("{}", line);
+    }
+    eprintln!();
+}
+
+/// Prints the report as JSON
+pub fn print_report_json(report: &Report) -> std::result::Result<(), serde_json::Error> {
+    println!("{}", serde_json::to_string_pretty(report)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{LastCommit, RepoInfo, RepoStatus};
+    use crate::diffstat::DiffStats;
+
+    fn repo(path: &str, dirty: bool, last_commit: i64) -> RepoStatus {
+        let info = RepoInfo {
+            last_commit: Some(LastCommit::at(last_commit)),
+            ..RepoInfo::default()
+        };
+        let path = PathBuf::from(path);
+        if dirty {
+            RepoStatus::Dirty {
+                path,
+                changes: " M a\n".to_string(),
+                diff: DiffStats::default(),
+                conflicts: Vec::new(),
+                branch: None,
+                info,
+            }
+        } else {
+            RepoStatus::Clean {
+                path,
+                branch: None,
+                info,
+            }
+        }
+    }
+
+    fn record(history: &mut History, created: i64, repositories: Vec<RepoStatus>) {
+        let snapshot = Snapshot {
+            created,
+            repositories,
+            ..Snapshot::new(Path::new("/p"), &[])
+        };
+        history.record(&snapshot).unwrap();
+    }
+
+    fn sample() -> History {
+        let mut history = History::init(Connection::open_in_memory().unwrap()).unwrap();
+        record(
+            &mut history,
+            100,
+            vec![repo("/p/api", true, 10), repo("/p/web", false, 50)],
+        );
+        record(
+            &mut history,
+            200,
+            vec![repo("/p/api", false, 150), repo("/p/web", true, 50)],
+        );
+        record(
+            &mut history,
+            300,
+            vec![repo("/p/api", true, 150), repo("/p/web", true, 50)],
+        );
+        history
+    }
+
+    #[test]
+    fn test_report_dirty_streaks() {
+        let report = sample().report(Path::new("/p")).unwrap();
+        assert_eq!(report.runs, 3);
+        assert_eq!(
+            report.dirty_since,
+            [
+                DirtyRepo {
+                    path: PathBuf::from("/p/web"),
+                    since: 200
+                },
+                DirtyRepo {
+                    path: PathBuf::from("/p/api"),
+                    since: 300
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_counts_and_commits() {
+        let report = sample().report(Path::new("/p")).unwrap();
+        let dirty: Vec<usize> = report.dirty_counts.iter().map(|c| c.dirty).collect();
+        assert_eq!(dirty, [1, 1, 2]);
+        assert_eq!(report.least_recent_commits[0].path, Path::new("/p/web"));
+        assert_eq!(report.least_recent_commits[0].last_commit, 50);
+    }
+
+    #[test]
+    fn test_report_other_root_is_empty() {
+        let report = sample().report(Path::new("/other")).unwrap();
+        assert_eq!(report.runs, 0);
+        assert!(report.dirty_counts.is_empty());
+    }
+
+    #[test]
+    fn test_record_prunes_old_runs() {
+        let mut history = sample();
+        let later = 300 + RETENTION.as_secs() as i64 + 50;
+        record(&mut history, later, vec![repo("/p/api", true, 150)]);
+        let report = history.report(Path::new("/p")).unwrap();
+        assert_eq!(report.runs, 1);
+        assert_eq!(report.dirty_since[0].since, later);
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[0, 2, 4, 8], output::UNICODE.spark), "▁▂▄█");
+        assert_eq!(sparkline(&[0, 0], output::UNICODE.spark), "▁▁");
+        assert_eq!(sparkline(&[], output::ASCII.spark), "");
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(100, 130), "less than a minute");
+        assert_eq!(format_age(0, 7200), "2 hours");
+        assert_eq!(format_age(0, 3 * 86400 + 5), "3 days");
+    }
+}
@@ -0,0 +1,202 @@
+//! Repository size and housekeeping
+//!
+//! This module reports how much space a repository's git directory takes,
+//! how many loose objects and packfiles it holds, and whether
+//! `git gc --auto` would repack it. It also notes shallow clones and sparse
+//! checkouts, which hold less than the full history or tree. Repositories
+//! that need it can have `git gc` run on them in parallel.
+
+use crate::core::{self, RepoStatus};
+use crate::error::{Error, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use walkdir::WalkDir;
+
+/// Loose objects above which `git gc --auto` repacks, unless `gc.auto` is set
+const DEFAULT_GC_AUTO: u64 = 6700;
+
+/// Packfiles above which `git gc --auto` consolidates them, unless
+/// `gc.autoPackLimit` is set
+const DEFAULT_GC_AUTO_PACK_LIMIT: u64 = 50;
+
+/// Size and maintenance state of a repository's git directory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Housekeeping {
+    /// Total size in bytes of the git directory
+    pub git_size: u64,
+    pub loose_objects: u64,
+    pub packs: u64,
+    /// Whether `git gc --auto` would run
+    pub needs_gc: bool,
+    pub shallow: bool,
+    pub sparse: bool,
+}
+
+/// Counts reported by `git count-objects -v`
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ObjectCounts {
+    loose: u64,
+    packs: u64,
+}
+
+/// Parses `git count-objects -v` output, made of `key: value` lines
+fn parse_count_objects(output: &str) -> ObjectCounts {
+    let mut counts = ObjectCounts::default();
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(": ") else {
+            continue;
+        };
+        let value = value.trim().parse().unwrap_or(0);
+        match key {
+            "count" => counts.loose = value,
+            "packs" => counts.packs = value,
+            _ => {}
+        }
+    }
+    counts
+}
+
+/// Returns true if `git gc --auto` would run for these counts
+///
+/// A threshold of zero disables that check, as it does for git.
+fn needs_gc(counts: &ObjectCounts, gc_auto: u64, pack_limit: u64) -> bool {
+    (gc_auto > 0 && counts.loose > gc_auto) || (pack_limit > 0 && counts.packs > pack_limit)
+}
+
+/// Reads a numeric git config value
+fn config_number(repo_path: &Path, key: &str, default: u64) -> u64 {
+    core::git_output(repo_path, &["config", "--type=int", "--get", key])
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Sums the sizes of the files in a directory
+fn dir_size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Inspects the git directory of a repository
+///
+/// Returns `None` if git can't count its objects.
+pub fn inspect(repo_path: &Path) -> Option<Housekeeping> {
+    let git_dir = PathBuf::from(core::git_output(
+        repo_path,
+        &["rev-parse", "--absolute-git-dir"],
+    )?);
+    let counts = parse_count_objects(&core::git_output(repo_path, &["count-objects", "-v"])?);
+    let gc_auto = config_number(repo_path, "gc.auto", DEFAULT_GC_AUTO);
+    let pack_limit = config_number(repo_path, "gc.autoPackLimit", DEFAULT_GC_AUTO_PACK_LIMIT);
+    Some(Housekeeping {
+        git_size: dir_size(&git_dir),
+        loose_objects: counts.loose,
+        packs: counts.packs,
+        needs_gc: needs_gc(&counts, gc_auto, pack_limit),
+        shallow: git_dir.join("shallow").is_file(),
+        sparse: core::git_output(
+            repo_path,
+            &["config", "--type=bool", "--get", "core.sparseCheckout"],
+        )
+        .is_some_and(|value| value == "true"),
+    })
+}
+
+/// Inspects every repository in parallel
+pub fn inspect_all(statuses: &mut [RepoStatus]) {
+    statuses.par_iter_mut().for_each(|status| {
+        let housekeeping = inspect(status.path());
+        status.info_mut().housekeeping = housekeeping;
+    });
+}
+
+/// Runs `git gc` in every repository that needs it, in parallel
+///
+/// Repositories are inspected again afterwards. Returns the number of
+/// repositories collected and an error for each failed run.
+pub fn gc_all(statuses: &mut [RepoStatus]) -> (usize, Vec<Error>) {
+    let results: Vec<Result<()>> = statuses
+        .par_iter_mut()
+        .filter(|s| s.info().housekeeping.as_ref().is_some_and(|h| h.needs_gc))
+        .map(|status| {
+            gc(status.path())?;
+            let housekeeping = inspect(status.path());
+            status.info_mut().housekeeping = housekeeping;
+            Ok(())
+        })
+        .collect();
+    let collected = results.iter().filter(|r| r.is_ok()).count();
+    (
+        collected,
+        results.into_iter().filter_map(|r| r.err()).collect(),
+    )
+}
+
+/// Runs `git gc --quiet` in a repository
+fn gc(repo_path: &Path) -> Result<()> {
+    let failed = |message: String| Error::GitCommandFailed {
+        repo: repo_path.to_path_buf(),
+        message,
+    };
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .arg("gc")
+        .arg("--quiet")
+        .output()
+        .map_err(|e| failed(e.to_string()))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(failed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "count: 7012
+size: 28044
+in-pack: 1520
+packs: 3
+size-pack: 912
+prune-packable: 0
+garbage: 0
+size-garbage: 0
+";
+
+    #[test]
+    fn test_parse_count_objects() {
+        assert_eq!(
+            parse_count_objects(OUTPUT),
+            ObjectCounts {
+                loose: 7012,
+                packs: 3
+            }
+        );
+        assert_eq!(parse_count_objects(""), ObjectCounts::default());
+    }
+
+    #[test]
+    fn test_needs_gc() {
+        let counts = parse_count_objects(OUTPUT);
+        assert!(needs_gc(
+            &counts,
+            DEFAULT_GC_AUTO,
+            DEFAULT_GC_AUTO_PACK_LIMIT
+        ));
+        assert!(!needs_gc(&counts, 0, DEFAULT_GC_AUTO_PACK_LIMIT));
+        assert!(!needs_gc(&counts, 10_000, DEFAULT_GC_AUTO_PACK_LIMIT));
+        assert!(needs_gc(&counts, 10_000, 2));
+    }
+}
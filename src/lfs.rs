@@ -0,0 +1,294 @@
+//! Git LFS health
+//!
+//! A repository using Git LFS can look clean while its large files are in
+//! a bad state: objects that were never uploaded, pointers whose content was
+//! never downloaded, or files that were committed as plain blobs because LFS
+//! wasn't installed at the time. This module checks for all three.
+//!
+//! Pointers are read straight from the index, so only the unpushed check
+//! needs `git-lfs` itself.
+
+use crate::core;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether to report repositories without LFS-tracked files
+static FORCE: AtomicBool = AtomicBool::new(false);
+
+/// First line of every LFS pointer file
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1\n";
+
+/// Pointer files are always smaller than this
+const MAX_POINTER_SIZE: u64 = 1024;
+
+/// Reports every repository's LFS state, not only those with LFS-tracked
+/// files
+pub fn set_force(force: bool) {
+    FORCE.store(force, Ordering::Relaxed);
+}
+
+/// LFS problems found in a repository
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LfsStatus {
+    /// Files whose objects haven't been pushed to the remote, empty when
+    /// `git-lfs` isn't installed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unpushed: Vec<PathBuf>,
+    /// Pointer files whose objects aren't in the local LFS store
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<PathBuf>,
+    /// LFS-tracked files committed as plain blobs instead of pointers
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub plain: Vec<PathBuf>,
+}
+
+impl LfsStatus {
+    pub fn needs_attention(&self) -> bool {
+        !self.unpushed.is_empty() || !self.missing.is_empty() || !self.plain.is_empty()
+    }
+
+    /// Describes the problems found, e.g. `["2 unpushed", "1 plain blob"]`
+    pub fn problems(&self) -> Vec<String> {
+        let plural =
+            |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+        let mut problems = Vec::new();
+        if !self.unpushed.is_empty() {
+            problems.push(format!("{} unpushed", self.unpushed.len()));
+        }
+        if !self.missing.is_empty() {
+            problems.push(format!("{} missing", self.missing.len()));
+        }
+        if !self.plain.is_empty() {
+            problems.push(plural(self.plain.len(), "plain blob", "plain blobs"));
+        }
+        problems
+    }
+}
+
+/// Extracts the object id from the contents of an LFS pointer file
+fn parse_pointer(content: &[u8]) -> Option<String> {
+    let content = std::str::from_utf8(content).ok()?;
+    let rest = content.strip_prefix(POINTER_VERSION)?;
+    rest.lines()
+        .find_map(|line| line.strip_prefix("oid sha256:"))
+        .filter(|oid| oid.len() == 64 && oid.bytes().all(|b| b.is_ascii_hexdigit()))
+        .map(str::to_string)
+}
+
+/// Parses `git ls-files -s -z` output into `(blob, path)` pairs
+fn parse_staged(output: &[u8]) -> Vec<(String, PathBuf)> {
+    output
+        .split(|&b| b == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (meta, path) = entry.split_once('\t')?;
+            let blob = meta.split(' ').nth(1)?;
+            Some((blob.to_string(), PathBuf::from(path)))
+        })
+        .collect()
+}
+
+/// Parses `git cat-file --batch` output into the content of each object,
+/// in request order
+///
+/// Each object is a `<oid> <type> <size>` header line followed by its
+/// content and a newline.
+fn parse_batch(mut output: &[u8]) -> Vec<Vec<u8>> {
+    let mut contents = Vec::new();
+    while let Some(end) = output.iter().position(|&b| b == b'\n') {
+        let header = String::from_utf8_lossy(&output[..end]);
+        let size = header
+            .rsplit(' ')
+            .next()
+            .and_then(|s| s.parse::<usize>().ok());
+        output = &output[end + 1..];
+        match size {
+            Some(size) if size <= output.len() => {
+                contents.push(output[..size].to_vec());
+                output = output.get(size + 1..).unwrap_or_default();
+            }
+            // `<oid> missing`
+            _ => contents.push(Vec::new()),
+        }
+    }
+    contents
+}
+
+/// Parses `git lfs push --dry-run` output, one `push <oid> => <path>` line
+/// per object
+fn parse_push_dry_run(output: &str) -> Vec<PathBuf> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("push ")?.split_once(" => "))
+        .map(|(_, path)| PathBuf::from(path))
+        .collect()
+}
+
+/// Runs git in a repository with the given input and returns its output if
+/// it succeeds
+fn git(repo_path: &Path, args: &[&str], input: Option<String>) -> Option<Vec<u8>> {
+    let output = core::run_git(
+        Command::new("git").arg("-C").arg(repo_path).args(args),
+        input.map(String::into_bytes),
+    )
+    .ok()??;
+    output.status.success().then_some(output.stdout)
+}
+
+/// Finds the local LFS object store
+///
+/// Objects live in `lfs/objects` below the common git directory, shared by
+/// all worktrees, unless `lfs.storage` moves them elsewhere. A relative
+/// `lfs.storage` is relative to that directory.
+fn object_store(repo_path: &Path) -> PathBuf {
+    let git_dir = core::common_git_dir(repo_path);
+    let storage = core::git_output(repo_path, &["config", "--get", "lfs.storage"])
+        .map_or_else(|| git_dir.join("lfs"), |dir| git_dir.join(dir));
+    storage.join("objects")
+}
+
+/// Checks a repository for LFS problems
+///
+/// LFS-tracked files are the ones git's attributes assign the LFS filter,
+/// from any `.gitattributes` or `info/attributes`. Returns `None` for
+/// repositories without any. Unpushed objects are checked against the
+/// remote `remote` returns, as `git lfs pre-push` would; it is only called
+/// for repositories that use LFS.
+pub fn check_lfs(repo_path: &Path, remote: impl FnOnce() -> Option<String>) -> Option<LfsStatus> {
+    let staged = git(
+        repo_path,
+        &["ls-files", "-s", "-z", "--", ":(attr:filter=lfs)"],
+        None,
+    )
+    .map(|output| parse_staged(&output))
+    .unwrap_or_default();
+    if staged.is_empty() && !FORCE.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let mut status = LfsStatus::default();
+    let input: String = staged.iter().map(|(blob, _)| format!("{blob}\n")).collect();
+    let sizes = git(
+        repo_path,
+        &["cat-file", "--batch-check=%(objectsize)"],
+        Some(input),
+    )
+    .unwrap_or_default();
+    let sizes: Vec<u64> = String::from_utf8_lossy(&sizes)
+        .lines()
+        .map(|size| size.parse().unwrap_or(u64::MAX))
+        .collect();
+
+    // Only blobs small enough to be pointers need reading
+    let mut candidates = Vec::new();
+    for ((blob, path), size) in staged.iter().zip(&sizes) {
+        if *size < MAX_POINTER_SIZE {
+            candidates.push((blob, path));
+        } else {
+            status.plain.push(path.clone());
+        }
+    }
+    let input: String = candidates
+        .iter()
+        .map(|(blob, _)| format!("{blob}\n"))
+        .collect();
+    let contents = git(repo_path, &["cat-file", "--batch"], Some(input))
+        .map(|output| parse_batch(&output))
+        .unwrap_or_default();
+    let store = if contents.is_empty() {
+        PathBuf::new()
+    } else {
+        object_store(repo_path)
+    };
+    for ((_, path), content) in candidates.into_iter().zip(&contents) {
+        match parse_pointer(content) {
+            Some(oid) => {
+                if !store.join(&oid[..2]).join(&oid[2..4]).join(&oid).is_file() {
+                    status.missing.push(path.clone());
+                }
+            }
+            None => status.plain.push(path.clone()),
+        }
+    }
+
+    if let Some(remote) = remote() {
+        status.unpushed = git(
+            repo_path,
+            &["lfs", "push", "--dry-run", &remote, "HEAD"],
+            None,
+        )
+        .map(|output| parse_push_dry_run(&String::from_utf8_lossy(&output)))
+        .unwrap_or_default();
+    }
+    Some(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OID: &str = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+
+    fn pointer(oid: &str) -> String {
+        format!("{POINTER_VERSION}oid sha256:{oid}\nsize 12345\n")
+    }
+
+    #[test]
+    fn test_parse_pointer() {
+        assert_eq!(
+            parse_pointer(pointer(OID).as_bytes()),
+            Some(OID.to_string())
+        );
+        assert_eq!(parse_pointer(pointer("abc").as_bytes()), None);
+        assert_eq!(parse_pointer(b"\x89PNG\r\n"), None);
+        assert_eq!(parse_pointer(b""), None);
+    }
+
+    #[test]
+    fn test_parse_staged() {
+        let output = b"100644 1a2b3c4d 0\tassets/logo.png\x00100644 9f8e7d6c 0\tdata/a b.bin\0";
+        assert_eq!(
+            parse_staged(output),
+            [
+                ("1a2b3c4d".to_string(), PathBuf::from("assets/logo.png")),
+                ("9f8e7d6c".to_string(), PathBuf::from("data/a b.bin")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_batch() {
+        let output = b"1a2b blob 5\nhello\n9f8e missing\n3c4d blob 0\n\n";
+        assert_eq!(
+            parse_batch(output),
+            [b"hello".to_vec(), Vec::new(), Vec::new()]
+        );
+    }
+
+    #[test]
+    fn test_parse_push_dry_run() {
+        let output = format!("push {OID} => assets/logo.png\npush {OID} => a => b.bin\n");
+        assert_eq!(
+            parse_push_dry_run(&output),
+            [
+                PathBuf::from("assets/logo.png"),
+                PathBuf::from("a => b.bin")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_problems() {
+        let status = LfsStatus {
+            unpushed: vec![PathBuf::from("a.bin"), PathBuf::from("b.bin")],
+            plain: vec![PathBuf::from("c.bin")],
+            ..LfsStatus::default()
+        };
+        assert!(status.needs_attention());
+        assert_eq!(status.problems(), ["2 unpushed", "1 plain blob"]);
+        assert!(!LfsStatus::default().needs_attention());
+    }
+}
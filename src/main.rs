@@ -1,34 +1,168 @@
+mod analysis;
+mod bare;
+mod cache;
 mod cli;
 mod core;
+#[cfg(unix)]
+mod daemon;
+mod default_branch;
+mod diffstat;
 mod error;
+mod exit;
+#[cfg(feature = "history")]
+mod history;
+mod housekeeping;
+mod lfs;
 mod output;
+mod policy;
+mod query;
+mod remote;
+mod snapshot;
+mod sort;
+mod submodule;
+mod tree;
+mod watch;
+mod worktree;
 
 use clap::Parser;
 use cli::Args;
 use error::Result;
 use output::{OutputFormat, Verbosity};
+use std::time::Duration;
 
 fn main() {
-    std::process::exit(match run() {
+    let args = Args::parse();
+    let mode = args.exit_code.unwrap_or_default();
+    let code = match run(&args) {
         Ok(code) => code,
         Err(e) => {
             output::print_error(&e.to_string());
-            1
+            mode.failure_code()
         }
+    };
+    std::process::exit(if mode == exit::ExitCodeMode::Zero {
+        0
+    } else {
+        code
     });
 }
 
-fn run() -> Result<i32> {
-    let args = Args::parse();
+fn run(args: &Args) -> Result<i32> {
+    output::configure(args.color, args.plain);
+    core::set_git_timeout(args.git_timeout.map(Duration::from_secs));
+    lfs::set_force(args.lfs);
 
     // Handle shell completion generation
     if args.handle_completion() {
         return Ok(0);
     }
 
+    // Query a running daemon
+    if let Some(request) = args.client {
+        return run_client(args, request);
+    }
+
     let verbosity = args.verbosity();
     let output_format = args.output_format();
-    let show_branch = args.show_branch;
+    let filter = [
+        args.filter
+            .as_deref()
+            .map(query::Filter::parse)
+            .transpose()?,
+        args.stale.map(query::Filter::stale),
+        args.active.map(query::Filter::active),
+        args.host
+            .as_deref()
+            .map(|host| query::Filter::remote(query::Field::Host, host)),
+        args.owner
+            .as_deref()
+            .map(|owner| query::Filter::remote(query::Field::Owner, owner)),
+        args.merged.then(query::Filter::merged),
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(query::Filter::and);
+    let policy = args
+        .policy
+        .as_deref()
+        .map(policy::Policy::load)
+        .transpose()?;
+
+    // Compare two snapshot files without scanning
+    if let Some([old, new]) = args.diff.as_deref() {
+        let mut old = snapshot::Snapshot::load(old)?;
+        let mut new = snapshot::Snapshot::load(new)?;
+        if let Some(filter) = &filter {
+            old.retain(|s| filter.matches(s));
+            new.retain(|s| filter.matches(s));
+        }
+        let diff = snapshot::SnapshotDiff::between(&old, &new);
+        return report_diff(&diff, &new.root, &[], args);
+    }
+
+    // Snapshots record branches so branch switches show up in diffs
+    let show_branch = args.show_branch
+        || args.save_snapshot.is_some()
+        || args.changes.is_some()
+        || args.sort == sort::SortKey::Branch
+        || args.group_by == Some(sort::GroupKey::Branch)
+        || filter
+            .as_ref()
+            .is_some_and(|f| f.uses(query::Field::Branch))
+        || policy
+            .as_ref()
+            .is_some_and(|p| p.uses(query::Field::Branch));
+
+    // Look up only the details that the output, sort order and queries read
+    let uses = |field| {
+        filter.as_ref().is_some_and(|f| f.uses(field))
+            || policy.as_ref().is_some_and(|p| p.uses(field))
+    };
+    let listing = args.stale.is_some() || args.active.is_some();
+    let every_detail = output_format == OutputFormat::Json
+        || verbosity >= Verbosity::Verbose
+        || listing
+        || args.daemon;
+    // The summary warns about repos without a remote and merged branches
+    let summary = output_format != OutputFormat::Json && verbosity >= Verbosity::Summary;
+    let snapshots = args.save_snapshot.is_some() || args.changes.is_some() || args.record_history;
+    let details = core::Details {
+        branch: show_branch,
+        last_commit: every_detail
+            || snapshots
+            || args.sort == sort::SortKey::LastCommit
+            || uses(query::Field::Age),
+        remotes: every_detail
+            || summary
+            || matches!(
+                args.group_by,
+                Some(sort::GroupKey::RemoteHost | sort::GroupKey::RemoteOwner)
+            )
+            || [
+                query::Field::Host,
+                query::Field::Owner,
+                query::Field::Remotes,
+                query::Field::HttpsPush,
+            ]
+            .into_iter()
+            .any(uses),
+        default_branch: every_detail
+            || summary
+            || [
+                query::Field::DefaultAhead,
+                query::Field::DefaultBehind,
+                query::Field::Merged,
+            ]
+            .into_iter()
+            .any(uses),
+        diff: every_detail
+            || output_format == OutputFormat::Tree
+            || args.watch
+            || args.sort == sort::SortKey::DiffSize
+            || [query::Field::Added, query::Field::Removed]
+                .into_iter()
+                .any(uses),
+    };
 
     // Validate and get configuration
     let root = args.root_path()?;
@@ -41,11 +175,172 @@ fn run() -> Result<i32> {
         output::print_header(&validated_root, validated_depth);
     }
 
-    // Find repositories
-    let repos = core::find_git_repos(&validated_root, validated_depth);
+    // Report trends from the run history
+    if args.history {
+        return show_history(args, &validated_root);
+    }
+
+    // Daemon mode serves requests until interrupted
+    if args.daemon {
+        run_daemon(args, validated_root, validated_depth, show_branch)?;
+        return Ok(0);
+    }
+
+    // Find repositories, reusing the cached list from the previous run
+    let repos = match cache::DiscoveryCache::default_path().filter(|_| !args.no_cache) {
+        Some(path) => {
+            let mut discovery = cache::DiscoveryCache::load(path);
+            let repos = discovery.find_repos(&validated_root, validated_depth, args.rescan);
+            if let Err(e) = discovery.save() {
+                output::print_warning(&format!("Failed to write discovery cache: {}", e));
+            }
+            if args.cache_stats {
+                output::print_discovery_stats(discovery.scanned(), repos.len());
+            }
+            repos
+        }
+        None => core::find_git_repos(&validated_root, validated_depth),
+    };
+
+    // Watch mode runs until interrupted
+    if args.watch {
+        watch::run(
+            &repos,
+            &watch::WatchOptions {
+                root: &validated_root,
+                details,
+                debounce: Duration::from_millis(args.debounce),
+                filter: filter.as_ref(),
+                sort: args.sort,
+                path_style: args.path_style,
+            },
+        )?;
+        return Ok(0);
+    }
+
+    // Check repositories in parallel, reusing cached results where possible.
+    // Cached statuses lack the LFS state of repos without LFS files, which
+    // `--lfs` reports, so it always checks live.
+    let mut cache = if args.no_cache || args.lfs {
+        None
+    } else {
+        cache::StatusCache::default_path().map(cache::StatusCache::load)
+    };
+    let (mut statuses, mut errors) = core::check_repos_parallel(&repos, details, cache.as_mut());
+    if let Some(cache) = &mut cache {
+        if let Err(e) = cache.save() {
+            output::print_warning(&format!("Failed to write status cache: {}", e));
+        }
+        if args.cache_stats {
+            output::print_cache_stats(&cache.stats(), cache.path());
+        }
+    }
+
+    if args.roll_up_submodules {
+        statuses = submodule::roll_up_all(statuses);
+    }
+    analysis::Analyzer::new(
+        args.large_file_size
+            .unwrap_or(analysis::DEFAULT_LARGE_FILE_THRESHOLD),
+        &args.secret_patterns,
+        uses(query::Field::LargestUntracked),
+    )
+    .analyze_all(&mut statuses);
+    let housekeeping = args.housekeeping || args.gc;
+    if housekeeping {
+        housekeeping::inspect_all(&mut statuses);
+    }
+
+    // Snapshot the full results before filtering
+    if snapshots {
+        let mut current = snapshot::Snapshot::new(&validated_root, &statuses);
+        if let Some(path) = &args.save_snapshot {
+            current.save(path)?;
+        }
+        if args.record_history {
+            record_history(&current);
+        }
+        let previous = match &args.changes {
+            Some(Some(path)) => Some(snapshot::Snapshot::load(path)?),
+            Some(None) => {
+                let path = snapshot::last_snapshot_path(&validated_root, validated_depth).ok_or(
+                    error::Error::Other("no cache directory for automatic snapshots".to_string()),
+                )?;
+                let previous = path
+                    .exists()
+                    .then(|| snapshot::Snapshot::load(&path))
+                    .transpose()?;
+                // Leave a baseline behind for the next `--changes` run
+                let saved = match current.save(&path) {
+                    Ok(()) => {
+                        snapshot::prune_snapshots();
+                        true
+                    }
+                    Err(e) => {
+                        output::print_warning(&format!(
+                            "Failed to write automatic snapshot: {}",
+                            e
+                        ));
+                        false
+                    }
+                };
+                // Without an earlier run there is nothing to compare with yet
+                if previous.is_none() {
+                    if verbosity >= Verbosity::Summary {
+                        output::print_info(&format!(
+                            "No earlier run of {} to compare with yet{}",
+                            validated_root.display(),
+                            if saved {
+                                "; saved a snapshot for next time"
+                            } else {
+                                ""
+                            }
+                        ));
+                    }
+                    return Ok(0);
+                }
+                previous
+            }
+            None => None,
+        };
+
+        // Report what changed since the previous snapshot
+        if let Some(mut previous) = previous {
+            if let Some(filter) = &filter {
+                previous.retain(|s| filter.matches(s));
+                current.retain(|s| filter.matches(s));
+            }
+            let diff = snapshot::SnapshotDiff::between(&previous, &current);
+            return report_diff(&diff, &validated_root, &errors, args);
+        }
+    }
+
+    // Keep only the repos selected by --where
+    if let Some(filter) = &filter {
+        statuses.retain(|s| filter.matches(s));
+    }
+
+    // Run maintenance on the selected repos that need it
+    if args.gc {
+        let (collected, failures) = housekeeping::gc_all(&mut statuses);
+        if verbosity >= Verbosity::Summary && output_format != OutputFormat::Json {
+            output::print_info(&format!(
+                "Ran git gc in {} repo{}",
+                collected,
+                if collected == 1 { "" } else { "s" }
+            ));
+        }
+        errors.extend(failures);
+    }
 
-    // Check repositories in parallel
-    let (statuses, errors) = core::check_repos_parallel(&repos, show_branch);
+    sort::sort_statuses(&mut statuses, args.sort);
+    let groups = args
+        .group_by
+        .map(|key| sort::group_statuses(&statuses, key, &validated_root));
+    let names = output::display_names(&statuses, &validated_root, args.path_style);
+    let violations = policy
+        .as_ref()
+        .map(|p| p.evaluate(&statuses, &validated_root));
 
     // Report errors if verbosity allows
     if verbosity >= Verbosity::Summary {
@@ -58,36 +353,232 @@ fn run() -> Result<i32> {
     let total = statuses.len();
     let dirty_count = statuses.iter().filter(|s| s.is_dirty()).count();
 
+    // Verbose output shows dirty repos, and clean ones whose worktrees
+    // hold changes or are stale
+    let noteworthy = |status: &core::RepoStatus| {
+        status.is_dirty()
+            || status
+                .info()
+                .worktrees
+                .iter()
+                .any(|w| w.is_dirty() || w.is_stale())
+            || !status.info().missing_upstream.is_empty()
+            || status
+                .info()
+                .lfs
+                .as_ref()
+                .is_some_and(|l| l.needs_attention())
+            || status
+                .info()
+                .default_branch
+                .as_ref()
+                .is_some_and(|d| d.merged)
+    };
+
     // Output results based on format
     match output_format {
         OutputFormat::Json => {
-            output::print_json(&statuses).map_err(|e| error::Error::Other(e.to_string()))?;
+            output::print_json(&statuses, groups.as_deref(), violations.as_deref())
+                .map_err(|e| error::Error::Other(e.to_string()))?;
         }
         OutputFormat::Human => {
-            // Print detailed status for dirty repos in verbose mode
-            if verbosity >= Verbosity::Verbose {
-                for status in &statuses {
-                    if status.is_dirty() {
-                        output::print_verbose_status(status);
+            match &groups {
+                // Print a header per group, followed by its noteworthy repos in verbose mode
+                Some(groups) if verbosity >= Verbosity::Summary => {
+                    for group in groups {
+                        output::print_group_header(group);
+                        if verbosity >= Verbosity::Verbose {
+                            for status in group.repos.iter().filter(|s| noteworthy(s)) {
+                                output::print_verbose_status(status, &names[status.path()]);
+                            }
+                        }
+                    }
+                }
+                // List every repo with its last commit age for --stale/--active
+                _ if listing && verbosity >= Verbosity::Summary => {
+                    for status in &statuses {
+                        if verbosity >= Verbosity::Verbose {
+                            output::print_verbose_status(status, &names[status.path()]);
+                        } else {
+                            output::print_repo_line(status, &names[status.path()]);
+                        }
+                    }
+                    eprintln!();
+                }
+                // Print detailed status for noteworthy repos in verbose mode
+                _ if verbosity >= Verbosity::Verbose => {
+                    for status in &statuses {
+                        if noteworthy(status) {
+                            output::print_verbose_status(status, &names[status.path()]);
+                        }
                     }
                 }
+                _ => {}
             }
 
             // Print summary in summary/verbose mode
             if verbosity >= Verbosity::Summary {
+                if housekeeping {
+                    output::print_housekeeping(&statuses, &names);
+                }
+                output::print_file_warnings(&statuses, &names);
                 output::print_summary(total, dirty_count);
+                output::print_warnings(&statuses);
             }
         }
+        OutputFormat::Tree => {
+            if verbosity >= Verbosity::Summary {
+                tree::print_tree(&statuses, &validated_root, args.expand);
+                if housekeeping {
+                    output::print_housekeeping(&statuses, &names);
+                }
+                output::print_file_warnings(&statuses, &names);
+                output::print_summary(total, dirty_count);
+                output::print_warnings(&statuses);
+            }
+        }
+    }
+    if let Some(violations) = &violations {
+        if output_format != OutputFormat::Json && verbosity >= Verbosity::Summary {
+            eprintln!();
+            policy::print_violations(violations, &names);
+        }
     }
 
-    // Return exit code (dirty count, capped at 255)
-    Ok(if dirty_count > 255 {
-        255
-    } else {
-        dirty_count as i32
+    let severity = violations.as_deref().and_then(policy::highest_severity);
+    let outcome = exit::Outcome::new(&statuses, &errors, severity);
+    Ok(match args.exit_code {
+        Some(mode) => mode.code(&outcome),
+        // Policy runs exit based on the most serious violation by default
+        None if policy.is_some() => outcome.policy_code(),
+        None => exit::ExitCodeMode::Count.code(&outcome),
     })
 }
 
+/// Prints a snapshot diff
+///
+/// For the exit code, every added, removed or changed repo counts like a
+/// dirty one.
+fn report_diff(
+    diff: &snapshot::SnapshotDiff,
+    root: &std::path::Path,
+    errors: &[error::Error],
+    args: &Args,
+) -> Result<i32> {
+    match args.output_format() {
+        OutputFormat::Json => {
+            snapshot::print_diff_json(diff).map_err(|e| error::Error::Other(e.to_string()))?;
+        }
+        _ if args.verbosity() >= Verbosity::Summary => {
+            snapshot::print_diff(diff, root, args.path_style);
+        }
+        _ => {}
+    }
+    let outcome = exit::Outcome {
+        dirty: diff.len(),
+        ..exit::Outcome::new(&[], errors, None)
+    };
+    Ok(args.exit_code.unwrap_or_default().code(&outcome))
+}
+
+/// Prints the trend report for a scan root from the history database
+#[cfg(feature = "history")]
+fn show_history(args: &Args, root: &std::path::Path) -> Result<i32> {
+    let path = history::History::default_path().ok_or(error::Error::History(
+        "no data directory for the history database".to_string(),
+    ))?;
+    let report = history::History::open(&path)?.report(root)?;
+    match args.output_format() {
+        OutputFormat::Json => {
+            history::print_report_json(&report).map_err(|e| error::Error::Other(e.to_string()))?;
+        }
+        _ => history::print_report(&report),
+    }
+    Ok(0)
+}
+
+#[cfg(not(feature = "history"))]
+fn show_history(_args: &Args, _root: &std::path::Path) -> Result<i32> {
+    Err(error::Error::History(
+        "built without the `history` feature".to_string(),
+    ))
+}
+
+/// Records a run in the history database, warning on failure
+#[cfg(feature = "history")]
+fn record_history(snapshot: &snapshot::Snapshot) {
+    let Some(path) = history::History::default_path() else {
+        return;
+    };
+    if let Err(e) = history::History::open(&path).and_then(|mut h| h.record(snapshot)) {
+        output::print_warning(&format!("Failed to record history: {}", e));
+    }
+}
+
+#[cfg(not(feature = "history"))]
+fn record_history(_snapshot: &snapshot::Snapshot) {}
+
+/// Sends a request to a running daemon
+#[cfg(unix)]
+fn run_client(args: &Args, request: cli::ClientRequest) -> Result<i32> {
+    use cli::ClientRequest;
+
+    let request = match request {
+        ClientRequest::List => daemon::Request::List,
+        ClientRequest::Subscribe => daemon::Request::Subscribe,
+        ClientRequest::Refresh => daemon::Request::Refresh,
+        ClientRequest::Get => {
+            let path = match &args.root {
+                Some(path) => path.clone(),
+                None => std::env::current_dir()?,
+            };
+            daemon::Request::Get {
+                path: core::validate_path(&path)?,
+            }
+        }
+    };
+    daemon::run_client(&args.socket_path(), &request)
+}
+
+#[cfg(not(unix))]
+fn run_client(_args: &Args, _request: cli::ClientRequest) -> Result<i32> {
+    Err(error::Error::Daemon(
+        "the daemon requires Unix domain sockets".to_string(),
+    ))
+}
+
+/// Runs the status daemon for the validated root
+#[cfg(unix)]
+fn run_daemon(
+    args: &Args,
+    root: std::path::PathBuf,
+    max_depth: usize,
+    include_branch: bool,
+) -> Result<()> {
+    daemon::run(daemon::DaemonOptions {
+        root,
+        max_depth,
+        details: core::Details {
+            branch: include_branch,
+            ..core::Details::ALL
+        },
+        debounce: Duration::from_millis(args.debounce),
+        socket: args.socket_path(),
+    })
+}
+
+#[cfg(not(unix))]
+fn run_daemon(
+    _args: &Args,
+    _root: std::path::PathBuf,
+    _max_depth: usize,
+    _include_branch: bool,
+) -> Result<()> {
+    Err(error::Error::Daemon(
+        "the daemon requires Unix domain sockets".to_string(),
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,7 +599,7 @@ mod tests {
             verbose: false,
             json: false,
             show_branch: false,
-            generate_completion: None,
+            ..Default::default()
         };
         assert_eq!(args.output_format(), OutputFormat::Human);
 
@@ -3,16 +3,160 @@
 //! This module handles all output formatting including human-readable
 //! colored terminal output and JSON serialization.
 
-use crate::core::RepoStatus;
+use crate::analysis::FileWarningKind;
+use crate::cache::CacheStats;
+use crate::core::{self, HeadState, RepoKind, RepoStatus};
+use crate::policy::Violation;
+use crate::sort::Group;
+use crate::worktree::Worktree;
+use clap::ValueEnum;
 use colored::*;
 use serde::Serialize;
-use std::path::Path;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Whether plain ASCII symbols are used instead of emoji and box drawing
+static PLAIN: AtomicBool = AtomicBool::new(false);
 
 /// Output format options
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OutputFormat {
     Human,
     Json,
+    Tree,
+}
+
+/// When to use colored output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stderr is a terminal, honoring NO_COLOR and CLICOLOR_FORCE
+    #[default]
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+/// Symbols used in human output
+#[derive(Debug)]
+pub struct Symbols {
+    pub search: &'static str,
+    pub repo: &'static str,
+    pub clean: &'static str,
+    pub dirty: &'static str,
+    pub group: &'static str,
+    pub rule: &'static str,
+    pub arrow: &'static str,
+    pub warning: &'static str,
+    pub submodule: &'static str,
+    pub worktree: &'static str,
+    #[cfg_attr(not(feature = "history"), allow(dead_code))]
+    pub spark: &'static [char],
+    pub tree_branch: &'static str,
+    pub tree_last: &'static str,
+    pub tree_pipe: &'static str,
+}
+
+/// Emoji and box-drawing symbols
+pub const UNICODE: Symbols = Symbols {
+    search: "🔍",
+    repo: "📦",
+    clean: "✓",
+    dirty: "✗",
+    group: "▸",
+    rule: "━",
+    arrow: "→",
+    warning: "⚠",
+    submodule: "↳",
+    worktree: "⎇",
+    spark: &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'],
+    tree_branch: "├── ",
+    tree_last: "└── ",
+    tree_pipe: "│   ",
+};
+
+/// Plain ASCII text markers
+pub const ASCII: Symbols = Symbols {
+    search: "[scan]",
+    repo: "[repo]",
+    clean: "[ok]",
+    dirty: "[dirty]",
+    group: ">",
+    rule: "-",
+    arrow: "->",
+    warning: "[warn]",
+    submodule: "`-",
+    worktree: "`+",
+    spark: &['_', '.', '-', '~', '=', '+', '*', '#'],
+    tree_branch: "|-- ",
+    tree_last: "`-- ",
+    tree_pipe: "|   ",
+};
+
+/// Decides whether to color output
+///
+/// An explicit `always`/`never` wins. In `auto` mode a non-empty `NO_COLOR`
+/// disables color, a `CLICOLOR_FORCE` other than `0` enables it, and
+/// otherwise color is used only when writing to a terminal.
+fn should_colorize(
+    choice: ColorChoice,
+    no_color: Option<&str>,
+    clicolor_force: Option<&str>,
+    is_terminal: bool,
+) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            if no_color.is_some_and(|v| !v.is_empty()) {
+                false
+            } else if clicolor_force.is_some_and(|v| !v.is_empty() && v != "0") {
+                true
+            } else {
+                is_terminal
+            }
+        }
+    }
+}
+
+/// Configures colors and symbols for all output in this process
+pub fn configure(color: ColorChoice, plain: bool) {
+    let no_color = std::env::var("NO_COLOR").ok();
+    let clicolor_force = std::env::var("CLICOLOR_FORCE").ok();
+    colored::control::set_override(should_colorize(
+        color,
+        no_color.as_deref(),
+        clicolor_force.as_deref(),
+        std::io::stderr().is_terminal(),
+    ));
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+/// Gets the symbols selected by `configure`
+pub fn symbols() -> &'static Symbols {
+    if PLAIN.load(Ordering::Relaxed) {
+        &ASCII
+    } else {
+        &UNICODE
+    }
+}
+
+/// How repository paths are displayed in human output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PathStyle {
+    /// Relative to the scan root
+    #[default]
+    Relative,
+    /// Absolute path
+    Absolute,
+    /// Absolute path with the home directory abbreviated to ~
+    Home,
+    /// Directory name, with parent directories added where names collide
+    Name,
 }
 
 /// Verbosity levels
@@ -42,6 +186,30 @@ pub struct JsonOutput {
     pub dirty: usize,
     pub clean: usize,
     pub repositories: Vec<RepoStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<JsonGroup>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violations: Option<Vec<Violation>>,
+}
+
+/// JSON structure for a group of repositories
+#[derive(Debug, Serialize)]
+pub struct JsonGroup {
+    pub name: String,
+    pub total: usize,
+    pub dirty: usize,
+    pub repositories: Vec<PathBuf>,
+}
+
+impl From<&Group<'_>> for JsonGroup {
+    fn from(group: &Group<'_>) -> Self {
+        JsonGroup {
+            name: group.name.clone(),
+            total: group.repos.len(),
+            dirty: group.dirty_count(),
+            repositories: group.repos.iter().map(|s| s.path().to_path_buf()).collect(),
+        }
+    }
 }
 
 /// Gets the repository name from a path
@@ -52,66 +220,451 @@ fn get_repo_name(path: &Path) -> String {
         .to_string()
 }
 
+/// Joins the last `depth` components of a path
+fn path_suffix(path: &Path, depth: usize) -> String {
+    let components: Vec<_> = path.components().collect();
+    let start = components.len().saturating_sub(depth);
+    components[start..]
+        .iter()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Computes the shortest unique trailing path for each repository
+///
+/// Starts from the directory name and adds parent components to every
+/// name that collides with another until all names are unique.
+fn unique_names(paths: &[&Path]) -> Vec<String> {
+    let mut depths = vec![1; paths.len()];
+    loop {
+        let names: Vec<String> = paths
+            .iter()
+            .zip(&depths)
+            .map(|(path, &depth)| {
+                if depth == 1 {
+                    get_repo_name(path)
+                } else {
+                    path_suffix(path, depth)
+                }
+            })
+            .collect();
+
+        let mut seen: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, name) in names.iter().enumerate() {
+            seen.entry(name).or_default().push(i);
+        }
+
+        let mut changed = false;
+        for indices in seen.values().filter(|indices| indices.len() > 1) {
+            for &i in indices {
+                if depths[i] < paths[i].components().count() {
+                    depths[i] += 1;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            return names;
+        }
+    }
+}
+
+/// Formats a path for display in the given style
+pub fn styled_path(path: &Path, root: &Path, style: PathStyle) -> String {
+    match style {
+        PathStyle::Relative => match path.strip_prefix(root) {
+            Ok(rel) if rel.as_os_str().is_empty() => ".".to_string(),
+            Ok(rel) => rel.display().to_string(),
+            Err(_) => path.display().to_string(),
+        },
+        PathStyle::Home => match dirs::home_dir().and_then(|h| path.strip_prefix(h).ok()) {
+            Some(rel) if rel.as_os_str().is_empty() => "~".to_string(),
+            Some(rel) => format!("~/{}", rel.display()),
+            None => path.display().to_string(),
+        },
+        PathStyle::Absolute | PathStyle::Name => path.display().to_string(),
+    }
+}
+
+/// Computes the display name of every repository in a run
+pub fn display_names(
+    statuses: &[RepoStatus],
+    root: &Path,
+    style: PathStyle,
+) -> HashMap<PathBuf, String> {
+    let paths: Vec<&Path> = statuses.iter().map(|s| s.path()).collect();
+    let names = match style {
+        PathStyle::Name => unique_names(&paths),
+        _ => paths
+            .iter()
+            .map(|path| styled_path(path, root, style))
+            .collect(),
+    };
+    paths
+        .into_iter()
+        .map(Path::to_path_buf)
+        .zip(names)
+        .collect()
+}
+
 /// Prints the header for verbose mode
 pub fn print_header(root: &Path, max_depth: usize) {
     eprintln!(
         "{} Checking git repos in {} (maxdepth={})",
-        "🔍".cyan(),
+        symbols().search.cyan(),
         root.display().to_string().bright_blue(),
         max_depth.to_string().yellow()
     );
 
     let width = terminal_width();
-    eprintln!("{}", "━".repeat(width).bright_black());
+    eprintln!("{}", symbols().rule.repeat(width).bright_black());
     eprintln!();
 }
 
-/// Prints verbose status for a single repository
-pub fn print_verbose_status(status: &RepoStatus) {
-    let path = status.path();
-    let repo_name = get_repo_name(path);
-
-    match status {
-        RepoStatus::Clean { branch, .. } => {
-            let branch_str = branch
-                .as_ref()
-                .map(|b| format!(" ({})", b.bright_cyan()))
-                .unwrap_or_default();
-            eprintln!("{} {}{}", "📦".green(), repo_name.green(), branch_str);
-        }
-        RepoStatus::Dirty {
-            changes, branch, ..
-        } => {
-            let branch_str = branch
-                .as_ref()
-                .map(|b| format!(" ({})", b.bright_cyan()))
-                .unwrap_or_default();
-            eprintln!(
-                "{} {}{}",
-                "📦".yellow(),
-                repo_name.yellow().bold(),
-                branch_str
-            );
+/// Gets the current time as a unix timestamp
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
 
-            for line in changes.lines() {
-                if !line.trim().is_empty() {
-                    eprintln!("  {}", line.bright_white());
-                }
+/// Formats a unix timestamp as RFC 3339
+pub fn format_time(secs: i64) -> String {
+    let time = UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64);
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+/// Formats a duration as a rounded-down age, e.g. "3 weeks"
+pub fn humanize_age(age: Duration) -> String {
+    const UNITS: [(&str, u64); 6] = [
+        ("year", 365 * 86400),
+        ("month", 30 * 86400),
+        ("week", 7 * 86400),
+        ("day", 86400),
+        ("hour", 3600),
+        ("minute", 60),
+    ];
+    let secs = age.as_secs();
+    UNITS.iter().find(|(_, size)| secs >= *size).map_or(
+        "less than a minute".to_string(),
+        |(unit, size)| {
+            let count = secs / size;
+            format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+        },
+    )
+}
+
+/// Formats a size in bytes with a decimal unit, e.g. "120.5 MB"
+pub fn humanize_size(bytes: u64) -> String {
+    const UNITS: [(&str, u64); 3] = [
+        ("GB", 1000 * 1000 * 1000),
+        ("MB", 1000 * 1000),
+        ("kB", 1000),
+    ];
+    UNITS
+        .iter()
+        .find(|(_, size)| bytes >= *size)
+        .map_or(format!("{bytes} B"), |(unit, size)| {
+            format!("{:.1} {}", bytes as f64 / *size as f64, unit)
+        })
+}
+
+/// Formats the lines added and removed in a dirty repository, e.g. " +120 -34"
+///
+/// Empty for clean repositories and for changes without any changed lines.
+pub fn diff_label(status: &RepoStatus) -> String {
+    let diff = status.diff();
+    if diff.lines_changed() == 0 {
+        return String::new();
+    }
+    format!(
+        " {} {}",
+        format!("+{}", diff.added()).green(),
+        format!("-{}", diff.removed()).red()
+    )
+}
+
+/// Formats the branch shown after a repository name
+///
+/// Detached and unborn heads are always shown, branch names only when they
+/// were requested.
+pub fn head_label(status: &RepoStatus) -> String {
+    match (&status.info().head, status.branch()) {
+        (Some(HeadState::Detached { commit, tag }), _) => {
+            let at = match tag {
+                Some(tag) => format!("{} {}", commit, tag),
+                None => commit.clone(),
+            };
+            format!(" ({})", format!("detached at {}", at).magenta())
+        }
+        (Some(HeadState::Unborn { .. }), Some(branch)) => {
+            format!(
+                " ({}, {})",
+                branch.bright_cyan(),
+                "no commits".bright_black()
+            )
+        }
+        (Some(HeadState::Unborn { .. }), None) => format!(" ({})", "no commits".bright_black()),
+        (_, Some(branch)) => format!(" ({})", branch.bright_cyan()),
+        (_, None) => String::new(),
+    }
+}
+
+/// Formats a repository line: its status symbol, name, branch and, when
+/// dirty, the change count and changed lines
+pub fn repo_label(name: &str, status: &RepoStatus, symbols: &Symbols) -> String {
+    let branch = head_label(status);
+
+    if status.is_dirty() {
+        let count = status.change_count();
+        format!(
+            "{} {}{} [{} change{}]{}",
+            symbols.dirty.yellow(),
+            name.yellow().bold(),
+            branch,
+            count,
+            if count == 1 { "" } else { "s" },
+            diff_label(status)
+        )
+    } else {
+        format!("{} {}{}", symbols.clean.green(), name.green(), branch)
+    }
+}
+
+/// Prints the name, branch and last commit age of a repository
+pub fn print_repo_line(status: &RepoStatus, repo_name: &str) {
+    let branch_str = head_label(status);
+    let age_str = status
+        .info()
+        .last_commit
+        .as_ref()
+        .map(|commit| {
+            let then = UNIX_EPOCH + Duration::from_secs(commit.timestamp.max(0) as u64);
+            let age = SystemTime::now().duration_since(then).unwrap_or_default();
+            format!(" {} ago", humanize_age(age))
+                .bright_black()
+                .to_string()
+        })
+        .unwrap_or_default();
+    let merged_str = if status
+        .info()
+        .default_branch
+        .as_ref()
+        .is_some_and(|d| d.merged)
+    {
+        format!(" {}", "[merged]".yellow())
+    } else {
+        String::new()
+    };
+    let diff_str = diff_label(status);
+    let conflicts = status.conflicts().len();
+    let conflict_str = if conflicts == 0 {
+        String::new()
+    } else {
+        let label = format!(
+            "[{} conflict{}]",
+            conflicts,
+            if conflicts == 1 { "" } else { "s" }
+        );
+        format!(" {}", label.red().bold())
+    };
+    let bare_str = if status.info().kind == RepoKind::Bare {
+        format!(" {}", "[bare]".bright_black())
+    } else {
+        String::new()
+    };
+    let remotes = &status.info().remotes;
+    let remote_str = if remotes.is_empty() {
+        format!(" {}", "[no remote]".red().bold())
+    } else if remotes.iter().any(|r| r.pushes_over_https()) {
+        format!(" {}", "[https push]".yellow())
+    } else {
+        String::new()
+    };
+    let (repo, name) = if status.is_dirty() {
+        (symbols().repo.yellow(), repo_name.yellow().bold())
+    } else {
+        (symbols().repo.green(), repo_name.green())
+    };
+    eprintln!(
+        "{} {}{}{}{}{}{}{}{}",
+        repo, name, bare_str, branch_str, merged_str, conflict_str, diff_str, remote_str, age_str
+    );
+}
+
+/// Prints the submodules of a repository, indented below it
+pub fn print_submodules(status: &RepoStatus) {
+    for submodule in &status.info().submodules {
+        let problems = submodule.problems();
+        let path = submodule.path.display().to_string();
+        let path = if submodule.needs_attention() {
+            path.yellow()
+        } else if problems.is_empty() {
+            path.green()
+        } else {
+            path.bright_black()
+        };
+        let detail = if problems.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", problems.join(", "))
+        };
+        eprintln!(
+            "    {} {} {}{}",
+            symbols().submodule.bright_black(),
+            path,
+            submodule.commit.bright_black(),
+            detail
+        );
+    }
+}
+
+/// Prints the linked worktrees of a repository, indented below it
+pub fn print_worktrees(status: &RepoStatus) {
+    for worktree in &status.info().worktrees {
+        let path = worktree.path.display().to_string();
+        let branch = match &worktree.branch {
+            Some(branch) => branch.bright_cyan(),
+            None => format!("detached at {}", worktree.commit).magenta(),
+        };
+        let detail = if worktree.missing {
+            format!(" {}", "[missing]".red().bold())
+        } else if worktree.prunable {
+            format!(" {}", "[prunable]".red())
+        } else if worktree.is_dirty() {
+            let count = worktree.changes;
+            format!(" [{} change{}]", count, if count == 1 { "" } else { "s" })
+                .yellow()
+                .to_string()
+        } else {
+            String::new()
+        };
+        let path = if worktree.is_stale() {
+            path.bright_black()
+        } else if worktree.is_dirty() {
+            path.yellow()
+        } else {
+            path.green()
+        };
+        eprintln!(
+            "    {} {} ({}){}",
+            symbols().worktree.bright_black(),
+            path,
+            branch,
+            detail
+        );
+    }
+}
+
+/// Prints verbose status for a single repository under the given name
+pub fn print_verbose_status(status: &RepoStatus, repo_name: &str) {
+    print_repo_line(status, repo_name);
+    print_submodules(status);
+    print_worktrees(status);
+    print_default_branch(status);
+    print_missing_upstream(status);
+    print_lfs(status);
+    if let RepoStatus::Dirty { changes, .. } = status {
+        for line in changes.lines() {
+            if line.get(..2).is_some_and(core::is_conflict_code) {
+                eprintln!("  {}", line.red().bold());
+            } else if !line.trim().is_empty() {
+                eprintln!("  {}", line.bright_white());
             }
-            eprintln!();
         }
+        eprintln!();
     }
 }
 
+/// Prints how far the checked-out branch has drifted from the default branch
+pub fn print_default_branch(status: &RepoStatus) {
+    let Some(default) = &status.info().default_branch else {
+        return;
+    };
+    let drift = format!("{} ahead, {} behind", default.ahead, default.behind);
+    eprintln!(
+        "    {} {}",
+        format!("vs {}:", default.name).bright_black(),
+        if default.merged {
+            format!("{drift}, merged").yellow()
+        } else {
+            drift.normal()
+        }
+    );
+}
+
+/// Prints the branches of a bare repository that have no upstream
+pub fn print_missing_upstream(status: &RepoStatus) {
+    let missing = &status.info().missing_upstream;
+    if !missing.is_empty() {
+        eprintln!(
+            "    {} {}",
+            "no upstream:".bright_black(),
+            missing.join(", ").yellow()
+        );
+    }
+}
+
+/// Prints the Git LFS problems of a repository, with the affected files
+pub fn print_lfs(status: &RepoStatus) {
+    let Some(lfs) = status.info().lfs.as_ref().filter(|l| l.needs_attention()) else {
+        return;
+    };
+    eprintln!(
+        "    {} {}",
+        "LFS:".bright_black(),
+        lfs.problems().join(", ").yellow()
+    );
+    for (label, files) in [
+        ("unpushed", &lfs.unpushed),
+        ("missing", &lfs.missing),
+        ("plain", &lfs.plain),
+    ] {
+        for file in files {
+            eprintln!(
+                "      {} {}",
+                format!("{label}:").bright_black(),
+                file.display()
+            );
+        }
+    }
+}
+
+/// Prints the header line for a group of repositories
+pub fn print_group_header(group: &Group<'_>) {
+    let total = group.repos.len();
+    let dirty = group.dirty_count();
+    let dirty_str = if dirty > 0 {
+        format!("{} dirty", dirty).yellow().bold()
+    } else {
+        "all clean".green()
+    };
+    eprintln!(
+        "{} {} ({} repo{}, {})",
+        symbols().group.cyan(),
+        group.name.bright_blue().bold(),
+        total,
+        if total == 1 { "" } else { "s" },
+        dirty_str
+    );
+}
+
 /// Prints summary statistics
 pub fn print_summary(total: usize, dirty: usize) {
     let clean = total - dirty;
 
-    eprintln!("{} Total repos: {}", "📦".cyan(), total);
-    eprintln!("{} Clean repos: {}", "✓".green(), clean.to_string().green());
+    let symbols = symbols();
+    eprintln!("{} Total repos: {}", symbols.repo.cyan(), total);
+    eprintln!(
+        "{} Clean repos: {}",
+        symbols.clean.green(),
+        clean.to_string().green()
+    );
     eprintln!(
         "{} Dirty repos: {}",
-        "✗".yellow(),
+        symbols.dirty.yellow(),
         if dirty > 0 {
             dirty.to_string().yellow().bold()
         } else {
@@ -120,8 +673,195 @@ pub fn print_summary(total: usize, dirty: usize) {
     );
 }
 
-/// Outputs results in JSON format
-pub fn print_json(statuses: &[RepoStatus]) -> Result<(), serde_json::Error> {
+/// Prints the size and maintenance state of each repository
+pub fn print_housekeeping(statuses: &[RepoStatus], names: &HashMap<PathBuf, String>) {
+    let mut printed = false;
+    for status in statuses {
+        let Some(housekeeping) = &status.info().housekeeping else {
+            continue;
+        };
+        let mut markers = String::new();
+        if housekeeping.needs_gc {
+            markers.push_str(&format!(" {}", "[needs gc]".yellow()));
+        }
+        if housekeeping.shallow {
+            markers.push_str(&format!(" {}", "[shallow]".bright_black()));
+        }
+        if housekeeping.sparse {
+            markers.push_str(&format!(" {}", "[sparse]".bright_black()));
+        }
+        let plural = |n: u64| if n == 1 { "" } else { "s" };
+        eprintln!(
+            "{} {} {}{}",
+            symbols().repo.cyan(),
+            names[status.path()],
+            format!(
+                "{}, {} loose object{}, {} pack{}",
+                humanize_size(housekeeping.git_size),
+                housekeeping.loose_objects,
+                plural(housekeeping.loose_objects),
+                housekeeping.packs,
+                plural(housekeeping.packs)
+            )
+            .bright_black(),
+            markers
+        );
+        printed = true;
+    }
+    if printed {
+        eprintln!();
+    }
+}
+
+/// Prints the uncommitted files that are too large or look like secrets,
+/// grouped by repository
+pub fn print_file_warnings(statuses: &[RepoStatus], names: &HashMap<PathBuf, String>) {
+    let flagged: Vec<&RepoStatus> = statuses
+        .iter()
+        .filter(|s| !s.info().file_warnings.is_empty())
+        .collect();
+    if flagged.is_empty() {
+        return;
+    }
+    for status in flagged {
+        eprintln!(
+            "{} {}",
+            symbols().warning.yellow(),
+            names[status.path()].bold()
+        );
+        for warning in &status.info().file_warnings {
+            let (label, detail) = match &warning.kind {
+                FileWarningKind::Large { size } => ("large".yellow(), humanize_size(*size)),
+                FileWarningKind::Secret { pattern } => {
+                    ("secret".red().bold(), format!("matches {pattern}"))
+                }
+            };
+            eprintln!(
+                "    [{}] {} {}",
+                label,
+                warning.path.display(),
+                format!("({detail})").bright_black()
+            );
+        }
+    }
+    eprintln!();
+}
+
+/// Prints counts of repos that risk losing work, if any
+///
+/// Covers repos with conflicts, without a remote or pushing over HTTPS,
+/// linked worktrees with changes or whose directory is gone, uncommitted
+/// files that are too large or look like secrets, LFS problems, and branches
+/// already merged into the default branch.
+pub fn print_warnings(statuses: &[RepoStatus]) {
+    let count = |pred: &dyn Fn(&RepoStatus) -> bool| statuses.iter().filter(|s| pred(s)).count();
+    let worktrees = |pred: fn(&Worktree) -> bool| {
+        statuses
+            .iter()
+            .flat_map(|s| &s.info().worktrees)
+            .filter(|w| pred(w))
+            .count()
+    };
+    let file_warnings = |pred: &dyn Fn(&FileWarningKind) -> bool| {
+        statuses
+            .iter()
+            .flat_map(|s| &s.info().file_warnings)
+            .filter(|w| pred(&w.kind))
+            .count()
+    };
+    let warnings = [
+        (
+            "Repos with conflicts",
+            count(&|s| !s.conflicts().is_empty()),
+            true,
+        ),
+        (
+            "Without a remote",
+            count(&|s| s.info().remotes.is_empty()),
+            true,
+        ),
+        (
+            "Pushing over HTTPS",
+            count(&|s| s.info().remotes.iter().any(|r| r.pushes_over_https())),
+            false,
+        ),
+        ("Dirty worktrees", worktrees(Worktree::is_dirty), false),
+        ("Stale worktrees", worktrees(Worktree::is_stale), true),
+        (
+            "Large uncommitted files",
+            file_warnings(&|k| matches!(k, FileWarningKind::Large { .. })),
+            false,
+        ),
+        (
+            "Secret-looking files",
+            file_warnings(&|k| matches!(k, FileWarningKind::Secret { .. })),
+            true,
+        ),
+        (
+            "LFS problems",
+            count(&|s| s.info().lfs.as_ref().is_some_and(|l| l.needs_attention())),
+            true,
+        ),
+        (
+            "Merged branches",
+            count(&|s| s.info().default_branch.as_ref().is_some_and(|d| d.merged)),
+            false,
+        ),
+    ];
+    for (label, n, severe) in warnings {
+        if n == 0 {
+            continue;
+        }
+        if severe {
+            eprintln!(
+                "{} {}: {}",
+                symbols().warning.red(),
+                label,
+                n.to_string().red().bold()
+            );
+        } else {
+            eprintln!(
+                "{} {}: {}",
+                symbols().warning.yellow(),
+                label,
+                n.to_string().yellow()
+            );
+        }
+    }
+}
+
+/// Prints status cache statistics
+pub fn print_cache_stats(stats: &CacheStats, path: &Path) {
+    print_info(&format!(
+        "Cache: {} hit{}, {} miss{}, {} entr{} in {}",
+        stats.hits,
+        if stats.hits == 1 { "" } else { "s" },
+        stats.misses,
+        if stats.misses == 1 { "" } else { "es" },
+        stats.entries,
+        if stats.entries == 1 { "y" } else { "ies" },
+        path.display()
+    ));
+}
+
+/// Prints discovery cache statistics
+pub fn print_discovery_stats(scanned: usize, repos: usize) {
+    print_info(&format!(
+        "Discovery: {} director{} read, {} repo{} found",
+        scanned,
+        if scanned == 1 { "y" } else { "ies" },
+        repos,
+        if repos == 1 { "" } else { "s" }
+    ));
+}
+
+/// Outputs results in JSON format, including groups and policy violations
+/// when given
+pub fn print_json(
+    statuses: &[RepoStatus],
+    groups: Option<&[Group<'_>]>,
+    violations: Option<&[Violation]>,
+) -> Result<(), serde_json::Error> {
     let total = statuses.len();
     let dirty = statuses.iter().filter(|s| s.is_dirty()).count();
     let clean = total - dirty;
@@ -131,6 +871,8 @@ pub fn print_json(statuses: &[RepoStatus]) -> Result<(), serde_json::Error> {
         dirty,
         clean,
         repositories: statuses.to_vec(),
+        groups: groups.map(|groups| groups.iter().map(JsonGroup::from).collect()),
+        violations: violations.map(<[Violation]>::to_vec),
     };
 
     println!("{}", serde_json::to_string_pretty(&output)?);
@@ -142,6 +884,11 @@ fn terminal_width() -> usize {
     term_size::dimensions().map(|(w, _)| w).unwrap_or(80)
 }
 
+/// Print informational message
+pub fn print_info(message: &str) {
+    eprintln!("{} {}", "Info:".cyan().bold(), message);
+}
+
 /// Print warning message
 pub fn print_warning(message: &str) {
     eprintln!("{} {}", "Warning:".yellow().bold(), message);
@@ -155,6 +902,8 @@ pub fn print_error(message: &str) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::RepoInfo;
+    use crate::diffstat::DiffStats;
 
     #[test]
     fn test_output_format_equality() {
@@ -176,21 +925,156 @@ mod tests {
         assert_eq!(Verbosity::from_flags(false, false), Verbosity::Summary);
     }
 
+    #[test]
+    fn test_should_colorize() {
+        assert!(should_colorize(ColorChoice::Always, Some("1"), None, false));
+        assert!(!should_colorize(ColorChoice::Never, None, Some("1"), true));
+        assert!(should_colorize(ColorChoice::Auto, None, None, true));
+        assert!(!should_colorize(ColorChoice::Auto, None, None, false));
+        assert!(!should_colorize(
+            ColorChoice::Auto,
+            Some("1"),
+            Some("1"),
+            true
+        ));
+        assert!(should_colorize(ColorChoice::Auto, Some(""), None, true));
+        assert!(should_colorize(ColorChoice::Auto, None, Some("1"), false));
+        assert!(!should_colorize(ColorChoice::Auto, None, Some("0"), false));
+    }
+
+    #[test]
+    fn test_ascii_symbols_are_plain() {
+        let all = [
+            ASCII.search,
+            ASCII.repo,
+            ASCII.clean,
+            ASCII.dirty,
+            ASCII.group,
+            ASCII.rule,
+            ASCII.tree_branch,
+            ASCII.tree_last,
+            ASCII.tree_pipe,
+        ];
+        assert!(all.iter().all(|s| s.is_ascii()));
+    }
+
+    #[test]
+    fn test_humanize_age() {
+        let days = |n: u64| Duration::from_secs(n * 86400);
+        assert_eq!(humanize_age(Duration::from_secs(59)), "less than a minute");
+        assert_eq!(humanize_age(Duration::from_secs(3600)), "1 hour");
+        assert_eq!(humanize_age(days(1)), "1 day");
+        assert_eq!(humanize_age(days(23)), "3 weeks");
+        assert_eq!(humanize_age(days(90)), "3 months");
+        assert_eq!(humanize_age(days(800)), "2 years");
+    }
+
+    #[test]
+    fn test_humanize_size() {
+        assert_eq!(humanize_size(512), "512 B");
+        assert_eq!(humanize_size(2048), "2.0 kB");
+        assert_eq!(humanize_size(120_500_000), "120.5 MB");
+        assert_eq!(humanize_size(3_000_000_000), "3.0 GB");
+    }
+
+    #[test]
+    fn test_diff_label() {
+        colored::control::set_override(false);
+        let mut status = RepoStatus::Dirty {
+            path: PathBuf::from("/r/a"),
+            changes: " M a\n".to_string(),
+            diff: DiffStats::default(),
+            conflicts: Vec::new(),
+            branch: None,
+            info: RepoInfo::default(),
+        };
+        assert_eq!(diff_label(&status), "");
+        if let RepoStatus::Dirty { diff, .. } = &mut status {
+            diff.staged.added = 100;
+            diff.unstaged.added = 20;
+            diff.unstaged.removed = 34;
+        }
+        assert_eq!(diff_label(&status), " +120 -34");
+    }
+
+    #[test]
+    fn test_head_label() {
+        colored::control::set_override(false);
+        let status = |branch: Option<&str>, head: Option<HeadState>| RepoStatus::Clean {
+            path: PathBuf::from("/r/a"),
+            branch: branch.map(str::to_string),
+            info: crate::core::RepoInfo {
+                head,
+                ..Default::default()
+            },
+        };
+        let detached = HeadState::Detached {
+            commit: "1a2b3c4".to_string(),
+            tag: Some("v1.0".to_string()),
+        };
+        let unborn = HeadState::Unborn {
+            name: "main".to_string(),
+        };
+        assert_eq!(
+            head_label(&status(None, Some(detached))),
+            " (detached at 1a2b3c4 v1.0)"
+        );
+        assert_eq!(
+            head_label(&status(Some("main"), Some(unborn.clone()))),
+            " (main, no commits)"
+        );
+        assert_eq!(head_label(&status(None, Some(unborn))), " (no commits)");
+        assert_eq!(head_label(&status(Some("dev"), None)), " (dev)");
+        assert_eq!(head_label(&status(None, None)), "");
+    }
+
+    #[test]
+    fn test_unique_names() {
+        let paths = [
+            Path::new("/p/work/api"),
+            Path::new("/p/oss/api"),
+            Path::new("/p/tool"),
+            Path::new("/q/oss/api"),
+        ];
+        assert_eq!(
+            unique_names(&paths),
+            ["work/api", "p/oss/api", "tool", "q/oss/api"]
+        );
+    }
+
+    #[test]
+    fn test_styled_path() {
+        let root = Path::new("/p");
+        let path = Path::new("/p/work/api");
+        assert_eq!(styled_path(path, root, PathStyle::Relative), "work/api");
+        assert_eq!(styled_path(root, root, PathStyle::Relative), ".");
+        assert_eq!(styled_path(path, root, PathStyle::Absolute), "/p/work/api");
+
+        if let Some(home) = dirs::home_dir() {
+            let path = home.join("projects/api");
+            assert_eq!(styled_path(&path, root, PathStyle::Home), "~/projects/api");
+        }
+    }
+
     #[test]
     fn test_json_output_structure() {
         let statuses = vec![
             RepoStatus::Clean {
                 path: std::path::PathBuf::from("/test/clean"),
                 branch: Some("main".to_string()),
+                info: RepoInfo::default(),
             },
             RepoStatus::Dirty {
                 path: std::path::PathBuf::from("/test/dirty"),
                 changes: "M file.txt\n".to_string(),
+                diff: DiffStats::default(),
+                conflicts: Vec::new(),
                 branch: Some("dev".to_string()),
+                info: RepoInfo::default(),
             },
         ];
 
-        let result = print_json(&statuses);
+        let result = print_json(&statuses, None, None);
         assert!(result.is_ok());
     }
 
@@ -201,11 +1085,36 @@ mod tests {
             dirty: 3,
             clean: 7,
             repositories: vec![],
+            groups: None,
+            violations: None,
         };
 
         let json = serde_json::to_string(&output).unwrap();
         assert!(json.contains("\"total\":10"));
         assert!(json.contains("\"dirty\":3"));
         assert!(json.contains("\"clean\":7"));
+        assert!(!json.contains("groups"));
+        assert!(!json.contains("violations"));
+    }
+
+    #[test]
+    fn test_json_group_from_group() {
+        let statuses = [RepoStatus::Dirty {
+            path: PathBuf::from("/test/dirty"),
+            changes: "M file.txt\n".to_string(),
+            diff: DiffStats::default(),
+            conflicts: Vec::new(),
+            branch: None,
+            info: RepoInfo::default(),
+        }];
+        let group = Group {
+            name: "main".to_string(),
+            repos: statuses.iter().collect(),
+        };
+
+        let json = JsonGroup::from(&group);
+        assert_eq!(json.total, 1);
+        assert_eq!(json.dirty, 1);
+        assert_eq!(json.repositories, [PathBuf::from("/test/dirty")]);
     }
 }
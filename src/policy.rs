@@ -0,0 +1,404 @@
+//! Policy rules for CI and pre-shutdown gates
+//!
+//! This module loads a TOML policy file of named rules, each a query
+//! expression that flags violating repositories, and evaluates them against
+//! the checked repositories:
+//!
+//! ```toml
+//! [tags]
+//! scratch = ["scratch/*", "tmp/**"]
+//!
+//! [[rule]]
+//! name = "detached-head"
+//! description = "No detached HEAD outside scratch repos"
+//! when = "detached"
+//! severity = "error"
+//! except_tags = ["scratch"]
+//! ```
+//!
+//! Tags name groups of repositories by glob patterns on their path
+//! relative to the scan root. A rule applies to repositories carrying any
+//! of its `tags` (all repositories if empty) and none of its `except_tags`.
+
+use crate::core::RepoStatus;
+use crate::error::{Error, Result};
+use crate::output;
+use crate::query::{Field, Filter};
+use colored::*;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How serious a rule violation is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// Exit code for a run whose most serious violation has this severity
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Severity::Info => 0,
+            Severity::Warning => 1,
+            Severity::Error => 2,
+        }
+    }
+
+    fn colorize(self, text: &str) -> ColoredString {
+        match self {
+            Severity::Info => text.cyan(),
+            Severity::Warning => text.yellow(),
+            Severity::Error => text.red().bold(),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A rule as written in the policy file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    when: String,
+    #[serde(default)]
+    severity: Severity,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    except_tags: Vec<String>,
+}
+
+/// The policy file layout
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyConfig {
+    #[serde(default)]
+    tags: BTreeMap<String, Vec<String>>,
+    #[serde(default, rename = "rule")]
+    rules: Vec<RuleConfig>,
+}
+
+/// A compiled rule
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    description: Option<String>,
+    filter: Filter,
+    severity: Severity,
+    tags: Vec<String>,
+    except_tags: Vec<String>,
+}
+
+impl Rule {
+    fn applies_to(&self, tags: &[&str]) -> bool {
+        let included = self.tags.is_empty() || self.tags.iter().any(|t| tags.contains(&t.as_str()));
+        included && !self.except_tags.iter().any(|t| tags.contains(&t.as_str()))
+    }
+}
+
+/// A repository that broke a rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    pub path: PathBuf,
+    pub rule: String,
+    pub severity: Severity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// A loaded policy file
+#[derive(Debug)]
+pub struct Policy {
+    tags: Vec<(String, GlobSet)>,
+    rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// Reads and compiles a policy file
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Policy` if the file can't be read, isn't valid TOML,
+    /// or contains an invalid glob, query or tag reference
+    pub fn load(path: &Path) -> Result<Policy> {
+        let text = fs::read_to_string(path).map_err(|e| Error::Policy {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        Policy::parse(&text).map_err(|message| Error::Policy {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    /// Compiles a policy from TOML text, describing the first problem found
+    fn parse(text: &str) -> std::result::Result<Policy, String> {
+        let config: PolicyConfig = toml::from_str(text).map_err(|e| e.to_string())?;
+
+        let mut tags = Vec::new();
+        for (name, patterns) in config.tags {
+            let mut builder = GlobSetBuilder::new();
+            for pattern in &patterns {
+                let glob = Glob::new(pattern)
+                    .map_err(|e| format!("tag '{}': invalid pattern: {}", name, e))?;
+                builder.add(glob);
+            }
+            let set = builder
+                .build()
+                .map_err(|e| format!("tag '{}': {}", name, e))?;
+            tags.push((name, set));
+        }
+
+        let mut rules = Vec::new();
+        for rule in config.rules {
+            let filter =
+                Filter::parse(&rule.when).map_err(|e| format!("rule '{}': {}", rule.name, e))?;
+            for tag in rule.tags.iter().chain(&rule.except_tags) {
+                if !tags.iter().any(|(name, _)| name == tag) {
+                    return Err(format!("rule '{}': unknown tag '{}'", rule.name, tag));
+                }
+            }
+            rules.push(Rule {
+                name: rule.name,
+                description: rule.description,
+                filter,
+                severity: rule.severity,
+                tags: rule.tags,
+                except_tags: rule.except_tags,
+            });
+        }
+
+        Ok(Policy { tags, rules })
+    }
+
+    /// Returns true if any rule references the given field
+    pub fn uses(&self, field: Field) -> bool {
+        self.rules.iter().any(|r| r.filter.uses(field))
+    }
+
+    /// Gets the tags of a repository from its path relative to the root
+    fn tags_for(&self, status: &RepoStatus, root: &Path) -> Vec<&str> {
+        let path = status.path();
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        self.tags
+            .iter()
+            .filter(|(_, set)| set.is_match(relative))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Evaluates every rule against every repository
+    ///
+    /// Violations are ordered by repository, then by rule order in the file.
+    pub fn evaluate(&self, statuses: &[RepoStatus], root: &Path) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for status in statuses {
+            let tags = self.tags_for(status, root);
+            for rule in &self.rules {
+                if rule.applies_to(&tags) && rule.filter.matches(status) {
+                    violations.push(Violation {
+                        path: status.path().to_path_buf(),
+                        rule: rule.name.clone(),
+                        severity: rule.severity,
+                        description: rule.description.clone(),
+                    });
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// Gets the most serious severity among the violations
+pub fn highest_severity(violations: &[Violation]) -> Option<Severity> {
+    violations.iter().map(|v| v.severity).max()
+}
+
+/// Prints violations grouped by repository
+pub fn print_violations(violations: &[Violation], names: &HashMap<PathBuf, String>) {
+    if violations.is_empty() {
+        eprintln!(
+            "{} {}",
+            output::symbols().clean.green(),
+            "No policy violations".green()
+        );
+        eprintln!();
+        return;
+    }
+
+    eprintln!(
+        "{} {} policy violation{}",
+        output::symbols().dirty.red(),
+        violations.len(),
+        if violations.len() == 1 { "" } else { "s" }
+    );
+    let mut current: Option<&Path> = None;
+    for violation in violations {
+        if current != Some(violation.path.as_path()) {
+            eprintln!("  {}", names[&violation.path].bold());
+            current = Some(&violation.path);
+        }
+        let label = format!("[{}]", violation.severity);
+        match &violation.description {
+            Some(description) => eprintln!(
+                "    {} {}: {}",
+                violation.severity.colorize(&label),
+                violation.rule,
+                description
+            ),
+            None => eprintln!(
+                "    {} {}",
+                violation.severity.colorize(&label),
+                violation.rule
+            ),
+        }
+    }
+    eprintln!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{HeadState, RepoInfo};
+
+    const POLICY: &str = r#"
+        [tags]
+        scratch = ["scratch/*"]
+
+        [[rule]]
+        name = "no-unpushed"
+        description = "No repo may be ahead of upstream"
+        when = "ahead > 0"
+        severity = "error"
+
+        [[rule]]
+        name = "detached-head"
+        when = "detached"
+        except_tags = ["scratch"]
+
+        [[rule]]
+        name = "big-untracked"
+        when = "largest_untracked > 10MB"
+        severity = "info"
+    "#;
+
+    fn detached() -> HeadState {
+        HeadState::Detached {
+            commit: "1a2b3c4".to_string(),
+            tag: None,
+        }
+    }
+
+    fn repo(path: &str, info: RepoInfo) -> RepoStatus {
+        RepoStatus::Clean {
+            path: PathBuf::from(path),
+            branch: None,
+            info,
+        }
+    }
+
+    fn sample() -> Vec<RepoStatus> {
+        vec![
+            repo(
+                "/p/api",
+                RepoInfo {
+                    ahead: Some(1),
+                    head: Some(detached()),
+                    ..RepoInfo::default()
+                },
+            ),
+            repo(
+                "/p/scratch/play",
+                RepoInfo {
+                    head: Some(detached()),
+                    largest_untracked: Some(50_000_000),
+                    ..RepoInfo::default()
+                },
+            ),
+            repo("/p/web", RepoInfo::default()),
+        ]
+    }
+
+    #[test]
+    fn test_evaluate_rules_and_tags() {
+        let policy = Policy::parse(POLICY).unwrap();
+        let violations = policy.evaluate(&sample(), Path::new("/p"));
+        let found: Vec<(&str, &str)> = violations
+            .iter()
+            .map(|v| (v.path.to_str().unwrap(), v.rule.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("/p/api", "no-unpushed"),
+                ("/p/api", "detached-head"),
+                ("/p/scratch/play", "big-untracked"),
+            ]
+        );
+        assert_eq!(highest_severity(&violations), Some(Severity::Error));
+        assert_eq!(violations[1].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_only_tags() {
+        let policy = Policy::parse(
+            r#"
+            [tags]
+            scratch = ["scratch/**"]
+
+            [[rule]]
+            name = "scratch-only"
+            when = "detached"
+            tags = ["scratch"]
+            "#,
+        )
+        .unwrap();
+        let violations = policy.evaluate(&sample(), Path::new("/p"));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, Path::new("/p/scratch/play"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = Policy::parse("[[rule]]\nname = \"x\"\nwhen = \"ahead >\"\n").unwrap_err();
+        assert!(err.starts_with("rule 'x': Invalid query"), "{err}");
+
+        let err =
+            Policy::parse("[[rule]]\nname = \"x\"\nwhen = \"dirty\"\nexcept_tags = [\"nope\"]\n")
+                .unwrap_err();
+        assert_eq!(err, "rule 'x': unknown tag 'nope'");
+
+        assert!(
+            Policy::parse("[[rule]]\nname = \"x\"\nwhen = \"dirty\"\nseverity = \"fatal\"\n")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_severity_order_and_exit_codes() {
+        assert!(Severity::Error > Severity::Warning);
+        assert!(Severity::Warning > Severity::Info);
+        assert_eq!(Severity::Info.exit_code(), 0);
+        assert_eq!(Severity::Error.exit_code(), 2);
+        assert_eq!(highest_severity(&[]), None);
+    }
+}
@@ -0,0 +1,1121 @@
+//! Query expressions for selecting repositories
+//!
+//! This module implements the small expression language accepted by
+//! `--where`, for example:
+//!
+//! ```text
+//! (dirty or ahead) and not branch == "main" and age > 30d
+//! ```
+//!
+//! Expressions combine field comparisons with `and`, `or` and `not`
+//! (or `&&`, `||` and `!`). Text fields support `==`, `!=` and the regex
+//! operators `=~` and `!~`; number and duration fields support all
+//! comparison operators. A bare field is true when it is set and non-zero.
+
+use crate::analysis::FileWarningKind;
+use crate::core::{HeadState, RepoKind, RepoStatus};
+use crate::error::{Error, Result};
+use crate::housekeeping::Housekeeping;
+use crate::lfs::LfsStatus;
+use crate::remote;
+use regex::Regex;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Fields of a `RepoStatus` that can be referenced in a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Path,
+    Name,
+    Status,
+    Branch,
+    Dirty,
+    Clean,
+    Changes,
+    Ahead,
+    Behind,
+    DefaultAhead,
+    DefaultBehind,
+    Merged,
+    Age,
+    Detached,
+    Unborn,
+    Stashes,
+    StashAge,
+    LargestUntracked,
+    Host,
+    Owner,
+    Remotes,
+    HttpsPush,
+    Worktrees,
+    DirtyWorktrees,
+    Bare,
+    MissingUpstream,
+    Lfs,
+    LfsUnpushed,
+    LfsMissing,
+    LfsPlain,
+    LargeFiles,
+    SecretFiles,
+    GitSize,
+    LooseObjects,
+    Packs,
+    NeedsGc,
+    Shallow,
+    Sparse,
+    Added,
+    Removed,
+    Conflicts,
+}
+
+/// Value type of a field, used to type-check comparisons
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Number,
+    Duration,
+    Bool,
+}
+
+impl Field {
+    const ALL: [Field; 41] = [
+        Field::Path,
+        Field::Name,
+        Field::Status,
+        Field::Branch,
+        Field::Dirty,
+        Field::Clean,
+        Field::Changes,
+        Field::Ahead,
+        Field::Behind,
+        Field::DefaultAhead,
+        Field::DefaultBehind,
+        Field::Merged,
+        Field::Age,
+        Field::Detached,
+        Field::Unborn,
+        Field::Stashes,
+        Field::StashAge,
+        Field::LargestUntracked,
+        Field::Host,
+        Field::Owner,
+        Field::Remotes,
+        Field::HttpsPush,
+        Field::Worktrees,
+        Field::DirtyWorktrees,
+        Field::Bare,
+        Field::MissingUpstream,
+        Field::Lfs,
+        Field::LfsUnpushed,
+        Field::LfsMissing,
+        Field::LfsPlain,
+        Field::LargeFiles,
+        Field::SecretFiles,
+        Field::GitSize,
+        Field::LooseObjects,
+        Field::Packs,
+        Field::NeedsGc,
+        Field::Shallow,
+        Field::Sparse,
+        Field::Added,
+        Field::Removed,
+        Field::Conflicts,
+    ];
+
+    fn from_name(name: &str) -> Option<Field> {
+        Field::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Path => "path",
+            Field::Name => "name",
+            Field::Status => "status",
+            Field::Branch => "branch",
+            Field::Dirty => "dirty",
+            Field::Clean => "clean",
+            Field::Changes => "changes",
+            Field::Ahead => "ahead",
+            Field::Behind => "behind",
+            Field::DefaultAhead => "default_ahead",
+            Field::DefaultBehind => "default_behind",
+            Field::Merged => "merged",
+            Field::Age => "age",
+            Field::Detached => "detached",
+            Field::Unborn => "unborn",
+            Field::Stashes => "stashes",
+            Field::StashAge => "stash_age",
+            Field::LargestUntracked => "largest_untracked",
+            Field::Host => "host",
+            Field::Owner => "owner",
+            Field::Remotes => "remotes",
+            Field::HttpsPush => "https_push",
+            Field::Worktrees => "worktrees",
+            Field::DirtyWorktrees => "dirty_worktrees",
+            Field::Bare => "bare",
+            Field::MissingUpstream => "missing_upstream",
+            Field::Lfs => "lfs",
+            Field::LfsUnpushed => "lfs_unpushed",
+            Field::LfsMissing => "lfs_missing",
+            Field::LfsPlain => "lfs_plain",
+            Field::LargeFiles => "large_files",
+            Field::SecretFiles => "secret_files",
+            Field::GitSize => "git_size",
+            Field::LooseObjects => "loose_objects",
+            Field::Packs => "packs",
+            Field::NeedsGc => "needs_gc",
+            Field::Shallow => "shallow",
+            Field::Sparse => "sparse",
+            Field::Added => "added",
+            Field::Removed => "removed",
+            Field::Conflicts => "conflicts",
+        }
+    }
+
+    fn kind(self) -> Kind {
+        match self {
+            Field::Path
+            | Field::Name
+            | Field::Status
+            | Field::Branch
+            | Field::Host
+            | Field::Owner => Kind::Text,
+            Field::Dirty
+            | Field::Clean
+            | Field::Detached
+            | Field::Unborn
+            | Field::HttpsPush
+            | Field::Bare
+            | Field::Merged
+            | Field::Lfs
+            | Field::NeedsGc
+            | Field::Shallow
+            | Field::Sparse => Kind::Bool,
+            Field::Changes
+            | Field::Ahead
+            | Field::Behind
+            | Field::DefaultAhead
+            | Field::DefaultBehind
+            | Field::Stashes
+            | Field::LargestUntracked
+            | Field::Remotes
+            | Field::Worktrees
+            | Field::DirtyWorktrees
+            | Field::MissingUpstream
+            | Field::LfsUnpushed
+            | Field::LfsMissing
+            | Field::LfsPlain
+            | Field::LargeFiles
+            | Field::SecretFiles
+            | Field::GitSize
+            | Field::LooseObjects
+            | Field::Packs
+            | Field::Added
+            | Field::Removed
+            | Field::Conflicts => Kind::Number,
+            Field::Age | Field::StashAge => Kind::Duration,
+        }
+    }
+
+    /// Extracts the field value, or `None` if the repository doesn't have it
+    fn value(self, status: &RepoStatus, now: SystemTime) -> Option<Value> {
+        let info = status.info();
+        match self {
+            Field::Path => Some(Value::Text(status.path().display().to_string())),
+            Field::Name => status
+                .path()
+                .file_name()
+                .map(|n| Value::Text(n.to_string_lossy().into_owned())),
+            Field::Status => Some(Value::Text(
+                if status.is_dirty() { "dirty" } else { "clean" }.to_string(),
+            )),
+            Field::Branch => status.branch().map(|b| Value::Text(b.to_string())),
+            Field::Dirty => Some(Value::Bool(status.is_dirty())),
+            Field::Clean => Some(Value::Bool(!status.is_dirty())),
+            Field::Changes => Some(Value::Number(status.change_count() as i64)),
+            Field::Ahead => info.ahead.map(|n| Value::Number(n as i64)),
+            Field::Behind => info.behind.map(|n| Value::Number(n as i64)),
+            Field::DefaultAhead => info
+                .default_branch
+                .as_ref()
+                .map(|d| Value::Number(d.ahead as i64)),
+            Field::DefaultBehind => info
+                .default_branch
+                .as_ref()
+                .map(|d| Value::Number(d.behind as i64)),
+            Field::Merged => info.default_branch.as_ref().map(|d| Value::Bool(d.merged)),
+            Field::Age => info.last_commit.as_ref().map(|c| age(c.timestamp, now)),
+            Field::Detached => Some(Value::Bool(matches!(
+                info.head,
+                Some(HeadState::Detached { .. })
+            ))),
+            Field::Unborn => Some(Value::Bool(matches!(
+                info.head,
+                Some(HeadState::Unborn { .. })
+            ))),
+            Field::Stashes => Some(Value::Number(info.stashes as i64)),
+            Field::StashAge => info.oldest_stash.map(|ts| age(ts, now)),
+            Field::LargestUntracked => info.largest_untracked.map(|n| Value::Number(n as i64)),
+            Field::Host => remote::primary(&info.remotes)
+                .and_then(|r| remote::host(&r.fetch))
+                .map(Value::Text),
+            Field::Owner => remote::primary(&info.remotes)
+                .and_then(|r| remote::owner(&r.fetch))
+                .map(Value::Text),
+            Field::Remotes => Some(Value::Number(info.remotes.len() as i64)),
+            Field::Worktrees => Some(Value::Number(info.worktrees.len() as i64)),
+            Field::DirtyWorktrees => Some(Value::Number(
+                info.worktrees.iter().filter(|w| w.is_dirty()).count() as i64,
+            )),
+            Field::HttpsPush => Some(Value::Bool(
+                info.remotes.iter().any(|r| r.pushes_over_https()),
+            )),
+            Field::Bare => Some(Value::Bool(info.kind == RepoKind::Bare)),
+            Field::MissingUpstream => Some(Value::Number(info.missing_upstream.len() as i64)),
+            Field::Lfs => Some(Value::Bool(info.lfs.is_some())),
+            Field::LfsUnpushed => lfs_count(status, |l| &l.unpushed),
+            Field::LfsMissing => lfs_count(status, |l| &l.missing),
+            Field::LfsPlain => lfs_count(status, |l| &l.plain),
+            Field::LargeFiles => Some(file_warning_count(status, |k| {
+                matches!(k, FileWarningKind::Large { .. })
+            })),
+            Field::SecretFiles => Some(file_warning_count(status, |k| {
+                matches!(k, FileWarningKind::Secret { .. })
+            })),
+            Field::GitSize => housekeeping(status, |h| Value::Number(h.git_size as i64)),
+            Field::LooseObjects => housekeeping(status, |h| Value::Number(h.loose_objects as i64)),
+            Field::Packs => housekeeping(status, |h| Value::Number(h.packs as i64)),
+            Field::NeedsGc => housekeeping(status, |h| Value::Bool(h.needs_gc)),
+            Field::Shallow => housekeeping(status, |h| Value::Bool(h.shallow)),
+            Field::Sparse => housekeeping(status, |h| Value::Bool(h.sparse)),
+            Field::Added => Some(Value::Number(status.diff().added() as i64)),
+            Field::Removed => Some(Value::Number(status.diff().removed() as i64)),
+            Field::Conflicts => Some(Value::Number(status.conflicts().len() as i64)),
+        }
+    }
+}
+
+/// Counts one kind of LFS problem, or `None` if the repository doesn't use LFS
+fn lfs_count(status: &RepoStatus, files: fn(&LfsStatus) -> &Vec<PathBuf>) -> Option<Value> {
+    status
+        .info()
+        .lfs
+        .as_ref()
+        .map(|lfs| Value::Number(files(lfs).len() as i64))
+}
+
+/// Counts the file warnings of one kind
+fn file_warning_count(status: &RepoStatus, kind: fn(&FileWarningKind) -> bool) -> Value {
+    let count = status
+        .info()
+        .file_warnings
+        .iter()
+        .filter(|w| kind(&w.kind))
+        .count();
+    Value::Number(count as i64)
+}
+
+/// Extracts a housekeeping value, or `None` outside housekeeping mode
+fn housekeeping(status: &RepoStatus, value: fn(&Housekeeping) -> Value) -> Option<Value> {
+    status.info().housekeeping.as_ref().map(value)
+}
+
+/// Computes the time elapsed since a Unix timestamp
+fn age(timestamp: i64, now: SystemTime) -> Value {
+    let then = UNIX_EPOCH + Duration::from_secs(timestamp.max(0) as u64);
+    Value::Duration(now.duration_since(then).unwrap_or_default())
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A literal or field value
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    Number(i64),
+    Duration(Duration),
+    Bool(bool),
+}
+
+impl Value {
+    fn kind(&self) -> Kind {
+        match self {
+            Value::Text(_) => Kind::Text,
+            Value::Number(_) => Kind::Number,
+            Value::Duration(_) => Kind::Duration,
+            Value::Bool(_) => Kind::Bool,
+        }
+    }
+
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Text(s) => !s.is_empty(),
+            Value::Number(n) => *n != 0,
+            Value::Duration(d) => !d.is_zero(),
+            Value::Bool(b) => *b,
+        }
+    }
+}
+
+/// Comparison operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        }
+    }
+
+    fn apply<T: PartialOrd>(self, left: &T, right: &T) -> bool {
+        match self {
+            CmpOp::Eq => left == right,
+            CmpOp::Ne => left != right,
+            CmpOp::Lt => left < right,
+            CmpOp::Le => left <= right,
+            CmpOp::Gt => left > right,
+            CmpOp::Ge => left >= right,
+        }
+    }
+}
+
+/// Parsed query expression
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Truthy(Field),
+    Compare(Field, CmpOp, Value),
+    Matches(Field, Regex, bool),
+}
+
+impl Expr {
+    fn eval(&self, status: &RepoStatus, now: SystemTime) -> bool {
+        match self {
+            Expr::And(l, r) => l.eval(status, now) && r.eval(status, now),
+            Expr::Or(l, r) => l.eval(status, now) || r.eval(status, now),
+            Expr::Not(e) => !e.eval(status, now),
+            Expr::Truthy(field) => field.value(status, now).is_some_and(|v| v.is_truthy()),
+            Expr::Compare(field, op, expected) => match field.value(status, now) {
+                Some(actual) => match (&actual, expected) {
+                    (Value::Text(a), Value::Text(b)) => op.apply(a, b),
+                    (Value::Number(a), Value::Number(b)) => op.apply(a, b),
+                    (Value::Duration(a), Value::Duration(b)) => op.apply(a, b),
+                    (Value::Bool(a), Value::Bool(b)) => op.apply(a, b),
+                    _ => false,
+                },
+                // A missing value is never equal to anything
+                None => *op == CmpOp::Ne,
+            },
+            Expr::Matches(field, regex, negate) => match field.value(status, now) {
+                Some(Value::Text(text)) => regex.is_match(&text) != *negate,
+                _ => *negate,
+            },
+        }
+    }
+
+    fn uses(&self, field: Field) -> bool {
+        match self {
+            Expr::And(l, r) | Expr::Or(l, r) => l.uses(field) || r.uses(field),
+            Expr::Not(e) => e.uses(field),
+            Expr::Truthy(f) | Expr::Compare(f, _, _) | Expr::Matches(f, _, _) => *f == field,
+        }
+    }
+}
+
+/// A compiled `--where` expression
+#[derive(Debug)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    /// Parses a query expression
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidQuery` describing the first syntax or type error
+    pub fn parse(query: &str) -> Result<Filter> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser {
+            query,
+            tokens,
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(parser.error_at(token.start, "unexpected token after expression"));
+        }
+        Ok(Filter { expr })
+    }
+
+    /// Matches repositories whose last commit is older than a duration
+    pub fn stale(after: Duration) -> Filter {
+        Filter {
+            expr: Expr::Compare(Field::Age, CmpOp::Gt, Value::Duration(after)),
+        }
+    }
+
+    /// Matches repositories with a commit within a duration
+    pub fn active(within: Duration) -> Filter {
+        Filter {
+            expr: Expr::Compare(Field::Age, CmpOp::Le, Value::Duration(within)),
+        }
+    }
+
+    /// Matches repositories whose branch is merged into the default branch
+    pub fn merged() -> Filter {
+        Filter {
+            expr: Expr::Truthy(Field::Merged),
+        }
+    }
+
+    /// Matches repositories whose remote field equals a value, ignoring case
+    pub fn remote(field: Field, value: &str) -> Filter {
+        let pattern = format!("(?i)^{}$", regex::escape(value));
+        let regex = Regex::new(&pattern).expect("escaped pattern is valid");
+        Filter {
+            expr: Expr::Matches(field, regex, false),
+        }
+    }
+
+    /// Combines two filters, matching repositories accepted by both
+    pub fn and(self, other: Filter) -> Filter {
+        Filter {
+            expr: Expr::And(Box::new(self.expr), Box::new(other.expr)),
+        }
+    }
+
+    /// Returns true if the repository matches the expression
+    pub fn matches(&self, status: &RepoStatus) -> bool {
+        self.matches_at(status, SystemTime::now())
+    }
+
+    /// Returns true if the repository matches, measuring ages relative to `now`
+    pub fn matches_at(&self, status: &RepoStatus, now: SystemTime) -> bool {
+        self.expr.eval(status, now)
+    }
+
+    /// Returns true if the expression references the given field
+    pub fn uses(&self, field: Field) -> bool {
+        self.expr.uses(field)
+    }
+}
+
+/// Lexical tokens
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Cmp(CmpOp),
+    Match(bool),
+    Word(String),
+    Str(String),
+    Number(i64),
+    Duration(Duration),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+}
+
+fn query_error(query: &str, position: usize, message: impl Into<String>) -> Error {
+    Error::InvalidQuery {
+        query: query.to_string(),
+        position,
+        message: message.into(),
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/')
+}
+
+/// Splits a query into tokens
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let two = query[start..].get(..2).unwrap_or("");
+        let (kind, len) = match (c, two) {
+            (_, "&&") => (TokenKind::And, 2),
+            (_, "||") => (TokenKind::Or, 2),
+            (_, "==") => (TokenKind::Cmp(CmpOp::Eq), 2),
+            (_, "!=") => (TokenKind::Cmp(CmpOp::Ne), 2),
+            (_, "<=") => (TokenKind::Cmp(CmpOp::Le), 2),
+            (_, ">=") => (TokenKind::Cmp(CmpOp::Ge), 2),
+            (_, "=~") => (TokenKind::Match(false), 2),
+            (_, "!~") => (TokenKind::Match(true), 2),
+            ('(', _) => (TokenKind::LParen, 1),
+            (')', _) => (TokenKind::RParen, 1),
+            ('<', _) => (TokenKind::Cmp(CmpOp::Lt), 1),
+            ('>', _) => (TokenKind::Cmp(CmpOp::Gt), 1),
+            ('!', _) => (TokenKind::Not, 1),
+            ('"' | '\'', _) => {
+                chars.next();
+                let mut text = String::new();
+                let mut closed = false;
+                while let Some((_, ch)) = chars.next() {
+                    match ch {
+                        '\\' => match chars.next() {
+                            Some((_, escaped)) => text.push(escaped),
+                            None => break,
+                        },
+                        ch if ch == c => {
+                            closed = true;
+                            break;
+                        }
+                        ch => text.push(ch),
+                    }
+                }
+                if !closed {
+                    return Err(query_error(query, start, "unterminated string"));
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Str(text),
+                    start,
+                });
+                continue;
+            }
+            (c, _) if is_word_char(c) => {
+                let mut end = start;
+                while let Some(&(i, ch)) = chars.peek() {
+                    if !is_word_char(ch) {
+                        break;
+                    }
+                    end = i + ch.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token {
+                    kind: word_token(query, start, &query[start..end])?,
+                    start,
+                });
+                continue;
+            }
+            (c, _) => {
+                return Err(query_error(
+                    query,
+                    start,
+                    format!("unexpected character '{}'", c),
+                ));
+            }
+        };
+
+        for _ in 0..len {
+            chars.next();
+        }
+        tokens.push(Token { kind, start });
+    }
+
+    Ok(tokens)
+}
+
+/// Parses a size such as `10MB`, `512KiB` or `100B` into bytes
+///
+/// Decimal units (`KB`, `MB`, `GB`) are powers of 1000 and binary units
+/// (`KiB`, `MiB`, `GiB`) powers of 1024. Units are case-insensitive.
+pub(crate) fn parse_size(word: &str) -> Option<i64> {
+    let split = word.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = word.split_at(split);
+    let multiplier: i64 = match unit.to_ascii_lowercase().as_str() {
+        "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000 * 1000,
+        "gb" => 1000 * 1000 * 1000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return None,
+    };
+    number.parse::<i64>().ok()?.checked_mul(multiplier)
+}
+
+/// Classifies a bare word as a keyword, number, size, duration or identifier
+fn word_token(query: &str, start: usize, word: &str) -> Result<TokenKind> {
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        if let Ok(n) = word.parse() {
+            return Ok(TokenKind::Number(n));
+        }
+        if let Some(bytes) = parse_size(word) {
+            return Ok(TokenKind::Number(bytes));
+        }
+        return humantime::parse_duration(word)
+            .map(TokenKind::Duration)
+            .map_err(|_| {
+                query_error(
+                    query,
+                    start,
+                    format!("invalid number, size or duration '{}'", word),
+                )
+            });
+    }
+
+    Ok(match word.to_ascii_lowercase().as_str() {
+        "and" => TokenKind::And,
+        "or" => TokenKind::Or,
+        "not" => TokenKind::Not,
+        _ => TokenKind::Word(word.to_string()),
+    })
+}
+
+/// Recursive-descent parser over the token stream
+struct Parser<'a> {
+    query: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek().is_some_and(|t| &t.kind == kind) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error_at(&self, position: usize, message: impl Into<String>) -> Error {
+        query_error(self.query, position, message)
+    }
+
+    fn error_at_end(&self, message: impl Into<String>) -> Error {
+        self.error_at(self.query.len(), message)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut left = self.parse_and()?;
+        while self.eat(&TokenKind::Or) {
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut left = self.parse_unary()?;
+        while self.eat(&TokenKind::And) {
+            let right = self.parse_unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.eat(&TokenKind::Not) {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        let Some(token) = self.next() else {
+            return Err(self.error_at_end("expected a field or '('"));
+        };
+
+        match token.kind {
+            TokenKind::LParen => {
+                let expr = self.parse_or()?;
+                if !self.eat(&TokenKind::RParen) {
+                    let position = self.peek().map_or(self.query.len(), |t| t.start);
+                    return Err(self.error_at(position, "expected ')'"));
+                }
+                Ok(expr)
+            }
+            TokenKind::Word(name) => {
+                let field = Field::from_name(&name).ok_or_else(|| {
+                    let names: Vec<_> = Field::ALL.iter().map(|f| f.name()).collect();
+                    self.error_at(
+                        token.start,
+                        format!(
+                            "unknown field '{}' (expected one of: {})",
+                            name,
+                            names.join(", ")
+                        ),
+                    )
+                })?;
+                self.parse_comparison(field)
+            }
+            _ => Err(self.error_at(token.start, "expected a field or '('")),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: Field) -> Result<Expr> {
+        let op_token = match self.peek() {
+            Some(t) if matches!(t.kind, TokenKind::Cmp(_) | TokenKind::Match(_)) => self.next(),
+            _ => return Ok(Expr::Truthy(field)),
+        };
+        let op_token = op_token.expect("peeked token");
+
+        let Some(value_token) = self.next() else {
+            return Err(self.error_at_end(format!("expected a value after '{}'", field)));
+        };
+        let value = match value_token.kind {
+            TokenKind::Str(s) => Value::Text(s),
+            TokenKind::Number(n) => Value::Number(n),
+            TokenKind::Duration(d) => Value::Duration(d),
+            TokenKind::Word(w) => match w.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => Value::Text(w),
+            },
+            _ => return Err(self.error_at(value_token.start, "expected a value")),
+        };
+
+        match op_token.kind {
+            TokenKind::Match(negate) => {
+                let Value::Text(pattern) = value else {
+                    return Err(self.error_at(value_token.start, "expected a regex string"));
+                };
+                if field.kind() != Kind::Text {
+                    return Err(self.error_at(
+                        op_token.start,
+                        format!(
+                            "regex match is only supported on text fields, not '{}'",
+                            field
+                        ),
+                    ));
+                }
+                let regex = Regex::new(&pattern).map_err(|e| {
+                    self.error_at(value_token.start, format!("invalid regex: {}", e))
+                })?;
+                Ok(Expr::Matches(field, regex, negate))
+            }
+            TokenKind::Cmp(op) => {
+                if value.kind() != field.kind() {
+                    let expected = match field.kind() {
+                        Kind::Text => "a string",
+                        Kind::Number => "a number",
+                        Kind::Duration => "a duration like 30d or 12h",
+                        Kind::Bool => "true or false",
+                    };
+                    return Err(self.error_at(
+                        value_token.start,
+                        format!("field '{}' must be compared with {}", field, expected),
+                    ));
+                }
+                let ordered = matches!(field.kind(), Kind::Number | Kind::Duration);
+                if !ordered && !matches!(op, CmpOp::Eq | CmpOp::Ne) {
+                    return Err(self.error_at(
+                        op_token.start,
+                        format!("operator '{}' is not supported on '{}'", op.symbol(), field),
+                    ));
+                }
+                Ok(Expr::Compare(field, op, value))
+            }
+            _ => unreachable!("operator token checked above"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{LastCommit, RepoInfo};
+    use crate::default_branch::DefaultBranch;
+    use crate::diffstat::DiffStats;
+    use crate::remote::Remote;
+    use std::path::PathBuf;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1000 * DAY)
+    }
+
+    fn dirty(branch: &str, ahead: usize, age_days: u64) -> RepoStatus {
+        RepoStatus::Dirty {
+            path: PathBuf::from("/work/api"),
+            changes: " M src/lib.rs\n?? notes.txt\n".to_string(),
+            diff: DiffStats::default(),
+            conflicts: Vec::new(),
+            branch: Some(branch.to_string()),
+            info: RepoInfo {
+                ahead: Some(ahead),
+                behind: Some(0),
+                last_commit: Some(LastCommit::at(((1000 - age_days) * DAY) as i64)),
+                ..RepoInfo::default()
+            },
+        }
+    }
+
+    fn clean(ahead: Option<usize>) -> RepoStatus {
+        RepoStatus::Clean {
+            path: PathBuf::from("/oss/tool"),
+            branch: Some("main".to_string()),
+            info: RepoInfo {
+                ahead,
+                ..RepoInfo::default()
+            },
+        }
+    }
+
+    fn check(query: &str, status: &RepoStatus) -> bool {
+        Filter::parse(query).unwrap().matches_at(status, now())
+    }
+
+    #[test]
+    fn test_boolean_operators() {
+        let query = "(dirty OR ahead) AND NOT branch == main AND age > 30d";
+        assert!(check(query, &dirty("feature", 0, 45)));
+        assert!(!check(query, &dirty("main", 0, 45)));
+        assert!(!check(query, &dirty("feature", 0, 10)));
+        assert!(!check(query, &clean(Some(0))));
+        assert!(check("dirty || ahead", &clean(Some(3))));
+        assert!(check("!clean && changes == 2", &dirty("x", 0, 1)));
+    }
+
+    #[test]
+    fn test_comparisons_and_missing_values() {
+        assert!(check("ahead >= 2", &dirty("x", 2, 1)));
+        assert!(!check("ahead > 0", &clean(None)));
+        assert!(check("behind != 1", &clean(None)));
+        assert!(check("status == 'clean'", &clean(None)));
+        assert!(check("name == tool and path =~ '^/oss/'", &clean(None)));
+    }
+
+    #[test]
+    fn test_stash_detached_and_untracked_fields() {
+        let mut status = dirty("x", 0, 1);
+        if let RepoStatus::Dirty { info, .. } = &mut status {
+            info.head = Some(HeadState::Detached {
+                commit: "1a2b3c4".to_string(),
+                tag: None,
+            });
+            info.stashes = 2;
+            info.oldest_stash = Some((990 * DAY) as i64);
+            info.largest_untracked = Some(20_000_000);
+        }
+        assert!(check("detached and stashes == 2 and not unborn", &status));
+        assert!(check("stash_age > 7d", &status));
+        assert!(check("largest_untracked > 10MB", &status));
+        assert!(!check("largest_untracked > 1GiB", &status));
+        assert!(!check("stash_age > 1d", &clean(None)));
+    }
+
+    #[test]
+    fn test_remote_fields() {
+        let remote = |name: &str, fetch: &str, push: &str| Remote {
+            name: name.to_string(),
+            fetch: fetch.to_string(),
+            push: push.to_string(),
+        };
+        let status = RepoStatus::Clean {
+            path: PathBuf::from("/work/api"),
+            branch: None,
+            info: RepoInfo {
+                remotes: vec![
+                    remote(
+                        "fork",
+                        "git@github.com:me/api.git",
+                        "git@github.com:me/api.git",
+                    ),
+                    remote(
+                        "origin",
+                        "git@github.com:Acme/api.git",
+                        "https://github.com/Acme/api.git",
+                    ),
+                ],
+                ..RepoInfo::default()
+            },
+        };
+        assert!(check(
+            "host == \"github.com\" and owner == \"Acme\"",
+            &status
+        ));
+        assert!(check("remotes == 2 and https_push", &status));
+        assert!(check("not remotes", &clean(None)));
+        assert!(!check("https_push", &clean(None)));
+
+        let owner = Filter::remote(Field::Owner, "acme");
+        assert!(owner.matches_at(&status, now()));
+        assert!(!Filter::remote(Field::Host, "gitlab.com").matches_at(&status, now()));
+        assert!(!owner.matches_at(&clean(None), now()));
+    }
+
+    #[test]
+    fn test_bare_fields() {
+        let status = RepoStatus::Clean {
+            path: PathBuf::from("/srv/git/tools.git"),
+            branch: None,
+            info: RepoInfo {
+                kind: RepoKind::Bare,
+                missing_upstream: vec!["wip".to_string()],
+                ..RepoInfo::default()
+            },
+        };
+        assert!(check("bare and missing_upstream == 1", &status));
+        assert!(!check("bare or missing_upstream", &clean(None)));
+    }
+
+    #[test]
+    fn test_lfs_fields() {
+        let mut status = clean(None);
+        if let RepoStatus::Clean { info, .. } = &mut status {
+            info.lfs = Some(LfsStatus {
+                plain: vec![PathBuf::from("assets/logo.png")],
+                ..LfsStatus::default()
+            });
+        }
+        assert!(check(
+            "lfs and lfs_plain == 1 and lfs_missing == 0",
+            &status
+        ));
+        assert!(!check("lfs or lfs_plain == 0", &clean(None)));
+    }
+
+    #[test]
+    fn test_housekeeping_fields() {
+        let mut status = clean(None);
+        if let RepoStatus::Clean { info, .. } = &mut status {
+            info.housekeeping = Some(Housekeeping {
+                git_size: 200_000_000,
+                packs: 60,
+                needs_gc: true,
+                shallow: true,
+                ..Housekeeping::default()
+            });
+        }
+        assert!(check(
+            "git_size > 100MB and packs > 50 and needs_gc",
+            &status
+        ));
+        assert!(check("shallow and not sparse", &status));
+        assert!(!check("needs_gc or git_size > 0", &clean(None)));
+    }
+
+    #[test]
+    fn test_diff_fields() {
+        let mut status = dirty("x", 0, 1);
+        if let RepoStatus::Dirty { diff, .. } = &mut status {
+            diff.staged.added = 100;
+            diff.unstaged.added = 20;
+            diff.unstaged.removed = 34;
+        }
+        assert!(check(
+            "added == 120 and removed > 30 and not conflicts",
+            &status
+        ));
+        assert!(!check("added or removed", &clean(None)));
+    }
+
+    #[test]
+    fn test_default_branch_fields() {
+        let mut status = dirty("feature", 0, 1);
+        status.info_mut().default_branch = Some(DefaultBranch {
+            name: "origin/main".to_string(),
+            ahead: 0,
+            behind: 7,
+            merged: true,
+        });
+        assert!(check("merged and default_behind > 5", &status));
+        assert!(Filter::merged().matches_at(&status, now()));
+
+        let default = status.info_mut().default_branch.as_mut().unwrap();
+        default.ahead = 3;
+        default.merged = false;
+        assert!(check("default_ahead == 3 and not merged", &status));
+        assert!(!Filter::merged().matches_at(&status, now()));
+        assert!(!Filter::merged().matches_at(&clean(None), now()));
+    }
+
+    #[test]
+    fn test_stale_and_active() {
+        let old = dirty("main", 0, 100);
+        assert!(Filter::stale(Duration::from_secs(90 * DAY)).matches_at(&old, now()));
+        assert!(!Filter::active(Duration::from_secs(90 * DAY)).matches_at(&old, now()));
+        let both = Filter::active(Duration::from_secs(200 * DAY))
+            .and(Filter::stale(Duration::from_secs(50 * DAY)));
+        assert!(both.matches_at(&old, now()));
+        assert!(!Filter::stale(Duration::ZERO).matches_at(&clean(None), now()));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("10MB"), Some(10_000_000));
+        assert_eq!(parse_size("2KiB"), Some(2048));
+        assert_eq!(parse_size("100b"), Some(100));
+        assert_eq!(parse_size("10m"), None);
+        assert_eq!(parse_size("MB"), None);
+    }
+
+    #[test]
+    fn test_regex_matching() {
+        assert!(check(r#"branch =~ "^feat/""#, &dirty("feat/login", 0, 1)));
+        assert!(check(r#"branch !~ "^feat/""#, &dirty("fix/login", 0, 1)));
+    }
+
+    #[test]
+    fn test_uses_field() {
+        let filter = Filter::parse("dirty and branch == main").unwrap();
+        assert!(filter.uses(Field::Branch));
+        assert!(!filter.uses(Field::Age));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("dirty and", "expected a field"),
+            ("colour == red", "unknown field 'colour'"),
+            ("(dirty", "expected ')'"),
+            ("age > 30", "must be compared with a duration"),
+            ("branch < main", "operator '<' is not supported"),
+            ("ahead =~ '1'", "only supported on text fields"),
+            ("branch =~ '('", "invalid regex"),
+            ("branch == \"main", "unterminated string"),
+            ("dirty clean", "unexpected token"),
+        ];
+        for (query, expected) in cases {
+            let err = Filter::parse(query).unwrap_err().to_string();
+            assert!(err.contains(expected), "{query:?} gave {err:?}");
+        }
+    }
+
+    #[test]
+    fn test_error_position() {
+        match Filter::parse("dirty and colour") {
+            Err(Error::InvalidQuery { position, .. }) => assert_eq!(position, 10),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+}
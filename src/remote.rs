@@ -0,0 +1,203 @@
+//! Git remotes and remote URL parsing
+//!
+//! This module reads the remotes of a repository and extracts the host and
+//! owner from their URLs. URLs may use the `scheme://[user@]host[:port]/path`
+//! form (HTTPS, SSH, git) or the scp-like `[user@]host:path` form.
+
+use crate::core;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+/// A configured remote
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Remote {
+    pub name: String,
+    /// URL fetched from
+    pub fetch: String,
+    /// URL pushed to, which is the fetch URL unless `pushurl` is set
+    pub push: String,
+}
+
+impl Remote {
+    /// Returns true if pushes go over HTTP(S) rather than SSH
+    pub fn pushes_over_https(&self) -> bool {
+        scheme(&self.push).is_some_and(|s| s == "https" || s == "http")
+    }
+}
+
+/// Picks the remote used for display and grouping: `origin`, or else the
+/// first remote
+pub fn primary(remotes: &[Remote]) -> Option<&Remote> {
+    remotes
+        .iter()
+        .find(|r| r.name == "origin")
+        .or_else(|| remotes.first())
+}
+
+/// Splits a URL into its scheme (if any), authority and path
+///
+/// Returns `None` for local paths.
+fn split_url(url: &str) -> Option<(Option<&str>, &str, &str)> {
+    if let Some((scheme, rest)) = url.split_once("://") {
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        Some((Some(scheme), authority, path))
+    } else {
+        let (authority, path) = url.split_once(':')?;
+        if authority.contains('/') {
+            return None;
+        }
+        Some((None, authority, path))
+    }
+}
+
+/// Gets the lowercase scheme of a URL, or `None` for scp-like and local URLs
+fn scheme(url: &str) -> Option<String> {
+    split_url(url).and_then(|(scheme, _, _)| scheme.map(str::to_lowercase))
+}
+
+/// Extracts the host from a remote URL
+pub fn host(url: &str) -> Option<String> {
+    let (scheme, authority, _) = split_url(url)?;
+    let host = authority.rsplit('@').next()?;
+    // scp-like URLs have no port, so only strip one from scheme URLs
+    let host = match scheme {
+        Some(_) => host.split(':').next()?,
+        None => host,
+    };
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// Extracts the owner (user or organization) from a remote URL
+///
+/// This is the first path segment, e.g. `kjanat` in
+/// `git@github.com:kjanat/tools.git`.
+pub fn owner(url: &str) -> Option<String> {
+    let (_, _, path) = split_url(url)?;
+    let mut segments = path.trim_start_matches('/').split('/');
+    let owner = segments.next()?.trim_start_matches('~');
+    // A lone segment is the repository itself
+    segments.next()?;
+    (!owner.is_empty()).then(|| owner.to_string())
+}
+
+/// Parses `git remote -v` output
+///
+/// Lines look like `<name>\t<url> (fetch)` or `<name>\t<url> (push)`.
+/// Remotes keep the order in which git lists them.
+fn parse_remotes(output: &str) -> Vec<Remote> {
+    let mut remotes: Vec<Remote> = Vec::new();
+    for line in output.lines() {
+        let Some((name, rest)) = line.split_once('\t') else {
+            continue;
+        };
+        let Some((url, kind)) = rest.rsplit_once(' ') else {
+            continue;
+        };
+        let index = match remotes.iter().position(|r| r.name == name) {
+            Some(index) => index,
+            None => {
+                remotes.push(Remote {
+                    name: name.to_string(),
+                    fetch: String::new(),
+                    push: String::new(),
+                });
+                remotes.len() - 1
+            }
+        };
+        match kind {
+            "(fetch)" => remotes[index].fetch = url.to_string(),
+            "(push)" => remotes[index].push = url.to_string(),
+            _ => {}
+        }
+    }
+    for remote in &mut remotes {
+        if remote.push.is_empty() {
+            remote.push = remote.fetch.clone();
+        }
+    }
+    remotes
+}
+
+/// Gets the remotes of a repository, with `insteadOf` rewrites applied
+pub fn get_remotes(repo_path: &Path) -> Vec<Remote> {
+    let output = core::run_git(
+        Command::new("git")
+            .arg("-C")
+            .arg(repo_path)
+            .arg("remote")
+            .arg("-v"),
+        None,
+    );
+
+    match output {
+        Ok(Some(output)) if output.status.success() => {
+            parse_remotes(&String::from_utf8_lossy(&output.stdout))
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_and_owner() {
+        let cases = [
+            (
+                "https://github.com/kjanat/tools.git",
+                Some("github.com"),
+                Some("kjanat"),
+            ),
+            (
+                "ssh://git@GitLab.example.com:2222/group/sub/b.git",
+                Some("gitlab.example.com"),
+                Some("group"),
+            ),
+            (
+                "git@github.com:kjanat/tools.git",
+                Some("github.com"),
+                Some("kjanat"),
+            ),
+            ("git@example.com:tools.git", Some("example.com"), None),
+            ("ssh://host/~alice/tools.git", Some("host"), Some("alice")),
+            ("/srv/git/tools.git", None, None),
+            ("../tools", None, None),
+        ];
+        for (url, expected_host, expected_owner) in cases {
+            assert_eq!(host(url).as_deref(), expected_host, "{url}");
+            assert_eq!(owner(url).as_deref(), expected_owner, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_parse_remotes() {
+        let output = "origin\tgit@github.com:me/tools.git (fetch)\n\
+                      origin\thttps://github.com/me/tools.git (push)\n\
+                      upstream\thttps://github.com/org/tools.git (fetch)\n\
+                      upstream\thttps://github.com/org/tools.git (push)\n";
+        let remotes = parse_remotes(output);
+        assert_eq!(remotes.len(), 2);
+        assert_eq!(remotes[0].name, "origin");
+        assert_eq!(remotes[0].fetch, "git@github.com:me/tools.git");
+        assert!(remotes[0].pushes_over_https());
+        assert_eq!(remotes[1].name, "upstream");
+        assert_eq!(primary(&remotes).unwrap().name, "origin");
+        assert_eq!(primary(&remotes[1..]).unwrap().name, "upstream");
+        assert!(parse_remotes("").is_empty());
+    }
+
+    #[test]
+    fn test_pushes_over_https() {
+        let remote = |push: &str| Remote {
+            name: "origin".to_string(),
+            fetch: push.to_string(),
+            push: push.to_string(),
+        };
+        assert!(remote("HTTPS://example.com/a/b").pushes_over_https());
+        assert!(!remote("git@example.com:a/b").pushes_over_https());
+        assert!(!remote("ssh://git@example.com/a/b").pushes_over_https());
+        assert!(!remote("/srv/git/b").pushes_over_https());
+    }
+}
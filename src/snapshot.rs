@@ -0,0 +1,563 @@
+//! Snapshots of scan results and diffs between them
+//!
+//! This module saves the full results of a run to a file, keeps an
+//! automatic snapshot of the last run per root, and compares two snapshots
+//! to report added and removed repositories, status transitions, branch
+//! switches, new commits and ahead/behind changes.
+
+use crate::core::{HeadState, LastCommit, RepoStatus};
+use crate::error::{Error, Result};
+use crate::output::{self, PathStyle};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Snapshot file format version
+const FORMAT_VERSION: u32 = 2;
+
+/// The full results of one run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Unix timestamp of the run
+    pub created: i64,
+    /// Scan root
+    pub root: PathBuf,
+    pub repositories: Vec<RepoStatus>,
+}
+
+impl Snapshot {
+    /// Creates a snapshot of the given results, taken now
+    pub fn new(root: &Path, statuses: &[RepoStatus]) -> Snapshot {
+        Snapshot {
+            version: FORMAT_VERSION,
+            created: output::unix_now(),
+            root: root.to_path_buf(),
+            repositories: statuses.to_vec(),
+        }
+    }
+
+    /// Reads a snapshot file
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Snapshot` if the file can't be read or isn't a
+    /// snapshot of a supported version
+    pub fn load(path: &Path) -> Result<Snapshot> {
+        let invalid = |message: String| Error::Snapshot {
+            path: path.to_path_buf(),
+            message,
+        };
+        let data = fs::read(path).map_err(|e| invalid(e.to_string()))?;
+        let snapshot: Snapshot =
+            serde_json::from_slice(&data).map_err(|e| invalid(e.to_string()))?;
+        if snapshot.version != FORMAT_VERSION {
+            return Err(invalid(format!("unsupported version {}", snapshot.version)));
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the snapshot as JSON
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file can't be written
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(self).map_err(std::io::Error::other)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Keeps only the repositories accepted by a predicate
+    pub fn retain(&mut self, keep: impl FnMut(&RepoStatus) -> bool) {
+        self.repositories.retain(keep);
+    }
+}
+
+/// Automatic snapshots older than this are deleted
+const MAX_SNAPSHOT_AGE: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Most automatic snapshots kept, newest first
+const MAX_SNAPSHOTS: usize = 100;
+
+/// Hashes a path with 64-bit FNV-1a, which unlike `DefaultHasher` is stable
+/// across Rust releases
+fn path_hash(path: &Path) -> u64 {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Gets the directory holding the automatic snapshots
+fn snapshot_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("check-git-status").join("snapshots"))
+}
+
+/// Gets the location of the automatic snapshot for a scan root and depth
+///
+/// The file is keyed on a hash of the root, prefixed with its directory
+/// name to make it recognizable, and on the depth, since a deeper scan
+/// finds other repositories.
+pub fn last_snapshot_path(root: &Path, max_depth: usize) -> Option<PathBuf> {
+    let name: String = root
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    snapshot_dir().map(|dir| {
+        dir.join(format!(
+            "{}-{:016x}-{}.json",
+            name,
+            path_hash(root),
+            max_depth
+        ))
+    })
+}
+
+/// Deletes automatic snapshots that are too old or too many
+///
+/// Errors are ignored, since a leftover snapshot does no harm.
+pub fn prune_snapshots() {
+    let Some(entries) = snapshot_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return;
+    };
+    let mut snapshots: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|p| Some((fs::metadata(&p).ok()?.modified().ok()?, p)))
+        .collect();
+    snapshots.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    let now = SystemTime::now();
+    for (i, (modified, path)) in snapshots.iter().enumerate() {
+        let age = now.duration_since(*modified).unwrap_or_default();
+        if i >= MAX_SNAPSHOTS || age > MAX_SNAPSHOT_AGE {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// An old and a new value of a field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Transition<T> {
+    pub old: T,
+    pub new: T,
+}
+
+/// Returns the transition between two values if they differ
+fn transition<T: PartialEq>(old: T, new: T) -> Option<Transition<T>> {
+    (old != new).then_some(Transition { old, new })
+}
+
+/// Fields of a repository that changed between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoChange {
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Transition<&'static str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<Transition<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ahead: Option<Transition<Option<usize>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub behind: Option<Transition<Option<usize>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_commit: Option<Transition<Option<LastCommit>>>,
+    /// Current status, used for display names
+    #[serde(skip)]
+    current: RepoStatus,
+}
+
+impl RepoChange {
+    /// Compares two results for the same repository
+    ///
+    /// Heads are only compared when both snapshots recorded one, so a
+    /// switch to or from a detached HEAD counts as a branch switch.
+    fn between(old: &RepoStatus, new: &RepoStatus) -> Option<RepoChange> {
+        let state = |s: &RepoStatus| if s.is_dirty() { "dirty" } else { "clean" };
+        let (oi, ni) = (old.info(), new.info());
+        let change = RepoChange {
+            path: new.path().to_path_buf(),
+            status: transition(state(old), state(new)),
+            branch: oi
+                .head
+                .as_ref()
+                .zip(ni.head.as_ref())
+                .and_then(|(o, n)| transition(head_name(o), head_name(n))),
+            ahead: transition(oi.ahead, ni.ahead),
+            behind: transition(oi.behind, ni.behind),
+            last_commit: transition(oi.last_commit.clone(), ni.last_commit.clone()),
+            current: new.clone(),
+        };
+
+        let changed = change.status.is_some()
+            || change.branch.is_some()
+            || change.ahead.is_some()
+            || change.behind.is_some()
+            || change.last_commit.is_some();
+        changed.then_some(change)
+    }
+}
+
+/// Names what HEAD points at, without the commit of a detached HEAD, whose
+/// moves show up as new commits instead
+fn head_name(head: &HeadState) -> String {
+    match head {
+        HeadState::Branch { name } | HeadState::Unborn { name } => name.clone(),
+        HeadState::Detached { .. } => "detached HEAD".to_string(),
+    }
+}
+
+/// Differences between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnapshotDiff {
+    /// Unix timestamp of the old snapshot
+    pub since: i64,
+    /// Unix timestamp of the new snapshot
+    pub until: i64,
+    pub added: Vec<RepoStatus>,
+    pub removed: Vec<RepoStatus>,
+    pub changed: Vec<RepoChange>,
+}
+
+impl SnapshotDiff {
+    /// Compares two snapshots, matching repositories by path
+    pub fn between(old: &Snapshot, new: &Snapshot) -> SnapshotDiff {
+        let old_repos: BTreeMap<&Path, &RepoStatus> =
+            old.repositories.iter().map(|s| (s.path(), s)).collect();
+        let new_repos: BTreeMap<&Path, &RepoStatus> =
+            new.repositories.iter().map(|s| (s.path(), s)).collect();
+
+        let mut diff = SnapshotDiff {
+            since: old.created,
+            until: new.created,
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
+        for (path, status) in &new_repos {
+            match old_repos.get(path) {
+                Some(previous) => diff.changed.extend(RepoChange::between(previous, status)),
+                None => diff.added.push((*status).clone()),
+            }
+        }
+        diff.removed = old_repos
+            .iter()
+            .filter(|(path, _)| !new_repos.contains_key(*path))
+            .map(|(_, status)| (*status).clone())
+            .collect();
+        diff
+    }
+
+    /// Total number of added, removed and changed repositories
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Formats an optional count, using `-` when it's missing
+fn format_count(count: Option<usize>) -> String {
+    count.map_or_else(|| "-".to_string(), |c| c.to_string())
+}
+
+/// Describes the changed fields of a repository
+fn describe(change: &RepoChange) -> Vec<String> {
+    let arrow = output::symbols().arrow;
+    let mut parts = Vec::new();
+
+    if let Some(t) = &change.status {
+        let new = if t.new == "dirty" {
+            t.new.yellow()
+        } else {
+            t.new.green()
+        };
+        parts.push(format!("{} {} {}", t.old, arrow, new));
+    }
+    if let Some(t) = &change.branch {
+        parts.push(format!(
+            "branch {} {} {}",
+            t.old,
+            arrow,
+            t.new.bright_cyan()
+        ));
+    }
+    if let Some(t) = &change.last_commit {
+        parts.push(match (&t.old, &t.new) {
+            (Some(old), Some(new)) if new.timestamp > old.timestamp => "new commits".to_string(),
+            (None, Some(_)) => "first commit".to_string(),
+            _ => "last commit changed".to_string(),
+        });
+    }
+    if let Some(t) = &change.ahead {
+        parts.push(format!(
+            "ahead {} {} {}",
+            format_count(t.old),
+            arrow,
+            format_count(t.new)
+        ));
+    }
+    if let Some(t) = &change.behind {
+        parts.push(format!(
+            "behind {} {} {}",
+            format_count(t.old),
+            arrow,
+            format_count(t.new)
+        ));
+    }
+    parts
+}
+
+/// Renders the diff as human-readable lines
+pub fn render(diff: &SnapshotDiff, root: &Path, style: PathStyle) -> Vec<String> {
+    let all: Vec<RepoStatus> = diff
+        .added
+        .iter()
+        .chain(&diff.removed)
+        .chain(diff.changed.iter().map(|c| &c.current))
+        .cloned()
+        .collect();
+    let names = output::display_names(&all, root, style);
+
+    let mut lines = vec![format!(
+        "{} Changes since {}",
+        output::symbols().search.cyan(),
+        output::format_time(diff.since).bright_blue()
+    )];
+    if diff.is_empty() {
+        lines.push("No changes".green().to_string());
+        return lines;
+    }
+
+    for status in &diff.added {
+        let state = if status.is_dirty() { "dirty" } else { "clean" };
+        lines.push(format!(
+            "{} {} (new, {})",
+            "+".green().bold(),
+            names[status.path()].green(),
+            state
+        ));
+    }
+    for status in &diff.removed {
+        lines.push(format!(
+            "{} {} (removed)",
+            "-".red().bold(),
+            names[status.path()].red()
+        ));
+    }
+    for change in &diff.changed {
+        lines.push(format!(
+            "{} {}: {}",
+            "~".yellow().bold(),
+            names[change.path.as_path()].bold(),
+            describe(change).join(", ")
+        ));
+    }
+    lines
+}
+
+/// Prints the diff in human-readable form
+pub fn print_diff(diff: &SnapshotDiff, root: &Path, style: PathStyle) {
+    for line in render(diff, root, style) {
+        eprintln!("{}", line);
+    }
+    eprintln!();
+    eprintln!(
+        "{} added, {} removed, {} changed",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    );
+}
+
+/// Prints the diff as JSON
+pub fn print_diff_json(diff: &SnapshotDiff) -> std::result::Result<(), serde_json::Error> {
+    println!("{}", serde_json::to_string_pretty(diff)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::RepoInfo;
+    use crate::diffstat::DiffStats;
+
+    fn repo(path: &str, dirty: bool, branch: &str, mut info: RepoInfo) -> RepoStatus {
+        let path = PathBuf::from(path);
+        info.head.get_or_insert(HeadState::Branch {
+            name: branch.to_string(),
+        });
+        let branch = Some(branch.to_string());
+        if dirty {
+            RepoStatus::Dirty {
+                path,
+                changes: " M a\n".to_string(),
+                diff: DiffStats::default(),
+                conflicts: Vec::new(),
+                branch,
+                info,
+            }
+        } else {
+            RepoStatus::Clean { path, branch, info }
+        }
+    }
+
+    fn snapshot(created: i64, repositories: Vec<RepoStatus>) -> Snapshot {
+        Snapshot {
+            version: FORMAT_VERSION,
+            created,
+            root: PathBuf::from("/p"),
+            repositories,
+        }
+    }
+
+    fn sample() -> (Snapshot, Snapshot) {
+        let old = snapshot(
+            0,
+            vec![
+                repo("/p/api", false, "main", RepoInfo::default()),
+                repo("/p/gone", false, "main", RepoInfo::default()),
+                repo(
+                    "/p/web",
+                    false,
+                    "main",
+                    RepoInfo {
+                        ahead: Some(0),
+                        last_commit: Some(LastCommit::at(100)),
+                        ..RepoInfo::default()
+                    },
+                ),
+            ],
+        );
+        let new = snapshot(
+            86400,
+            vec![
+                repo("/p/api", false, "main", RepoInfo::default()),
+                repo("/p/new", true, "main", RepoInfo::default()),
+                repo(
+                    "/p/web",
+                    true,
+                    "dev",
+                    RepoInfo {
+                        ahead: Some(2),
+                        last_commit: Some(LastCommit::at(200)),
+                        ..RepoInfo::default()
+                    },
+                ),
+            ],
+        );
+        (old, new)
+    }
+
+    #[test]
+    fn test_diff_between_snapshots() {
+        let (old, new) = sample();
+        let diff = SnapshotDiff::between(&old, &new);
+
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].path(), Path::new("/p/new"));
+        assert_eq!(diff.removed[0].path(), Path::new("/p/gone"));
+        assert_eq!(diff.changed.len(), 1);
+
+        let change = &diff.changed[0];
+        assert_eq!(
+            change.status,
+            Some(Transition {
+                old: "clean",
+                new: "dirty"
+            })
+        );
+        assert_eq!(change.branch.as_ref().unwrap().new, "dev");
+        assert_eq!(
+            change.ahead,
+            Some(Transition {
+                old: Some(0),
+                new: Some(2)
+            })
+        );
+        assert_eq!(change.behind, None);
+    }
+
+    #[test]
+    fn test_identical_snapshots_have_no_diff() {
+        let (old, _) = sample();
+        assert!(SnapshotDiff::between(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_detached_head_is_a_branch_switch() {
+        let detached = RepoInfo {
+            head: Some(HeadState::Detached {
+                commit: "1a2b3c4".to_string(),
+                tag: None,
+            }),
+            ..RepoInfo::default()
+        };
+        let old = repo("/p/api", false, "main", RepoInfo::default());
+        let new = repo("/p/api", false, "main", detached);
+        let change = RepoChange::between(&old, &new).unwrap();
+        assert_eq!(change.branch.unwrap().new, "detached HEAD");
+        assert!(RepoChange::between(&new, &old).unwrap().branch.is_some());
+    }
+
+    #[test]
+    fn test_last_snapshot_path() {
+        let path = |root: &str| last_snapshot_path(Path::new(root), 3).unwrap();
+        assert_ne!(path("/a-b"), path("/a_b"));
+        assert_ne!(
+            path("/a/b"),
+            last_snapshot_path(Path::new("/a/b"), 4).unwrap()
+        );
+        assert_ne!(path("/a/b"), path("/a_b"));
+        assert_eq!(path("/a/b"), path("/a/b"));
+        assert!(
+            path("/work/my-repos")
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("my_repos-")
+        );
+    }
+
+    #[test]
+    fn test_render_diff() {
+        colored::control::set_override(false);
+        let (old, new) = sample();
+        let diff = SnapshotDiff::between(&old, &new);
+        let lines = render(&diff, Path::new("/p"), PathStyle::Relative);
+        assert_eq!(
+            &lines[1..],
+            [
+                "+ new (new, dirty)",
+                "- gone (removed)",
+                "~ web: clean → dirty, branch main → dev, new commits, ahead 0 → 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("snap/today.json");
+        let (old, _) = sample();
+        old.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), old);
+
+        fs::write(&path, "{}").unwrap();
+        assert!(matches!(Snapshot::load(&path), Err(Error::Snapshot { .. })));
+    }
+}
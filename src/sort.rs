@@ -0,0 +1,341 @@
+//! Sorting and grouping of repository results
+//!
+//! This module orders the checked repositories by a user-selected key and
+//! partitions them into labelled groups for display.
+
+use crate::core::{LastCommit, RepoStatus};
+use crate::remote;
+use clap::ValueEnum;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Keys that results can be sorted by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// Repository path (A-Z)
+    #[default]
+    Path,
+    /// Repository directory name (A-Z)
+    Name,
+    /// Dirty repos first
+    Status,
+    /// Branch name (A-Z)
+    Branch,
+    /// Most recent commit first
+    LastCommit,
+    /// Most changed entries first
+    ChangeCount,
+    /// Most lines added and removed first
+    DiffSize,
+    /// Most commits ahead of upstream first
+    Ahead,
+    /// Most commits behind upstream first
+    Behind,
+}
+
+/// Keys that results can be grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupKey {
+    /// Clean or dirty
+    Status,
+    /// Current branch
+    Branch,
+    /// Parent directory relative to the scan root
+    Parent,
+    /// Host of the origin remote
+    RemoteHost,
+    /// Host and owner of the origin remote, e.g. github.com/kjanat
+    RemoteOwner,
+}
+
+/// A labelled group of repositories
+#[derive(Debug)]
+pub struct Group<'a> {
+    pub name: String,
+    pub repos: Vec<&'a RepoStatus>,
+}
+
+impl Group<'_> {
+    pub fn dirty_count(&self) -> usize {
+        self.repos.iter().filter(|s| s.is_dirty()).count()
+    }
+}
+
+/// Orders two optional values, placing missing values last
+fn cmp_present<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Compares two repositories by the given key after putting conflicted
+/// repositories first, breaking ties by path
+fn compare(a: &RepoStatus, b: &RepoStatus, key: SortKey) -> Ordering {
+    let (ia, ib) = (a.info(), b.info());
+    // Descending keys wrap values in `Reverse` so missing values stay last
+    let primary = match key {
+        SortKey::Path => Ordering::Equal,
+        SortKey::Name => a.path().file_name().cmp(&b.path().file_name()),
+        SortKey::Status => b.is_dirty().cmp(&a.is_dirty()),
+        SortKey::Branch => cmp_present(a.branch(), b.branch()),
+        SortKey::LastCommit => {
+            let timestamp = |c: &LastCommit| Reverse(c.timestamp);
+            cmp_present(
+                ia.last_commit.as_ref().map(timestamp),
+                ib.last_commit.as_ref().map(timestamp),
+            )
+        }
+        SortKey::ChangeCount => b.change_count().cmp(&a.change_count()),
+        SortKey::DiffSize => b.diff().lines_changed().cmp(&a.diff().lines_changed()),
+        SortKey::Ahead => cmp_present(ia.ahead.map(Reverse), ib.ahead.map(Reverse)),
+        SortKey::Behind => cmp_present(ia.behind.map(Reverse), ib.behind.map(Reverse)),
+    };
+    // Repos with conflicts always come first, since someone is in the
+    // middle of a merge or rebase there
+    let conflicted = |s: &RepoStatus| !s.conflicts().is_empty();
+    conflicted(b)
+        .cmp(&conflicted(a))
+        .then(primary)
+        .then_with(|| a.path().cmp(b.path()))
+}
+
+/// Sorts repositories in place by the given key
+pub fn sort_statuses(statuses: &mut [RepoStatus], key: SortKey) {
+    statuses.sort_by(|a, b| compare(a, b, key));
+}
+
+/// Computes the group label of a repository
+fn group_name(status: &RepoStatus, key: GroupKey, root: &Path) -> Option<String> {
+    match key {
+        GroupKey::Status => Some(if status.is_dirty() { "dirty" } else { "clean" }.to_string()),
+        GroupKey::Branch => status.branch().map(str::to_string),
+        GroupKey::Parent => status
+            .path()
+            .parent()
+            .map(|parent| match parent.strip_prefix(root) {
+                Ok(rel) if rel.as_os_str().is_empty() => ".".to_string(),
+                Ok(rel) => rel.display().to_string(),
+                Err(_) => parent.display().to_string(),
+            }),
+        GroupKey::RemoteHost => {
+            remote::primary(&status.info().remotes).and_then(|r| remote::host(&r.fetch))
+        }
+        GroupKey::RemoteOwner => {
+            let url = &remote::primary(&status.info().remotes)?.fetch;
+            Some(format!("{}/{}", remote::host(url)?, remote::owner(url)?))
+        }
+    }
+}
+
+/// Label used for repositories without a value for the group key
+fn missing_label(key: GroupKey) -> &'static str {
+    match key {
+        GroupKey::Branch => "(no branch)",
+        GroupKey::RemoteHost | GroupKey::RemoteOwner => "(no remote)",
+        GroupKey::Status | GroupKey::Parent => "(unknown)",
+    }
+}
+
+/// Partitions repositories into groups ordered by label
+///
+/// Repositories keep their relative order within each group. Repositories
+/// without a value for the key are collected in a final placeholder group.
+pub fn group_statuses<'a>(
+    statuses: &'a [RepoStatus],
+    key: GroupKey,
+    root: &Path,
+) -> Vec<Group<'a>> {
+    let mut groups: BTreeMap<String, Vec<&RepoStatus>> = BTreeMap::new();
+    let mut missing = Vec::new();
+
+    for status in statuses {
+        match group_name(status, key, root) {
+            Some(name) => groups.entry(name).or_default().push(status),
+            None => missing.push(status),
+        }
+    }
+
+    let mut result: Vec<Group> = groups
+        .into_iter()
+        .map(|(name, repos)| Group { name, repos })
+        .collect();
+    if key == GroupKey::Status {
+        result.reverse();
+    }
+    if !missing.is_empty() {
+        result.push(Group {
+            name: missing_label(key).to_string(),
+            repos: missing,
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::RepoInfo;
+    use crate::diffstat::DiffStats;
+    use crate::remote::Remote;
+    use std::path::PathBuf;
+
+    fn repo(path: &str, dirty: bool, branch: Option<&str>, info: RepoInfo) -> RepoStatus {
+        let path = PathBuf::from(path);
+        let branch = branch.map(str::to_string);
+        if dirty {
+            RepoStatus::Dirty {
+                path,
+                changes: " M a\n M b\n".to_string(),
+                diff: DiffStats::default(),
+                conflicts: Vec::new(),
+                branch,
+                info,
+            }
+        } else {
+            RepoStatus::Clean { path, branch, info }
+        }
+    }
+
+    fn paths(statuses: &[RepoStatus]) -> Vec<&str> {
+        statuses
+            .iter()
+            .map(|s| s.path().to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_by_status_and_ahead() {
+        let mut statuses = vec![
+            repo("/r/c", false, None, RepoInfo::default()),
+            repo("/r/b", true, None, RepoInfo::default()),
+            repo(
+                "/r/a",
+                false,
+                None,
+                RepoInfo {
+                    ahead: Some(3),
+                    ..RepoInfo::default()
+                },
+            ),
+        ];
+
+        sort_statuses(&mut statuses, SortKey::Status);
+        assert_eq!(paths(&statuses), ["/r/b", "/r/a", "/r/c"]);
+
+        sort_statuses(&mut statuses, SortKey::Ahead);
+        assert_eq!(paths(&statuses), ["/r/a", "/r/b", "/r/c"]);
+
+        sort_statuses(&mut statuses, SortKey::Path);
+        assert_eq!(paths(&statuses), ["/r/a", "/r/b", "/r/c"]);
+    }
+
+    #[test]
+    fn test_sort_by_diff_size() {
+        let with_diff = |path: &str, added: usize, removed: usize| {
+            let mut status = repo(path, true, None, RepoInfo::default());
+            if let RepoStatus::Dirty { diff, .. } = &mut status {
+                diff.unstaged.added = added;
+                diff.staged.removed = removed;
+            }
+            status
+        };
+        let mut statuses = vec![
+            repo("/r/a", false, None, RepoInfo::default()),
+            with_diff("/r/b", 1, 0),
+            with_diff("/r/c", 120, 34),
+            with_diff("/r/d", 0, 40),
+        ];
+        sort_statuses(&mut statuses, SortKey::DiffSize);
+        assert_eq!(paths(&statuses), ["/r/c", "/r/d", "/r/b", "/r/a"]);
+    }
+
+    #[test]
+    fn test_conflicts_sort_first() {
+        let mut conflicted = repo("/r/z", true, None, RepoInfo::default());
+        if let RepoStatus::Dirty { conflicts, .. } = &mut conflicted {
+            conflicts.push(PathBuf::from("src/lib.rs"));
+        }
+        for key in [SortKey::Path, SortKey::Name, SortKey::ChangeCount] {
+            let mut statuses = vec![
+                repo("/r/a", true, None, RepoInfo::default()),
+                conflicted.clone(),
+                repo("/r/b", false, None, RepoInfo::default()),
+            ];
+            sort_statuses(&mut statuses, key);
+            assert_eq!(paths(&statuses)[0], "/r/z");
+        }
+    }
+
+    #[test]
+    fn test_sort_missing_values_last() {
+        let mut statuses = vec![
+            repo("/r/a", false, None, RepoInfo::default()),
+            repo("/r/b", false, Some("main"), RepoInfo::default()),
+            repo("/r/c", false, Some("dev"), RepoInfo::default()),
+        ];
+        sort_statuses(&mut statuses, SortKey::Branch);
+        assert_eq!(paths(&statuses), ["/r/c", "/r/b", "/r/a"]);
+    }
+
+    #[test]
+    fn test_group_by_parent_and_status() {
+        let statuses = vec![
+            repo("/root/oss/api", false, None, RepoInfo::default()),
+            repo("/root/tool", true, None, RepoInfo::default()),
+            repo("/root/work/api", true, None, RepoInfo::default()),
+        ];
+        let root = Path::new("/root");
+
+        let groups = group_statuses(&statuses, GroupKey::Parent, root);
+        let names: Vec<_> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, [".", "oss", "work"]);
+
+        let groups = group_statuses(&statuses, GroupKey::Status, root);
+        assert_eq!(groups[0].name, "dirty");
+        assert_eq!(groups[0].dirty_count(), 2);
+        assert_eq!(groups[1].name, "clean");
+    }
+
+    #[test]
+    fn test_group_by_remote_owner() {
+        let with_remote = |url: &str| RepoInfo {
+            remotes: vec![Remote {
+                name: "origin".to_string(),
+                fetch: url.to_string(),
+                push: url.to_string(),
+            }],
+            ..RepoInfo::default()
+        };
+        let statuses = vec![
+            repo(
+                "/r/a",
+                false,
+                None,
+                with_remote("git@github.com:acme/a.git"),
+            ),
+            repo(
+                "/r/b",
+                false,
+                None,
+                with_remote("https://github.com/acme/b"),
+            ),
+            repo("/r/c", false, None, with_remote("/srv/git/c.git")),
+        ];
+        let groups = group_statuses(&statuses, GroupKey::RemoteOwner, Path::new("/r"));
+        let names: Vec<_> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["github.com/acme", "(no remote)"]);
+        assert_eq!(groups[0].repos.len(), 2);
+    }
+
+    #[test]
+    fn test_group_missing_values() {
+        let statuses = vec![repo("/root/a", false, None, RepoInfo::default())];
+        let groups = group_statuses(&statuses, GroupKey::RemoteHost, Path::new("/root"));
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "(no remote)");
+    }
+}
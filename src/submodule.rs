@@ -0,0 +1,284 @@
+//! Submodule status
+//!
+//! This module lists the submodules of a superproject with whether each is
+//! initialized, checked out at the recorded commit, dirty, or at a commit
+//! that isn't on any remote branch. A superproject can look clean while its
+//! submodules hold the only copy of some work, so their state can also be
+//! rolled up into the superproject's.
+
+use crate::core::{self, RepoStatus};
+use crate::diffstat::DiffStats;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// How a submodule's checkout relates to the commit recorded in its parent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubmoduleState {
+    /// Not cloned into the work tree
+    Uninitialized,
+    /// Checked out at the recorded commit
+    Current,
+    /// Checked out at a different commit than recorded
+    Modified,
+    /// The recorded commit has merge conflicts
+    Conflict,
+}
+
+/// A submodule of a repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Submodule {
+    /// Path relative to the superproject
+    pub path: PathBuf,
+    /// Abbreviated commit checked out, or recorded if uninitialized
+    pub commit: String,
+    pub state: SubmoduleState,
+    /// Whether the submodule has uncommitted changes
+    #[serde(default, skip_serializing_if = "core::is_false")]
+    pub dirty: bool,
+    /// Whether the checked-out commit is missing from every remote branch
+    #[serde(default, skip_serializing_if = "core::is_false")]
+    pub unpushed: bool,
+}
+
+impl Submodule {
+    /// Returns true if the submodule holds work its parent doesn't account for
+    pub fn needs_attention(&self) -> bool {
+        self.dirty
+            || self.unpushed
+            || matches!(
+                self.state,
+                SubmoduleState::Modified | SubmoduleState::Conflict
+            )
+    }
+
+    /// Describes what is unusual about the submodule, e.g. `["new commits", "dirty"]`
+    pub fn problems(&self) -> Vec<&'static str> {
+        let mut problems = Vec::new();
+        match self.state {
+            SubmoduleState::Uninitialized => problems.push("uninitialized"),
+            SubmoduleState::Modified => problems.push("new commits"),
+            SubmoduleState::Conflict => problems.push("conflict"),
+            SubmoduleState::Current => {}
+        }
+        if self.dirty {
+            problems.push("dirty");
+        }
+        if self.unpushed {
+            problems.push("unpushed");
+        }
+        problems
+    }
+}
+
+/// Parses one line of `git submodule status`
+///
+/// Lines look like `<flag><sha> <path>[ (<describe>)]`, where the flag is
+/// a space, `-` (uninitialized), `+` (different commit) or `U` (conflict).
+fn parse_status_line(line: &str) -> Option<Submodule> {
+    let flag = line.chars().next()?;
+    let (sha, rest) = line[flag.len_utf8()..].split_once(' ')?;
+    let path = match rest.rfind(" (") {
+        Some(end) if rest.ends_with(')') => &rest[..end],
+        _ => rest,
+    };
+    let state = match flag {
+        ' ' => SubmoduleState::Current,
+        '-' => SubmoduleState::Uninitialized,
+        '+' => SubmoduleState::Modified,
+        'U' => SubmoduleState::Conflict,
+        _ => return None,
+    };
+    Some(Submodule {
+        path: PathBuf::from(path),
+        commit: sha.chars().take(7).collect(),
+        state,
+        dirty: false,
+        unpushed: false,
+    })
+}
+
+/// Lists the submodules of a repository, including nested ones
+///
+/// Returns an empty list for repositories without a `.gitmodules` file.
+pub fn get_submodules(repo_path: &Path) -> Vec<Submodule> {
+    if !repo_path.join(".gitmodules").is_file() {
+        return Vec::new();
+    }
+    let Some(output) = core::git_stdout(repo_path, &["submodule", "status", "--recursive"]) else {
+        return Vec::new();
+    };
+
+    let mut submodules: Vec<Submodule> = output.lines().filter_map(parse_status_line).collect();
+    for submodule in &mut submodules {
+        if submodule.state == SubmoduleState::Uninitialized {
+            continue;
+        }
+        let dir = repo_path.join(&submodule.path);
+        submodule.dirty = core::git_output(&dir, &["--no-optional-locks", "status", "--porcelain"])
+            .is_some_and(|changes| !changes.is_empty());
+        submodule.unpushed = core::git_output(
+            &dir,
+            &[
+                "for-each-ref",
+                "--count=1",
+                "--contains=HEAD",
+                "--format=%(refname)",
+                "refs/remotes",
+            ],
+        )
+        .is_some_and(|refs| refs.is_empty());
+    }
+    submodules
+}
+
+/// Marks a superproject dirty when any of its submodules needs attention
+///
+/// Each such submodule is added to the changes as a modified entry unless
+/// `git status` already reported it.
+pub fn roll_up(status: RepoStatus) -> RepoStatus {
+    let pending: Vec<String> = status
+        .info()
+        .submodules
+        .iter()
+        .filter(|s| s.needs_attention())
+        .map(|s| format!(" M {}", s.path.display()))
+        .collect();
+    if pending.is_empty() {
+        return status;
+    }
+
+    match status {
+        RepoStatus::Clean { path, branch, info } => {
+            let mut changes = String::new();
+            for line in pending {
+                changes.push_str(&line);
+                changes.push('\n');
+            }
+            RepoStatus::Dirty {
+                path,
+                changes,
+                diff: DiffStats::default(),
+                conflicts: Vec::new(),
+                branch,
+                info,
+            }
+        }
+        RepoStatus::Dirty {
+            path,
+            mut changes,
+            diff,
+            conflicts,
+            branch,
+            info,
+        } => {
+            for line in pending {
+                let path = &line[3..];
+                if !changes.lines().any(|l| l.get(3..) == Some(path)) {
+                    changes.push_str(&line);
+                    changes.push('\n');
+                }
+            }
+            RepoStatus::Dirty {
+                path,
+                changes,
+                diff,
+                conflicts,
+                branch,
+                info,
+            }
+        }
+    }
+}
+
+/// Rolls submodule state up into every superproject
+pub fn roll_up_all(statuses: Vec<RepoStatus>) -> Vec<RepoStatus> {
+    statuses.into_iter().map(roll_up).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::RepoInfo;
+
+    fn submodule(path: &str, state: SubmoduleState, dirty: bool) -> Submodule {
+        Submodule {
+            path: PathBuf::from(path),
+            commit: "1a2b3c4".to_string(),
+            state,
+            dirty,
+            unpushed: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_status_line() {
+        let line = " 1a2b3c4d5e6f lib/core (v1.2-3-g1a2b3c4)";
+        let parsed = parse_status_line(line).unwrap();
+        assert_eq!(parsed.path, Path::new("lib/core"));
+        assert_eq!(parsed.commit, "1a2b3c4");
+        assert_eq!(parsed.state, SubmoduleState::Current);
+
+        let parsed = parse_status_line("-9f8e7d6c5b4a vendor/x").unwrap();
+        assert_eq!(parsed.state, SubmoduleState::Uninitialized);
+        assert_eq!(parsed.path, Path::new("vendor/x"));
+
+        assert_eq!(
+            parse_status_line("+1a2b3c4 a (heads/main)").unwrap().state,
+            SubmoduleState::Modified
+        );
+        assert_eq!(
+            parse_status_line("U1a2b3c4 a").unwrap().state,
+            SubmoduleState::Conflict
+        );
+        assert_eq!(parse_status_line(""), None);
+    }
+
+    #[test]
+    fn test_needs_attention() {
+        assert!(!submodule("a", SubmoduleState::Current, false).needs_attention());
+        assert!(!submodule("a", SubmoduleState::Uninitialized, false).needs_attention());
+        assert!(submodule("a", SubmoduleState::Current, true).needs_attention());
+        assert!(submodule("a", SubmoduleState::Modified, false).needs_attention());
+        assert_eq!(
+            submodule("a", SubmoduleState::Modified, true).problems(),
+            ["new commits", "dirty"]
+        );
+    }
+
+    #[test]
+    fn test_roll_up() {
+        let with = |submodules: Vec<Submodule>| RepoInfo {
+            submodules,
+            ..RepoInfo::default()
+        };
+        let clean = RepoStatus::Clean {
+            path: PathBuf::from("/r/app"),
+            branch: None,
+            info: with(vec![
+                submodule("lib", SubmoduleState::Current, true),
+                submodule("ok", SubmoduleState::Current, false),
+            ]),
+        };
+        let rolled = roll_up(clean);
+        assert!(rolled.is_dirty());
+        assert_eq!(rolled.change_count(), 1);
+
+        let dirty = RepoStatus::Dirty {
+            path: PathBuf::from("/r/app"),
+            changes: " M lib\n M README.md\n".to_string(),
+            diff: DiffStats::default(),
+            conflicts: Vec::new(),
+            branch: None,
+            info: with(vec![submodule("lib", SubmoduleState::Modified, false)]),
+        };
+        assert_eq!(roll_up(dirty).change_count(), 2);
+
+        let untouched = RepoStatus::Clean {
+            path: PathBuf::from("/r/app"),
+            branch: None,
+            info: with(vec![submodule("ok", SubmoduleState::Current, false)]),
+        };
+        assert!(!roll_up(untouched).is_dirty());
+    }
+}
@@ -0,0 +1,248 @@
+//! Tree view of repositories
+//!
+//! This module renders the discovered repositories under their parent
+//! directories relative to the scan root, collapsing directories that
+//! only contain clean repositories.
+
+use crate::core::RepoStatus;
+use crate::output::{self, Symbols};
+use colored::*;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A directory or repository in the tree
+#[derive(Debug, Default)]
+struct Node<'a> {
+    repo: Option<&'a RepoStatus>,
+    children: BTreeMap<String, Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    fn insert(&mut self, components: &[String], status: &'a RepoStatus) {
+        match components.split_first() {
+            Some((first, rest)) => self
+                .children
+                .entry(first.clone())
+                .or_default()
+                .insert(rest, status),
+            None => self.repo = Some(status),
+        }
+    }
+
+    /// Counts the repositories below this node as `(total, dirty)`
+    fn descendant_counts(&self) -> (usize, usize) {
+        self.children
+            .values()
+            .fold((0, 0), |(total, dirty), child| {
+                let (t, d) = child.descendant_counts();
+                let own = child.repo.map_or((0, 0), |r| (1, r.is_dirty() as usize));
+                (total + t + own.0, dirty + d + own.1)
+            })
+    }
+
+    /// Follows chains of plain directories with a single child
+    ///
+    /// Returns the joined label and the node where the chain ends.
+    fn compress(&self, name: &str) -> (String, &Node<'a>) {
+        let mut label = name.to_string();
+        let mut node = self;
+        while node.repo.is_none() && node.children.len() == 1 {
+            let (child_name, child) = node.children.iter().next().expect("one child");
+            if child.repo.is_some() {
+                break;
+            }
+            label = format!("{}/{}", label, child_name);
+            node = child;
+        }
+        (label, node)
+    }
+}
+
+/// Builds the tree of repositories relative to the scan root
+fn build<'a>(statuses: &'a [RepoStatus], root: &Path) -> Node<'a> {
+    let mut tree = Node::default();
+    for status in statuses {
+        let path = status.path();
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let components: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        tree.insert(&components, status);
+    }
+    tree
+}
+
+/// Formats the suffix shown for a collapsed directory
+fn collapsed_label(total: usize) -> String {
+    format!(
+        "({} clean repo{})",
+        total,
+        if total == 1 { "" } else { "s" }
+    )
+    .bright_black()
+    .to_string()
+}
+
+fn render_children(
+    node: &Node<'_>,
+    prefix: &str,
+    expand: bool,
+    symbols: &Symbols,
+    lines: &mut Vec<String>,
+) {
+    let count = node.children.len();
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let last = i + 1 == count;
+        let branch = if last {
+            symbols.tree_last
+        } else {
+            symbols.tree_branch
+        };
+        let child_prefix = format!(
+            "{}{}",
+            prefix,
+            if last { "    " } else { symbols.tree_pipe }
+        );
+
+        let (label, child) = child.compress(name);
+        let (total, dirty) = child.descendant_counts();
+        let collapse = !expand && total > 0 && dirty == 0;
+
+        let mut line = match child.repo {
+            Some(status) => output::repo_label(&label, status, symbols),
+            None => format!("{}/", label).bright_blue().to_string(),
+        };
+        if collapse {
+            line = format!("{} {}", line, collapsed_label(total));
+        }
+        lines.push(format!("{}{}{}", prefix, branch.bright_black(), line));
+
+        if !collapse {
+            render_children(child, &child_prefix, expand, symbols, lines);
+        }
+    }
+}
+
+/// Renders the repositories as tree lines below the scan root
+///
+/// Directories whose repositories are all clean are collapsed into a
+/// single line unless `expand` is set.
+pub fn render(
+    statuses: &[RepoStatus],
+    root: &Path,
+    expand: bool,
+    symbols: &Symbols,
+) -> Vec<String> {
+    let tree = build(statuses, root);
+    let (total, dirty) = tree.descendant_counts();
+
+    let mut header = root.display().to_string().bright_blue().bold().to_string();
+    if let Some(status) = tree.repo {
+        header = output::repo_label(&root.display().to_string(), status, symbols);
+    }
+    let collapse = !expand && total > 0 && dirty == 0;
+    if collapse {
+        header = format!("{} {}", header, collapsed_label(total));
+    }
+
+    let mut lines = vec![header];
+    if !collapse {
+        render_children(&tree, "", expand, symbols, &mut lines);
+    }
+    lines
+}
+
+/// Prints the repository tree
+pub fn print_tree(statuses: &[RepoStatus], root: &Path, expand: bool) {
+    for line in render(statuses, root, expand, output::symbols()) {
+        eprintln!("{}", line);
+    }
+    eprintln!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::RepoInfo;
+    use crate::diffstat::DiffStats;
+    use std::path::PathBuf;
+
+    fn repo(path: &str, dirty: bool) -> RepoStatus {
+        let path = PathBuf::from(path);
+        if dirty {
+            RepoStatus::Dirty {
+                path,
+                changes: " M a\n".to_string(),
+                diff: DiffStats::default(),
+                conflicts: Vec::new(),
+                branch: None,
+                info: RepoInfo::default(),
+            }
+        } else {
+            RepoStatus::Clean {
+                path,
+                branch: None,
+                info: RepoInfo::default(),
+            }
+        }
+    }
+
+    fn sample() -> Vec<RepoStatus> {
+        vec![
+            repo("/p/oss/api", false),
+            repo("/p/oss/cli", false),
+            repo("/p/work/clients/acme/api", true),
+            repo("/p/work/web", false),
+        ]
+    }
+
+    #[test]
+    fn test_collapses_clean_directories() {
+        colored::control::set_override(false);
+        let lines = render(&sample(), Path::new("/p"), false, &output::UNICODE);
+        assert_eq!(
+            lines,
+            [
+                "/p",
+                "├── oss/ (2 clean repos)",
+                "└── work/",
+                "    ├── clients/acme/",
+                "    │   └── ✗ api [1 change]",
+                "    └── ✓ web",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_shows_all_repos() {
+        colored::control::set_override(false);
+        let lines = render(&sample(), Path::new("/p"), true, &output::UNICODE);
+        assert!(lines.contains(&"├── oss/".to_string()));
+        assert!(lines.contains(&"│   ├── ✓ api".to_string()));
+        assert!(lines.contains(&"│   └── ✓ cli".to_string()));
+    }
+
+    #[test]
+    fn test_all_clean_collapses_root() {
+        colored::control::set_override(false);
+        let statuses = [repo("/p/a", false), repo("/p/b", false)];
+        let lines = render(&statuses, Path::new("/p"), false, &output::UNICODE);
+        assert_eq!(lines, ["/p (2 clean repos)"]);
+    }
+
+    #[test]
+    fn test_plain_symbols() {
+        colored::control::set_override(false);
+        let lines = render(&sample(), Path::new("/p"), false, &output::ASCII);
+        assert_eq!(lines[1], "|-- oss/ (2 clean repos)");
+        assert_eq!(lines[4], "    |   `-- [dirty] api [1 change]");
+    }
+
+    #[test]
+    fn test_descendant_counts() {
+        let statuses = sample();
+        let tree = build(&statuses, Path::new("/p"));
+        assert_eq!(tree.descendant_counts(), (4, 1));
+    }
+}
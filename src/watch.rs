@@ -0,0 +1,440 @@
+//! Watch mode
+//!
+//! This module subscribes to filesystem events for every repository,
+//! re-checks only the repositories that changed once events settle, and
+//! redraws a live status display in place.
+
+use crate::bare;
+use crate::core::{self, Details, RepoStatus};
+use crate::error::{Error, Result};
+use crate::output::{self, PathStyle};
+use crate::query::Filter;
+use crate::sort::{self, SortKey};
+use colored::*;
+use notify::event::{EventKind, MetadataKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// Default quiet period before changed repositories are re-checked
+pub const DEFAULT_DEBOUNCE_MS: u64 = 500;
+
+/// Watches the working trees and `.git` directories of a set of repositories
+///
+/// Ignored directories such as `target/` or `node_modules` aren't watched.
+/// The watcher can be shared between threads: one thread waits for changes
+/// while others add or remove repositories.
+pub struct RepoWatcher {
+    watcher: Mutex<RecommendedWatcher>,
+    events: Mutex<Receiver<notify::Result<Event>>>,
+    /// The watched paths of each repository
+    repos: RwLock<BTreeMap<PathBuf, BTreeSet<PathBuf>>>,
+    debounce: Duration,
+}
+
+impl RepoWatcher {
+    /// Subscribes to filesystem events for every repository
+    ///
+    /// Repositories that can't be watched are reported as warnings and
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Watch` if the watcher can't be created
+    pub fn new(repos: &[PathBuf], debounce: Duration) -> Result<RepoWatcher> {
+        let (tx, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(tx).map_err(|e| Error::Watch(e.to_string()))?;
+
+        let repo_watcher = RepoWatcher {
+            watcher: Mutex::new(watcher),
+            events: Mutex::new(events),
+            repos: RwLock::new(BTreeMap::new()),
+            debounce,
+        };
+        for repo in repos {
+            if let Err(e) = repo_watcher.watch_repo(repo) {
+                output::print_warning(&e.to_string());
+            }
+        }
+        Ok(repo_watcher)
+    }
+
+    /// Starts watching another repository; does nothing if already watched
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Watch` if the repository can't be watched
+    pub fn watch_repo(&self, repo: &Path) -> Result<()> {
+        let mut repos = self.repos.write().expect("watcher lock poisoned");
+        if repos.contains_key(repo) {
+            return Ok(());
+        }
+        let mut watcher = self.watcher.lock().expect("watcher lock poisoned");
+        watcher
+            .watch(repo, RecursiveMode::NonRecursive)
+            .map_err(|e| Error::Watch(format!("{}: {}", repo.display(), e)))?;
+
+        let mut watched = BTreeSet::from([repo.to_path_buf()]);
+        sync_watches(&mut *watcher, repo, &mut watched);
+        repos.insert(repo.to_path_buf(), watched);
+        Ok(())
+    }
+
+    /// Stops watching a repository
+    pub fn unwatch_repo(&self, repo: &Path) {
+        let mut repos = self.repos.write().expect("watcher lock poisoned");
+        if let Some(watched) = repos.remove(repo) {
+            let mut watcher = self.watcher.lock().expect("watcher lock poisoned");
+            for path in watched {
+                // Fails for paths that are already gone, which is fine
+                let _ = watcher.unwatch(&path);
+            }
+        }
+    }
+
+    /// Blocks until repositories change, returning the changed repositories
+    ///
+    /// After the first relevant event, keeps collecting events until none
+    /// arrive for the debounce period. The watches of changed repositories
+    /// are then updated for created, removed or newly ignored directories.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Watch` if the watcher reports an error or stops
+    pub fn wait_for_changes(&self) -> Result<BTreeSet<PathBuf>> {
+        let events = self.events.lock().expect("watcher lock poisoned");
+        let mut changed = BTreeSet::new();
+
+        while changed.is_empty() {
+            let event = events
+                .recv()
+                .map_err(|_| Error::Watch("watcher stopped".to_string()))?;
+            self.collect(event, &mut changed)?;
+        }
+
+        loop {
+            match events.recv_timeout(self.debounce) {
+                Ok(event) => self.collect(event, &mut changed)?,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::Watch("watcher stopped".to_string()));
+                }
+            }
+        }
+
+        let mut repos = self.repos.write().expect("watcher lock poisoned");
+        let mut watcher = self.watcher.lock().expect("watcher lock poisoned");
+        for repo in &changed {
+            if let Some(watched) = repos.get_mut(repo) {
+                sync_watches(&mut *watcher, repo, watched);
+            }
+        }
+        Ok(changed)
+    }
+
+    fn collect(&self, event: notify::Result<Event>, changed: &mut BTreeSet<PathBuf>) -> Result<()> {
+        let event = event.map_err(|e| Error::Watch(e.to_string()))?;
+        if !is_relevant(&event) {
+            return Ok(());
+        }
+        let repos = self.repos.read().expect("watcher lock poisoned");
+        for path in &event.paths {
+            if is_lock_file(path) {
+                continue;
+            }
+            if let Some(repo) = repo_for_path(repos.keys(), path) {
+                changed.insert(repo.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lists the paths to watch for a repository, and whether recursively
+///
+/// Every directory of the working tree is watched on its own so ignored
+/// directories can be left out. Of the git directory only the top level,
+/// where the index and `HEAD` live, and the refs matter.
+fn watch_targets(repo: &Path) -> Vec<(PathBuf, RecursiveMode)> {
+    if bare::is_bare_repo(repo) {
+        return vec![
+            (repo.to_path_buf(), RecursiveMode::NonRecursive),
+            (repo.join("refs"), RecursiveMode::Recursive),
+        ];
+    }
+
+    let mut targets: Vec<(PathBuf, RecursiveMode)> = core::walk_work_tree(repo)
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_some_and(|t| t.is_dir()))
+        .map(|e| (e.into_path(), RecursiveMode::NonRecursive))
+        .collect();
+    let git_dir = repo.join(".git");
+    if git_dir.is_dir() {
+        targets.push((git_dir.join("refs"), RecursiveMode::Recursive));
+    }
+    targets.push((git_dir, RecursiveMode::NonRecursive));
+    targets
+}
+
+/// Updates the watches of a repository to match its current directories
+///
+/// Failures are ignored: directories can disappear between listing and
+/// watching, and the next change re-syncs anyway.
+fn sync_watches(watcher: &mut impl Watcher, repo: &Path, watched: &mut BTreeSet<PathBuf>) {
+    let targets = watch_targets(repo);
+    let wanted: BTreeSet<&Path> = targets.iter().map(|(path, _)| path.as_path()).collect();
+
+    watched.retain(|path| {
+        if wanted.contains(path.as_path()) {
+            return true;
+        }
+        let _ = watcher.unwatch(path);
+        false
+    });
+    for (path, mode) in &targets {
+        if !watched.contains(path) && watcher.watch(path, *mode).is_ok() {
+            watched.insert(path.clone());
+        }
+    }
+}
+
+/// Returns true for events that can change a repository's status
+fn is_relevant(event: &Event) -> bool {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime)) => false,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => true,
+        EventKind::Access(_) | EventKind::Any | EventKind::Other => false,
+    }
+}
+
+/// Returns true for git's transient `*.lock` files inside a `.git` directory
+fn is_lock_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "lock")
+        && path.components().any(|c| c.as_os_str() == ".git")
+}
+
+/// Finds the innermost repository containing a path
+pub fn repo_for_path<'a>(
+    repos: impl IntoIterator<Item = &'a PathBuf>,
+    path: &Path,
+) -> Option<&'a PathBuf> {
+    repos
+        .into_iter()
+        .filter(|repo| path.starts_with(repo))
+        .max_by_key(|repo| repo.components().count())
+}
+
+/// Settings for the watch display
+pub struct WatchOptions<'a> {
+    pub root: &'a Path,
+    pub details: Details,
+    pub debounce: Duration,
+    pub filter: Option<&'a Filter>,
+    pub sort: SortKey,
+    pub path_style: PathStyle,
+}
+
+/// Formats one line of the watch display
+fn status_line(status: &RepoStatus, name: &str, changed: bool) -> String {
+    let line = output::repo_label(name, status, output::symbols());
+    if changed {
+        format!("{} {}", line.reversed(), "(changed)".magenta().bold())
+    } else {
+        line
+    }
+}
+
+/// Redraws the whole display
+fn draw(
+    statuses: &BTreeMap<PathBuf, RepoStatus>,
+    changed: &BTreeSet<PathBuf>,
+    options: &WatchOptions<'_>,
+) {
+    let mut shown: Vec<RepoStatus> = statuses
+        .values()
+        .filter(|s| options.filter.is_none_or(|f| f.matches(s)))
+        .cloned()
+        .collect();
+    sort::sort_statuses(&mut shown, options.sort);
+    let names = output::display_names(&shown, options.root, options.path_style);
+
+    if std::io::stderr().is_terminal() {
+        // Move the cursor home and clear the screen
+        eprint!("\x1b[H\x1b[2J");
+    }
+
+    let symbols = output::symbols();
+    eprintln!(
+        "{} Watching {} repos in {} (updated {})",
+        symbols.search.cyan(),
+        statuses.len(),
+        options.root.display().to_string().bright_blue(),
+        humantime::format_rfc3339_seconds(SystemTime::now())
+    );
+    eprintln!("{}", symbols.rule.repeat(40).bright_black());
+
+    for status in &shown {
+        let name = &names[status.path()];
+        eprintln!(
+            "{}",
+            status_line(status, name, changed.contains(status.path()))
+        );
+    }
+
+    eprintln!();
+    let dirty = shown.iter().filter(|s| s.is_dirty()).count();
+    output::print_summary(shown.len(), dirty);
+}
+
+/// Runs the initial scan, then re-checks and redraws on every change
+///
+/// Only returns on error; the process is expected to be interrupted.
+///
+/// # Errors
+///
+/// Returns `Error::Watch` if filesystem watching fails
+pub fn run(repos: &[PathBuf], options: &WatchOptions<'_>) -> Result<()> {
+    let watcher = RepoWatcher::new(repos, options.debounce)?;
+
+    let (initial, errors) = core::check_repos_parallel(repos, options.details, None);
+    for error in &errors {
+        output::print_warning(&error.to_string());
+    }
+    let mut statuses: BTreeMap<PathBuf, RepoStatus> = initial
+        .into_iter()
+        .map(|s| (s.path().to_path_buf(), s))
+        .collect();
+    draw(&statuses, &BTreeSet::new(), options);
+
+    loop {
+        let touched: Vec<PathBuf> = watcher.wait_for_changes()?.into_iter().collect();
+        let (updated, errors) = core::check_repos_parallel(&touched, options.details, None);
+
+        // Repositories that were removed or can no longer be checked drop
+        // out of the display; the latter stay watched in case they recover
+        for path in &touched {
+            if !updated.iter().any(|s| s.path() == path) {
+                statuses.remove(path);
+                if !path.exists() {
+                    watcher.unwatch_repo(path);
+                }
+            }
+        }
+
+        let mut changed = BTreeSet::new();
+        for status in updated {
+            let path = status.path().to_path_buf();
+            if statuses.get(&path) != Some(&status) {
+                changed.insert(path.clone());
+            }
+            statuses.insert(path, status);
+        }
+
+        draw(&statuses, &changed, options);
+        for error in &errors {
+            output::print_warning(&error.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, DataChange};
+    use std::fs;
+    use std::process::Command;
+
+    #[test]
+    fn test_repo_for_path_prefers_innermost() {
+        let repos = [PathBuf::from("/p/outer"), PathBuf::from("/p/outer/inner")];
+        assert_eq!(
+            repo_for_path(&repos, Path::new("/p/outer/inner/src/a.rs")),
+            Some(&repos[1])
+        );
+        assert_eq!(
+            repo_for_path(&repos, Path::new("/p/outer/.git/HEAD")),
+            Some(&repos[0])
+        );
+        assert_eq!(repo_for_path(&repos, Path::new("/p/other/a.rs")), None);
+    }
+
+    #[test]
+    fn test_event_relevance() {
+        assert!(is_relevant(&Event::new(EventKind::Create(
+            CreateKind::File
+        ))));
+        assert!(is_relevant(&Event::new(EventKind::Modify(
+            ModifyKind::Data(DataChange::Content)
+        ))));
+        assert!(!is_relevant(&Event::new(EventKind::Access(
+            AccessKind::Read
+        ))));
+        assert!(is_lock_file(Path::new("/p/.git/index.lock")));
+        assert!(!is_lock_file(Path::new("/p/Cargo.toml")));
+        assert!(!is_lock_file(Path::new("/p/Cargo.lock")));
+    }
+
+    #[test]
+    fn test_watcher_reports_changed_repo() {
+        let temp = tempfile::tempdir().unwrap();
+        let repo = temp.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        Command::new("git")
+            .arg("init")
+            .current_dir(&repo)
+            .output()
+            .unwrap();
+
+        let repos = vec![repo.canonicalize().unwrap()];
+        let watcher = RepoWatcher::new(&repos, Duration::from_millis(50)).unwrap();
+        fs::write(repo.join("new.txt"), "hello").unwrap();
+
+        let changed = watcher.wait_for_changes().unwrap();
+        assert_eq!(changed.into_iter().collect::<Vec<_>>(), repos);
+    }
+
+    #[test]
+    fn test_watch_targets_skip_ignored_dirs() {
+        let temp = tempfile::tempdir().unwrap();
+        let repo = temp.path();
+        Command::new("git")
+            .arg("init")
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        fs::write(repo.join(".gitignore"), "target/\n").unwrap();
+        fs::create_dir_all(repo.join("target/debug")).unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+
+        let targets: Vec<PathBuf> = watch_targets(repo).into_iter().map(|(p, _)| p).collect();
+        assert!(targets.contains(&repo.join("src")));
+        assert!(targets.contains(&repo.join(".git/refs")));
+        assert!(!targets.iter().any(|p| p.starts_with(repo.join("target"))));
+        assert!(!targets.contains(&repo.join(".git/objects")));
+    }
+
+    #[test]
+    fn test_watcher_follows_new_directories() {
+        let temp = tempfile::tempdir().unwrap();
+        let repo = temp.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        Command::new("git")
+            .arg("init")
+            .current_dir(&repo)
+            .output()
+            .unwrap();
+
+        let repos = vec![repo.canonicalize().unwrap()];
+        let watcher = RepoWatcher::new(&repos, Duration::from_millis(50)).unwrap();
+        fs::create_dir(repo.join("src")).unwrap();
+        assert_eq!(watcher.wait_for_changes().unwrap().len(), 1);
+
+        fs::write(repo.join("src/a.rs"), "fn main() {}").unwrap();
+        let changed = watcher.wait_for_changes().unwrap();
+        assert_eq!(changed.into_iter().collect::<Vec<_>>(), repos);
+    }
+}
@@ -0,0 +1,178 @@
+//! Linked worktrees
+//!
+//! This module lists the linked worktrees of a repository, created with
+//! `git worktree add`, and checks each one for uncommitted changes.
+//! Worktrees whose directory is gone are reported so they can be pruned.
+
+use crate::core;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A linked worktree of a repository
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Worktree {
+    pub path: PathBuf,
+    /// Checked-out branch, or `None` when detached
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Abbreviated commit at HEAD
+    pub commit: String,
+    /// Number of entries reported by `git status --porcelain`
+    #[serde(skip_serializing_if = "core::is_zero")]
+    pub changes: usize,
+    #[serde(skip_serializing_if = "core::is_false")]
+    pub locked: bool,
+    /// Whether git considers the worktree stale and `git worktree prune`
+    /// would remove it
+    #[serde(skip_serializing_if = "core::is_false")]
+    pub prunable: bool,
+    /// Whether the worktree directory no longer exists
+    #[serde(skip_serializing_if = "core::is_false")]
+    pub missing: bool,
+}
+
+impl Worktree {
+    pub fn is_dirty(&self) -> bool {
+        self.changes > 0
+    }
+
+    /// Returns true if the worktree is gone or stale
+    pub fn is_stale(&self) -> bool {
+        self.prunable || self.missing
+    }
+}
+
+/// Parses `git worktree list --porcelain` output, skipping the main
+/// worktree and bare entries
+///
+/// Entries are separated by blank lines and consist of a `worktree <path>`
+/// line followed by `HEAD <sha>`, `branch <ref>` or `detached`, and
+/// optional `bare`, `locked [reason]` and `prunable [reason]` lines.
+fn parse_worktrees(output: &str) -> Vec<Worktree> {
+    let mut worktrees = Vec::new();
+    for (index, entry) in output.split("\n\n").enumerate() {
+        let mut worktree = Worktree::default();
+        let mut bare = false;
+        for line in entry.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "worktree" => worktree.path = PathBuf::from(value),
+                "HEAD" => worktree.commit = value.chars().take(7).collect(),
+                "branch" => {
+                    let name = value.strip_prefix("refs/heads/").unwrap_or(value);
+                    worktree.branch = Some(name.to_string());
+                }
+                "bare" => bare = true,
+                "locked" => worktree.locked = true,
+                "prunable" => worktree.prunable = true,
+                _ => {}
+            }
+        }
+        // The first entry is always the main worktree
+        if index > 0 && !bare && !worktree.path.as_os_str().is_empty() {
+            worktrees.push(worktree);
+        }
+    }
+    worktrees
+}
+
+/// Counts the entries reported by `git status --porcelain` in a worktree
+fn count_changes(path: &Path) -> usize {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(path)
+        .arg("--no-optional-locks")
+        .arg("status")
+        .arg("--porcelain");
+    core::run_git(&mut command, None)
+        .ok()
+        .flatten()
+        .filter(|output| output.status.success())
+        .map_or(0, |output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .count()
+        })
+}
+
+/// Lists the linked worktrees of a repository with their status
+///
+/// `git_dir` is the repository's `.git` directory, or the repository itself
+/// when it is bare. Returns an empty list if it has no `worktrees`
+/// directory.
+pub fn get_worktrees(repo_path: &Path, git_dir: &Path) -> Vec<Worktree> {
+    if !git_dir.join("worktrees").is_dir() {
+        return Vec::new();
+    }
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(repo_path)
+        .arg("worktree")
+        .arg("list")
+        .arg("--porcelain");
+    let output = match core::run_git(&mut command, None) {
+        Ok(Some(output)) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+
+    let mut worktrees = parse_worktrees(&String::from_utf8_lossy(&output.stdout));
+    for worktree in &mut worktrees {
+        worktree.missing = !worktree.path.is_dir();
+        if !worktree.missing {
+            worktree.changes = count_changes(&worktree.path);
+        }
+    }
+    worktrees
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "worktree /src/app
+HEAD 1a2b3c4d5e6f7a8b9c0d
+branch refs/heads/main
+
+worktree /src/app-feature
+HEAD 9f8e7d6c5b4a39281706
+branch refs/heads/feat/login
+locked
+
+worktree /tmp/app-review
+HEAD 0123456789abcdef0123
+detached
+prunable gitdir file points to non-existent location
+";
+
+    #[test]
+    fn test_parse_worktrees() {
+        let worktrees = parse_worktrees(OUTPUT);
+        assert_eq!(worktrees.len(), 2);
+
+        assert_eq!(worktrees[0].path, Path::new("/src/app-feature"));
+        assert_eq!(worktrees[0].branch.as_deref(), Some("feat/login"));
+        assert_eq!(worktrees[0].commit, "9f8e7d6");
+        assert!(worktrees[0].locked);
+        assert!(!worktrees[0].is_stale());
+
+        assert_eq!(worktrees[1].branch, None);
+        assert!(worktrees[1].prunable);
+        assert!(worktrees[1].is_stale());
+    }
+
+    #[test]
+    fn test_parse_worktrees_skips_main_and_bare() {
+        assert!(
+            parse_worktrees("worktree /src/app\nHEAD 1a2b3c4\nbranch refs/heads/main\n").is_empty()
+        );
+        assert!(
+            parse_worktrees("worktree /src/app.git\nbare\n\nworktree /src/x\nbare\n").is_empty()
+        );
+        assert!(parse_worktrees("").is_empty());
+    }
+}
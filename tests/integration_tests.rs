@@ -1,11 +1,27 @@
 use std::env;
 use std::fs;
+use std::path::Path;
 use std::process::Command;
 
-/// Helper to run the binary with arguments
+/// Helper to build a command for the binary that keeps its caches and data
+/// in `home` instead of the user's directories
+fn binary_in(home: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_check-git-status"));
+    command
+        .env("XDG_CACHE_HOME", home.join("cache"))
+        .env("XDG_DATA_HOME", home.join("data"));
+    command
+}
+
+/// Helper to run the binary with arguments and a home of its own
 fn run_with_args(args: &[&str]) -> std::process::Output {
-    let bin_path = env!("CARGO_BIN_EXE_check-git-status");
-    Command::new(bin_path)
+    let home = tempfile::tempdir().expect("Failed to create temp dir");
+    run_in_home(home.path(), args)
+}
+
+/// Helper to run the binary with arguments in a home shared between runs
+fn run_in_home(home: &Path, args: &[&str]) -> std::process::Output {
+    binary_in(home)
         .args(args)
         .output()
         .expect("Failed to execute binary")
@@ -58,6 +74,37 @@ fn create_temp_git_repo(name: &str, dirty: bool) -> tempfile::TempDir {
     temp
 }
 
+/// Helper to build a git command in a directory that commits as the test
+/// user and may clone local submodules
+fn git_command(dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .args([
+            "-c",
+            "user.name=Test User",
+            "-c",
+            "user.email=test@test.com",
+            "-c",
+            "protocol.file.allow=always",
+        ])
+        .current_dir(dir);
+    command
+}
+
+/// Helper to run git in a directory, failing the test if it fails
+fn git(dir: &Path, args: &[&str]) {
+    let output = git_command(dir)
+        .args(args)
+        .output()
+        .expect("Failed to run git");
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
 #[test]
 fn test_help_flag() {
     let output = run_with_args(&["--help"]);
@@ -183,3 +230,916 @@ fn test_summary_mode_default() {
     // Default mode should show summary
     assert!(stderr.contains("Total repos:") || stderr.contains("Dirty repos:"));
 }
+
+#[test]
+fn test_where_filter() {
+    let temp = create_temp_git_repo("where_test", true);
+    let root = temp.path().to_str().unwrap();
+
+    let output = run_with_args(&["-q", "--where", "clean", root, "2"]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = run_with_args(&["-q", "--where", "dirty and changes >= 1", root, "2"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // The untracked test file is 12 bytes
+    let output = run_with_args(&["--json", "--where", "largest_untracked > 10", root, "2"]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    assert_eq!(json["repositories"][0]["largest_untracked"], 12);
+    let output = run_with_args(&["-q", "--where", "largest_untracked > 1KB", root, "2"]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_where_parse_error() {
+    let output = run_with_args(&["--where", "dirty and", ".", "1"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid query at column 10"));
+}
+
+#[test]
+fn test_group_by_json() {
+    let temp = create_temp_git_repo("group_test", true);
+    let output = run_with_args(&[
+        "--json",
+        "--sort",
+        "status",
+        "--group-by",
+        "status",
+        temp.path().to_str().unwrap(),
+        "2",
+    ]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\"groups\":"));
+    assert!(stdout.contains("\"name\": \"dirty\""));
+}
+
+#[test]
+fn test_tree_output() {
+    let temp = create_temp_git_repo("tree_test", true);
+    let output = run_with_args(&["--tree", temp.path().to_str().unwrap(), "2"]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("tree_test"));
+    assert!(stderr.contains("└── "));
+    assert!(stderr.contains("Total repos:"));
+}
+
+#[test]
+fn test_verbose_relative_paths() {
+    let temp = create_temp_git_repo("nested", true);
+    let root = temp.path().to_str().unwrap();
+
+    let output = run_with_args(&["-v", root, "2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("nested"));
+
+    let output = run_with_args(&["-v", "--path-style", "absolute", root, "2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("{}/nested", root)));
+}
+
+#[test]
+fn test_plain_no_color_output() {
+    let temp = create_temp_git_repo("plain_test", true);
+    let output = run_with_args(&[
+        "-v",
+        "--plain",
+        "--color",
+        "never",
+        temp.path().to_str().unwrap(),
+        "2",
+    ]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.is_ascii());
+    assert!(!stderr.contains('\x1b'));
+    assert!(stderr.contains("[repo] Total repos:"));
+}
+
+#[test]
+fn test_clicolor_force() {
+    let temp = create_temp_git_repo("color_test", false);
+    let output = binary_in(temp.path())
+        .args([temp.path().to_str().unwrap(), "2"])
+        .env_remove("NO_COLOR")
+        .env("CLICOLOR_FORCE", "1")
+        .output()
+        .expect("Failed to execute binary");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains('\x1b'));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_client_list() {
+    let temp = create_temp_git_repo("daemon_test", true);
+    let socket = temp.path().join("daemon.sock");
+    let socket_arg = socket.to_str().unwrap();
+
+    let mut daemon = binary_in(temp.path())
+        .args([
+            "--daemon",
+            "--socket",
+            socket_arg,
+            temp.path().to_str().unwrap(),
+            "2",
+        ])
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("Failed to start daemon");
+
+    for _ in 0..100 {
+        if socket.exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+
+    let output = run_with_args(&["--client", "list", "--socket", socket_arg]);
+    daemon.kill().expect("Failed to stop daemon");
+    let _ = daemon.wait();
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\"ok\":true"));
+    assert!(stdout.contains("daemon_test"));
+}
+
+#[test]
+fn test_client_without_daemon() {
+    let temp = tempfile::tempdir().expect("Failed to create temp dir");
+    let socket = temp.path().join("missing.sock");
+    let output = run_with_args(&["--client", "list", "--socket", socket.to_str().unwrap()]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is the daemon running?"));
+}
+
+#[test]
+fn test_status_cache_hits_and_invalidation() {
+    let temp = create_temp_git_repo("cache_test", false);
+    let home = tempfile::tempdir().expect("Failed to create temp dir");
+    let root = temp.path().to_str().unwrap();
+    let run = || run_in_home(home.path(), &["--cache-stats", root, "2"]);
+
+    let stderr = String::from_utf8_lossy(&run().stderr).into_owned();
+    assert!(stderr.contains("0 hits, 1 miss"), "{stderr}");
+
+    let output = run();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stderr.contains("1 hit, 0 misses"), "{stderr}");
+
+    // A new file changes the fingerprint, so the repo is re-checked
+    fs::write(temp.path().join("cache_test/new.txt"), "x").expect("Failed to write file");
+    let output = run();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("0 hits, 1 miss"), "{stderr}");
+}
+
+#[test]
+fn test_discovery_cache_rescan() {
+    let temp = create_temp_git_repo("discovery_test", false);
+    let home = tempfile::tempdir().expect("Failed to create temp dir");
+    let root = temp.path().to_str().unwrap();
+    let run = |extra: &[&str]| {
+        let output = binary_in(home.path())
+            .args(["--cache-stats", root, "2"])
+            .args(extra)
+            .output()
+            .expect("Failed to execute binary");
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+
+    assert!(run(&[]).contains("2 directories read, 1 repo found"));
+    assert!(run(&[]).contains("0 directories read, 1 repo found"));
+    assert!(run(&["--rescan"]).contains("2 directories read, 1 repo found"));
+}
+
+#[test]
+fn test_snapshot_diff() {
+    let temp = create_temp_git_repo("snapshot_test", false);
+    let root = temp.path().to_str().unwrap();
+    let old = temp.path().join("old.json");
+    let new = temp.path().join("new.json");
+
+    let output = run_with_args(&["--save-snapshot", old.to_str().unwrap(), root, "2"]);
+    assert_eq!(output.status.code(), Some(0));
+
+    fs::write(temp.path().join("snapshot_test/new.txt"), "x").expect("Failed to write file");
+    run_with_args(&["--save-snapshot", new.to_str().unwrap(), root, "2"]);
+
+    let output = run_with_args(&["--diff", old.to_str().unwrap(), new.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("snapshot_test: clean"), "{stderr}");
+
+    let changes = format!("--changes={}", new.display());
+    let output = run_with_args(&["--json", &changes, root, "2"]);
+    assert_eq!(output.status.code(), Some(0));
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    assert_eq!(json["changed"].as_array().map(Vec::len), Some(0));
+}
+
+#[test]
+fn test_changes_since_last_run() {
+    let temp = create_temp_git_repo("changes_test", false);
+    let home = tempfile::tempdir().expect("Failed to create temp dir");
+    let root = temp.path().to_str().unwrap();
+    let run = |args: &[&str]| run_in_home(home.path(), args);
+
+    // Plain runs don't leave a baseline behind
+    run(&[root]);
+    // The first run has no baseline, but leaves one behind
+    let output = run(&["--changes", root]);
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No earlier run"), "{stderr}");
+
+    fs::write(temp.path().join("changes_test/new.txt"), "x").expect("Failed to write file");
+    let output = run(&["--changes", "--exit-code", "bitmask", root]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("changes_test: clean"), "{stderr}");
+
+    // Nothing changed since the last run, and a deeper scan has its own baseline
+    let output = run(&["--changes", root]);
+    assert_eq!(output.status.code(), Some(0));
+    let output = run(&["--changes", root, "5"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No earlier run"), "{stderr}");
+}
+
+#[cfg(feature = "history")]
+#[test]
+fn test_history_report() {
+    let temp = create_temp_git_repo("history_test", true);
+    let home = tempfile::tempdir().expect("Failed to create temp dir");
+    let root = temp.path().to_str().unwrap();
+    let run = |extra: &[&str]| {
+        binary_in(home.path())
+            .args(extra)
+            .args([root, "2"])
+            .output()
+            .expect("Failed to execute binary")
+    };
+
+    run(&["-q", "--record-history"]);
+    run(&["-q", "--record-history"]);
+    run(&["-q"]);
+
+    let output = run(&["--json", "--history"]);
+    assert_eq!(output.status.code(), Some(0));
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    assert_eq!(json["runs"], 2);
+    assert_eq!(json["dirty_counts"][1]["dirty"], 1);
+    assert!(
+        json["dirty_since"][0]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("history_test")
+    );
+}
+
+#[test]
+fn test_policy_violations() {
+    let temp = create_temp_git_repo("policy_test", true);
+    let policy = temp.path().join("policy.toml");
+    fs::write(
+        &policy,
+        r#"
+[[rule]]
+name = "no-dirty"
+description = "Commit or stash everything"
+when = "dirty"
+severity = "error"
+"#,
+    )
+    .expect("Failed to write policy");
+
+    let output = run_with_args(&[
+        "--policy",
+        policy.to_str().unwrap(),
+        temp.path().to_str().unwrap(),
+        "2",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("[error] no-dirty: Commit or stash everything"));
+
+    fs::write(&policy, "[[rule]]\nname = \"bad\"\nwhen = \"dirty and\"\n")
+        .expect("Failed to write policy");
+    let output = run_with_args(&["--policy", policy.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid policy"));
+}
+
+#[test]
+fn test_exit_code_modes() {
+    let temp = create_temp_git_repo("exit_code_test", true);
+    let root = temp.path().to_str().unwrap();
+
+    let code = |mode: &str| {
+        run_with_args(&["-q", "--no-cache", "--exit-code", mode, root, "2"])
+            .status
+            .code()
+    };
+    assert_eq!(code("count"), Some(1));
+    assert_eq!(code("boolean"), Some(1));
+    assert_eq!(code("bitmask"), Some(1));
+    assert_eq!(code("zero"), Some(0));
+
+    // Fatal errors map to the error code of each mode
+    let output = run_with_args(&["--exit-code", "bitmask", "/nonexistent/path"]);
+    assert_eq!(output.status.code(), Some(4));
+    let output = run_with_args(&["--exit-code", "boolean", "/nonexistent/path"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = run_with_args(&["--exit-code", "zero", "/nonexistent/path"]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = run_with_args(&["--help"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("16  a policy rule above `info` was broken"));
+}
+
+#[test]
+fn test_stale_and_active_filters() {
+    let temp = create_temp_git_repo("fresh", false);
+    let old_repo = temp.path().join("old");
+    fs::create_dir_all(&old_repo).expect("Failed to create repo dir");
+    git(&old_repo, &["init"]);
+    git_command(&old_repo)
+        .args(["commit", "--allow-empty", "-m", "Ancient history"])
+        .env("GIT_AUTHOR_NAME", "Old Author")
+        .env("GIT_AUTHOR_DATE", "2020-01-01T00:00:00Z")
+        .env("GIT_COMMITTER_DATE", "2020-01-01T00:00:00Z")
+        .output()
+        .expect("Failed to run git");
+
+    let root = temp.path().to_str().unwrap();
+    let output = run_with_args(&["--json", "--no-cache", "--stale", "365d", root, "2"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let repos = json["repositories"].as_array().unwrap();
+    assert_eq!(repos.len(), 1);
+    let commit = &repos[0]["last_commit"];
+    assert_eq!(commit["timestamp"], "2020-01-01T00:00:00Z");
+    assert_eq!(commit["author"], "Old Author");
+    assert_eq!(commit["subject"], "Ancient history");
+
+    let output = run_with_args(&["--no-cache", "--active", "1d", root, "2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("fresh"));
+    assert!(!stderr.contains("old"));
+    assert!(stderr.contains("less than a minute ago"));
+}
+
+#[test]
+fn test_remote_filters_and_warnings() {
+    let temp = create_temp_git_repo("hosted", false);
+    let hosted = temp.path().join("hosted");
+    Command::new("git")
+        .args(["remote", "add", "origin", "git@github.com:Acme/hosted.git"])
+        .current_dir(&hosted)
+        .output()
+        .expect("Failed to add remote");
+    Command::new("git")
+        .args([
+            "remote",
+            "set-url",
+            "--push",
+            "origin",
+            "https://github.com/Acme/hosted.git",
+        ])
+        .current_dir(&hosted)
+        .output()
+        .expect("Failed to set push URL");
+    let local = temp.path().join("local");
+    fs::create_dir_all(&local).expect("Failed to create repo dir");
+    Command::new("git")
+        .args(["init"])
+        .current_dir(&local)
+        .output()
+        .expect("Failed to init git repo");
+
+    let root = temp.path().to_str().unwrap();
+    let output = run_with_args(&["--json", "--no-cache", "--owner", "acme", root, "2"]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let repos = json["repositories"].as_array().unwrap();
+    assert_eq!(repos.len(), 1);
+    let remote = &repos[0]["remotes"][0];
+    assert_eq!(remote["name"], "origin");
+    assert_eq!(remote["fetch"], "git@github.com:Acme/hosted.git");
+    assert_eq!(remote["push"], "https://github.com/Acme/hosted.git");
+
+    let output = run_with_args(&["--no-cache", root, "2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Without a remote: 1"));
+    assert!(stderr.contains("Pushing over HTTPS: 1"));
+}
+
+#[test]
+fn test_head_states() {
+    let temp = create_temp_git_repo("tagged", false);
+    let tagged = temp.path().join("tagged");
+    for args in [&["tag", "v1.0"][..], &["checkout", "--detach", "HEAD"][..]] {
+        Command::new("git")
+            .args(args)
+            .current_dir(&tagged)
+            .output()
+            .expect("Failed to run git");
+    }
+    let fresh = temp.path().join("fresh");
+    fs::create_dir_all(&fresh).expect("Failed to create repo dir");
+    Command::new("git")
+        .args(["init", "-b", "trunk"])
+        .current_dir(&fresh)
+        .output()
+        .expect("Failed to init git repo");
+
+    let root = temp.path().to_str().unwrap();
+    let output = run_with_args(&["--json", "-b", "--no-cache", root, "2"]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let repos = json["repositories"].as_array().unwrap();
+    assert_eq!(repos[0]["head"]["state"], "unborn");
+    assert_eq!(repos[0]["head"]["name"], "trunk");
+    assert_eq!(repos[0]["branch"], "trunk");
+    assert_eq!(repos[1]["head"]["state"], "detached");
+    assert_eq!(repos[1]["head"]["tag"], "v1.0");
+    assert!(repos[1].get("branch").is_none());
+
+    let output = run_with_args(&["--tree", "--expand", "--no-cache", root, "2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("fresh (no commits)"), "{stderr}");
+    assert!(stderr.contains("(detached at "), "{stderr}");
+    assert!(!stderr.contains("(HEAD)"));
+}
+
+#[test]
+fn test_submodules() {
+    let temp = create_temp_git_repo("lib", false);
+    let lib = temp.path().join("lib");
+    let app = temp.path().join("app");
+    fs::create_dir_all(&app).expect("Failed to create repo dir");
+    git(&app, &["init"]);
+    git(
+        &app,
+        &["submodule", "add", lib.to_str().unwrap(), "vendor/lib"],
+    );
+    git(&app, &["commit", "-m", "Add lib"]);
+
+    // Commit in the submodule and record it, without pushing it anywhere
+    let vendored = app.join("vendor/lib");
+    git(&vendored, &["commit", "--allow-empty", "-m", "Local fix"]);
+    git(&app, &["commit", "-am", "Bump lib"]);
+
+    let app_path = app.to_str().unwrap();
+    let output = run_with_args(&["--json", "--no-cache", app_path, "1"]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let repo = &json["repositories"][0];
+    assert_eq!(repo["status"], "clean");
+    let submodule = &repo["submodules"][0];
+    assert_eq!(submodule["path"], "vendor/lib");
+    assert_eq!(submodule["state"], "current");
+    assert_eq!(submodule["unpushed"], true);
+
+    let output = run_with_args(&["-v", "--no-cache", "--roll-up-submodules", app_path, "1"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("vendor/lib"));
+    assert!(stderr.contains("(unpushed)"), "{stderr}");
+}
+
+#[test]
+fn test_worktrees() {
+    let temp = create_temp_git_repo("main", false);
+    let main = temp.path().join("main");
+    let feature = temp.path().join("feature");
+    let gone = temp.path().join("gone");
+    for args in [
+        vec![
+            "worktree",
+            "add",
+            "-b",
+            "feature",
+            feature.to_str().unwrap(),
+        ],
+        vec!["worktree", "add", "--detach", gone.to_str().unwrap()],
+    ] {
+        Command::new("git")
+            .args(&args)
+            .current_dir(&main)
+            .output()
+            .expect("Failed to add worktree");
+    }
+    fs::write(feature.join("wip.txt"), "work in progress").expect("Failed to write file");
+    fs::remove_dir_all(&gone).expect("Failed to remove worktree");
+
+    let root = temp.path().to_str().unwrap();
+    let output = run_with_args(&["--json", "--no-cache", root, "2"]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    // Worktrees are reported under their repo, not as repos of their own
+    let repos = json["repositories"].as_array().unwrap();
+    assert_eq!(repos.len(), 1);
+    let worktrees = repos[0]["worktrees"].as_array().unwrap();
+    assert_eq!(worktrees.len(), 2);
+    assert_eq!(worktrees[0]["branch"], "feature");
+    assert_eq!(worktrees[0]["changes"], 1);
+    assert_eq!(worktrees[1]["missing"], true);
+
+    let output = run_with_args(&["-v", "--no-cache", root, "2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("[1 change]"), "{stderr}");
+    assert!(stderr.contains("[missing]"));
+    assert!(stderr.contains("Dirty worktrees: 1"));
+    assert!(stderr.contains("Stale worktrees: 1"));
+}
+
+#[test]
+fn test_bare_repos() {
+    let temp = create_temp_git_repo("source", false);
+    let source = temp.path().join("source");
+    let mirrors = temp.path().join("mirrors");
+    let bare = mirrors.join("tools.git");
+    git(&source, &["branch", "-M", "main"]);
+    fs::create_dir_all(&mirrors).expect("Failed to create dir");
+    git(&mirrors, &["clone", "--bare", "../source", "tools.git"]);
+    // Track the source's main, then fall one commit behind it
+    git(&bare, &["config", "branch.main.remote", "origin"]);
+    git(&bare, &["config", "branch.main.merge", "refs/heads/main"]);
+    git(
+        &bare,
+        &[
+            "config",
+            "remote.origin.fetch",
+            "+refs/heads/*:refs/remotes/origin/*",
+        ],
+    );
+    git(&bare, &["branch", "wip", "main"]);
+    // A mirror's branches are the source's own, so none lack an upstream
+    git(&mirrors, &["clone", "--mirror", "../source", "copy.git"]);
+    git(&source, &["commit", "--allow-empty", "-m", "Second"]);
+    git(&bare, &["fetch", "origin"]);
+
+    let root = temp.path().to_str().unwrap();
+    let output = run_with_args(&["--json", "--no-cache", root, "2"]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let repos = json["repositories"].as_array().unwrap();
+    assert_eq!(repos.len(), 3);
+    let find = |name: &str| {
+        repos
+            .iter()
+            .find(|r| r["path"].as_str().unwrap().ends_with(name))
+            .expect("Bare repo not found")
+    };
+    let mirror = find("tools.git");
+    assert_eq!(mirror["kind"], "bare");
+    assert_eq!(mirror["status"], "clean");
+    assert_eq!(mirror["behind"], 1);
+    assert_eq!(mirror["missing_upstream"], serde_json::json!(["wip"]));
+    let copy = find("copy.git");
+    assert_eq!(copy["kind"], "bare");
+    assert_eq!(copy["behind"], 0);
+    assert!(copy.get("missing_upstream").is_none(), "{copy}");
+
+    let output = run_with_args(&["-v", "--no-cache", "--where", "bare", root, "2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("tools.git [bare]"), "{stderr}");
+    assert!(stderr.contains("no upstream: wip"), "{stderr}");
+}
+
+#[test]
+fn test_lfs_checks() {
+    let temp = create_temp_git_repo("assets", false);
+    let repo = temp.path().join("assets");
+    // LFS is only enabled below media/, not in the top-level .gitattributes
+    let media = repo.join("media");
+    fs::create_dir_all(&media).expect("Failed to create dir");
+    fs::write(
+        media.join(".gitattributes"),
+        "*.bin filter=lfs diff=lfs merge=lfs -text\n",
+    )
+    .expect("Failed to write file");
+    fs::write(media.join("plain.bin"), vec![0u8; 4096]).expect("Failed to write file");
+    // A pointer whose object was never fetched
+    fs::write(
+        media.join("pointer.bin"),
+        "version https://git-lfs.github.com/spec/v1\n\
+         oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\n\
+         size 12345\n",
+    )
+    .expect("Failed to write file");
+    for args in [vec!["add", "."], vec!["commit", "-m", "Add assets"]] {
+        Command::new("git")
+            .args(&args)
+            .current_dir(&repo)
+            .output()
+            .expect("Failed to run git");
+    }
+
+    let root = temp.path().to_str().unwrap();
+    let output = run_with_args(&["--json", "--no-cache", root]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let lfs = &json["repositories"][0]["lfs"];
+    assert_eq!(lfs["plain"], serde_json::json!(["media/plain.bin"]));
+    assert_eq!(lfs["missing"], serde_json::json!(["media/pointer.bin"]));
+
+    let output = run_with_args(&["-v", "--no-cache", "--where", "lfs_plain > 0", root]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("LFS: 1 missing, 1 plain blob"), "{stderr}");
+    assert!(stderr.contains("LFS problems: 1"), "{stderr}");
+
+    // The object is found in a store moved by lfs.storage
+    let oid = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+    let objects = repo.join(".git/media-store/objects/4d/7a");
+    fs::create_dir_all(&objects).expect("Failed to create dir");
+    fs::write(objects.join(oid), "content").expect("Failed to write file");
+    git(&repo, &["config", "lfs.storage", "media-store"]);
+    let output = run_with_args(&["--json", "--no-cache", root]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let lfs = &json["repositories"][0]["lfs"];
+    assert!(lfs.get("missing").is_none(), "{lfs}");
+}
+
+#[test]
+fn test_lfs_flag_bypasses_status_cache() {
+    let temp = create_temp_git_repo("lfs_cache_test", false);
+    let home = tempfile::tempdir().expect("Failed to create temp dir");
+    let root = temp.path().to_str().unwrap();
+    let run = |extra: &[&str]| {
+        let output = binary_in(home.path())
+            .args(["--json", root])
+            .args(extra)
+            .output()
+            .expect("Failed to execute binary");
+        let json: serde_json::Value =
+            serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+        json["repositories"][0].clone()
+    };
+
+    assert!(run(&[]).get("lfs").is_none());
+    // The cached status from the first run doesn't know the LFS state
+    assert!(run(&["--lfs"]).get("lfs").is_some());
+}
+
+#[test]
+fn test_file_warnings() {
+    let temp = create_temp_git_repo("api", false);
+    let repo = temp.path().join("api");
+    fs::write(repo.join(".env"), "TOKEN=secret").expect("Failed to write file");
+    fs::create_dir_all(repo.join("backups")).expect("Failed to create dir");
+    fs::write(repo.join("backups/dump.sql"), vec![0u8; 4096]).expect("Failed to write file");
+    fs::write(repo.join("deploy.yml"), "x").expect("Failed to write file");
+
+    let root = temp.path().to_str().unwrap();
+    let args = [
+        "--json",
+        "--no-cache",
+        "--large-file-size",
+        "2KiB",
+        "--secret-pattern",
+        "deploy.*",
+        root,
+    ];
+    let output = run_with_args(&args);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let warnings = json["repositories"][0]["file_warnings"].as_array().unwrap();
+    let flagged: Vec<(&str, &str)> = warnings
+        .iter()
+        .map(|w| (w["path"].as_str().unwrap(), w["reason"].as_str().unwrap()))
+        .collect();
+    assert_eq!(flagged.len(), 3, "{flagged:?}");
+    assert!(flagged.contains(&(".env", "secret")));
+    assert!(flagged.contains(&("backups/dump.sql", "large")));
+    assert!(flagged.contains(&("deploy.yml", "secret")));
+
+    let output = run_with_args(&["--no-cache", "--large-file-size", "2KiB", root]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(".env (matches .env)"), "{stderr}");
+    assert!(stderr.contains("backups/dump.sql (4.1 kB)"), "{stderr}");
+    assert!(stderr.contains("Secret-looking files: 1"), "{stderr}");
+
+    let output = run_with_args(&["--secret-pattern", "[", root]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_housekeeping_and_gc() {
+    let temp = create_temp_git_repo("app", false);
+    let repo = temp.path().join("app");
+    // The initial commit's loose objects are over this threshold
+    Command::new("git")
+        .args(["config", "gc.auto", "1"])
+        .current_dir(&repo)
+        .output()
+        .expect("Failed to config git");
+    let url = format!("file://{}", repo.display());
+    Command::new("git")
+        .args(["clone", "-q", "--depth", "1", &url, "shallow"])
+        .current_dir(temp.path())
+        .output()
+        .expect("Failed to clone");
+
+    let root = temp.path().to_str().unwrap();
+    let output = run_with_args(&["--json", "--no-cache", "--housekeeping", root, "2"]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let repos = json["repositories"].as_array().unwrap();
+    let app = repos
+        .iter()
+        .find(|r| r["path"].as_str().unwrap().ends_with("app"))
+        .unwrap();
+    assert_eq!(app["housekeeping"]["needs_gc"], true);
+    assert!(app["housekeeping"]["loose_objects"].as_u64().unwrap() >= 3);
+    assert!(app["housekeeping"]["git_size"].as_u64().unwrap() > 0);
+    let shallow = repos
+        .iter()
+        .find(|r| r["path"].as_str().unwrap().ends_with("shallow"))
+        .unwrap();
+    assert_eq!(shallow["housekeeping"]["shallow"], true);
+
+    let output = run_with_args(&["--no-cache", "--gc", "--where", "name == app", root, "2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Ran git gc in 1 repo"), "{stderr}");
+    assert!(stderr.contains("0 loose objects, 1 pack"), "{stderr}");
+    assert!(!stderr.contains("[needs gc]"), "{stderr}");
+}
+
+#[test]
+fn test_diff_stats() {
+    let temp = create_temp_git_repo("big", false);
+    let big = temp.path().join("big");
+    let lines: String = (0..10).map(|i| format!("line {i}\n")).collect();
+    fs::write(big.join("staged.txt"), &lines).expect("Failed to write file");
+    Command::new("git")
+        .args(["add", "staged.txt"])
+        .current_dir(&big)
+        .output()
+        .expect("Failed to add file");
+    fs::write(big.join("test.txt"), "changed\n").expect("Failed to write file");
+
+    // Staged files in a repo without commits are diffed against nothing
+    let small = temp.path().join("small");
+    fs::create_dir_all(&small).expect("Failed to create dir");
+    Command::new("git")
+        .args(["init", "-q"])
+        .current_dir(&small)
+        .output()
+        .expect("Failed to init git repo");
+    fs::write(small.join("new.txt"), "x\n").expect("Failed to write file");
+    Command::new("git")
+        .args(["add", "new.txt"])
+        .current_dir(&small)
+        .output()
+        .expect("Failed to add file");
+
+    let root = temp.path().to_str().unwrap();
+    let output = run_with_args(&["--json", "--no-cache", "--sort", "diff-size", root]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let repos = json["repositories"].as_array().unwrap();
+    assert!(repos[0]["path"].as_str().unwrap().ends_with("big"));
+    let diff = &repos[0]["diff"];
+    assert_eq!(diff["staged"]["files"], 1);
+    assert_eq!(diff["staged"]["added"], 10);
+    assert_eq!(diff["unstaged"]["added"], 1);
+    assert_eq!(diff["unstaged"]["removed"], 1);
+    assert_eq!(repos[1]["diff"]["staged"]["added"], 1);
+
+    let output = run_with_args(&["-v", "--no-cache", root]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("big +11 -1"), "{stderr}");
+}
+
+#[test]
+fn test_conflicts() {
+    let temp = create_temp_git_repo("merging", false);
+    let repo = temp.path().join("merging");
+    git(&repo, &["checkout", "-q", "-b", "other"]);
+    fs::write(repo.join("test.txt"), "theirs").expect("Failed to write file");
+    git(&repo, &["commit", "-q", "-am", "Theirs"]);
+    git(&repo, &["checkout", "-q", "-"]);
+    fs::write(repo.join("test.txt"), "ours").expect("Failed to write file");
+    git(&repo, &["commit", "-q", "-am", "Ours"]);
+    // The merge stops at the conflict
+    git_command(&repo)
+        .args(["merge", "-q", "other"])
+        .output()
+        .expect("Failed to run git");
+    // A plain dirty repo that sorts before it by path
+    let plain = temp.path().join("aaa");
+    fs::create_dir_all(&plain).expect("Failed to create dir");
+    Command::new("git")
+        .args(["init", "-q"])
+        .current_dir(&plain)
+        .output()
+        .expect("Failed to init git repo");
+    fs::write(plain.join("new.txt"), "x").expect("Failed to write file");
+
+    let root = temp.path().to_str().unwrap();
+    let output = run_with_args(&["--json", "--no-cache", root]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let repos = json["repositories"].as_array().unwrap();
+    assert!(repos[0]["path"].as_str().unwrap().ends_with("merging"));
+    assert_eq!(repos[0]["conflicts"], serde_json::json!(["test.txt"]));
+    assert!(repos[1].get("conflicts").is_none());
+
+    let output = run_with_args(&["-v", "--no-cache", "--exit-code", "bitmask", root]);
+    assert_eq!(output.status.code(), Some(1 | 32));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("merging [1 conflict]"), "{stderr}");
+    assert!(stderr.contains("UU test.txt"), "{stderr}");
+    assert!(stderr.contains("Repos with conflicts: 1"), "{stderr}");
+}
+
+#[test]
+fn test_default_branch_divergence() {
+    let temp = create_temp_git_repo("source", false);
+    let source = temp.path().join("source");
+    git(&source, &["branch", "-M", "main"]);
+    for clone in ["done", "fresh", "feature"] {
+        git(temp.path(), &["clone", "-q", "source", clone]);
+    }
+    // A branch whose commit was merged into main
+    let done = temp.path().join("done");
+    git(&done, &["checkout", "-q", "-b", "done"]);
+    git(&done, &["commit", "--allow-empty", "-m", "Done"]);
+    git(&source, &["fetch", "-q", "../done", "done"]);
+    git(
+        &source,
+        &["merge", "-q", "--no-ff", "-m", "Merge done", "FETCH_HEAD"],
+    );
+    git(&done, &["fetch", "-q", "origin"]);
+    // A branch just created, with nothing of its own yet
+    let fresh = temp.path().join("fresh");
+    git(&fresh, &["checkout", "-q", "-b", "fresh"]);
+    git(&fresh, &["fetch", "-q", "origin"]);
+    // A branch with work of its own
+    let feature = temp.path().join("feature");
+    git(&feature, &["checkout", "-q", "-b", "feature"]);
+    git(&feature, &["commit", "--allow-empty", "-m", "Feature"]);
+
+    let root = temp.path().to_str().unwrap();
+    let output = run_with_args(&["--json", "--no-cache", root]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let repo = |name: &str| {
+        json["repositories"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|r| r["path"].as_str().unwrap().ends_with(name))
+            .cloned()
+            .expect("Repo not found")
+    };
+    assert_eq!(
+        repo("done")["default_branch"],
+        serde_json::json!({ "name": "origin/main", "ahead": 0, "behind": 1, "merged": true })
+    );
+    assert_eq!(
+        repo("fresh")["default_branch"],
+        serde_json::json!({ "name": "origin/main", "ahead": 0, "behind": 2 })
+    );
+    assert_eq!(repo("feature")["default_branch"]["ahead"], 1);
+    assert!(repo("source").get("default_branch").is_none());
+
+    let output = run_with_args(&["--json", "--no-cache", "--merged", root]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let repos = json["repositories"].as_array().unwrap();
+    assert_eq!(repos.len(), 1);
+    assert!(repos[0]["path"].as_str().unwrap().ends_with("done"));
+
+    // Uncommitted work means the branch is still in use
+    fs::write(done.join("wip.txt"), "x").expect("Failed to write file");
+    let output = run_with_args(&["-q", "--no-cache", "--merged", root]);
+    assert_eq!(output.status.code(), Some(0));
+    fs::remove_file(done.join("wip.txt")).expect("Failed to remove file");
+
+    let output = run_with_args(&["-v", "--no-cache", "--merged", root]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("done [merged]"), "{stderr}");
+    assert!(
+        stderr.contains("vs origin/main: 0 ahead, 1 behind, merged"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("Merged branches: 1"), "{stderr}");
+}